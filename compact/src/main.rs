@@ -0,0 +1,242 @@
+//! Converts the `.eh_frame` section of an ELF file into a compact unwind table.
+//!
+//! Usage: `unwinding-compact <input ELF> <output>`
+//!
+//! Code whose CFI can't be expressed compactly is marked as such in the table, and is unwound
+//! using DWARF CFI at runtime; the `.eh_frame` section therefore still needs to be available if
+//! the table is not complete.
+
+use std::error::Error;
+use std::{env, fs, mem, process};
+
+use gimli::{
+    AArch64, BaseAddresses, CfaRule, CieOrFde, EhFrame, Register, RegisterRule, RiscV,
+    RunTimeEndian, UnwindContext, UnwindSection, Vendor, X86, X86_64,
+};
+use object::{Architecture, Object, ObjectSection};
+use unwinding::compact_unwind::*;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// The registers of an architecture the compact format refers to.
+struct Registers {
+    sp: Register,
+    fp: Register,
+    ra: Register,
+}
+
+fn registers(arch: Architecture) -> Option<Registers> {
+    let (sp, fp, ra) = match arch {
+        Architecture::X86_64 => (X86_64::RSP, X86_64::RBP, X86_64::RA),
+        Architecture::I386 => (X86::ESP, X86::EBP, X86::RA),
+        Architecture::Aarch64 => (AArch64::SP, AArch64::X29, AArch64::X30),
+        Architecture::Riscv32 | Architecture::Riscv64 => (RiscV::SP, RiscV::S0, RiscV::RA),
+        _ => return None,
+    };
+    Some(Registers { sp, fp, ra })
+}
+
+const UNDEFINED: CompactEntry = CompactEntry {
+    pc: 0,
+    cfa_off: 0,
+    ra_off: RA_IN_REGISTER,
+    fp_off: FP_UNCHANGED,
+    cfa_reg: CFA_UNDEFINED,
+    flags: 0,
+    reserved: 0,
+};
+
+/// Converts a row of the unwind table, if it only describes the CFA, return address and frame
+/// pointer.
+fn convert_row(regs: &Registers, row: &gimli::UnwindTableRow<usize>) -> Option<CompactEntry> {
+    if row.saved_args_size() != 0 {
+        return None;
+    }
+    let (cfa_reg, cfa_off) = match *row.cfa() {
+        CfaRule::RegisterAndOffset { register, offset } if register == regs.sp => (CFA_SP, offset),
+        CfaRule::RegisterAndOffset { register, offset } if register == regs.fp => (CFA_FP, offset),
+        _ => return None,
+    };
+    let mut entry = CompactEntry {
+        cfa_off: i32::try_from(cfa_off).ok()?,
+        cfa_reg,
+        ..UNDEFINED
+    };
+
+    let offset = |offset: i64| i16::try_from(offset).ok().filter(|&x| x != i16::MIN);
+    for &(reg, ref rule) in row.registers() {
+        match *rule {
+            RegisterRule::Offset(off) if reg == regs.ra => entry.ra_off = offset(off)?,
+            RegisterRule::Offset(off) if reg == regs.fp => entry.fp_off = offset(off)?,
+            // The return address is never the same value as the PC of its caller.
+            RegisterRule::SameValue if reg != regs.ra => (),
+            _ => return None,
+        }
+    }
+    Some(entry)
+}
+
+fn same_layout(a: &CompactEntry, b: &CompactEntry) -> bool {
+    a.cfa_reg == b.cfa_reg && a.cfa_off == b.cfa_off && a.ra_off == b.ra_off && a.fp_off == b.fp_off
+}
+
+/// Builds the table from the FDEs of `eh_frame`, as (link-time address, entry) pairs.
+fn convert(
+    eh_frame: &EhFrame<gimli::EndianSlice<'_, RunTimeEndian>>,
+    bases: &BaseAddresses,
+    regs: &Registers,
+) -> Result<Vec<(u64, CompactEntry)>> {
+    let mut ranges = Vec::new();
+    let mut ctx = UnwindContext::new();
+    let mut entries = eh_frame.entries(bases);
+    while let Some(entry) = entries.next()? {
+        let CieOrFde::Fde(partial) = entry else {
+            continue;
+        };
+        let fde = partial.parse(EhFrame::cie_from_offset)?;
+        let start = fde.initial_address();
+        let end = start + fde.len();
+        if start == end {
+            continue;
+        }
+
+        let mut rows = Vec::new();
+        // Frames with landing pads need their personality routine, and signal trampolines
+        // restore every register; leave those to DWARF CFI.
+        if fde.lsda().is_none() && fde.personality().is_none() && !fde.is_signal_trampoline() {
+            let mut table = fde.rows(eh_frame, bases, &mut ctx)?;
+            while let Some(row) = table.next_row()? {
+                let entry = convert_row(regs, row).unwrap_or(UNDEFINED);
+                rows.push((row.start_address(), entry));
+            }
+        }
+        if rows.is_empty() {
+            rows.push((start, UNDEFINED));
+        }
+        rows[0].1.flags |= FLAG_FUNCTION_START;
+        ranges.push((start, end, rows));
+    }
+    ranges.sort_by_key(|&(start, ..)| start);
+
+    // FDEs of code discarded by the linker may be left behind, overlapping real ones.
+    let mut last_end = 0;
+    ranges.retain(|&(start, end, _)| {
+        let keep = start >= last_end;
+        if keep {
+            last_end = end;
+        }
+        keep
+    });
+
+    let mut table: Vec<(u64, CompactEntry)> = Vec::new();
+    for (i, (_, end, rows)) in ranges.iter().enumerate() {
+        for &(pc, entry) in rows {
+            // Consecutive entries describing the same frame layout are merged, unless they
+            // start a new function.
+            if let Some((_, last)) = table.last() {
+                if entry.flags & FLAG_FUNCTION_START == 0 && same_layout(last, &entry) {
+                    continue;
+                }
+            }
+            table.push((pc, entry));
+        }
+        if ranges.get(i + 1).map_or(true, |&(next, ..)| next != *end) {
+            table.push((*end, UNDEFINED));
+        }
+    }
+    Ok(table)
+}
+
+fn write_table(table: &[(u64, CompactEntry)], endian: RunTimeEndian) -> Result<Vec<u8>> {
+    let base = table.first().map_or(0, |&(pc, _)| pc);
+    let mut entries = Vec::with_capacity(table.len());
+    for &(pc, entry) in table {
+        let pc = u32::try_from(pc - base).map_err(|_| "text is too large for a compact table")?;
+        entries.push(CompactEntry { pc, ..entry });
+    }
+    let functions: Vec<u32> = entries
+        .iter()
+        .filter(|entry| entry.flags & FLAG_FUNCTION_START != 0)
+        .map(|entry| entry.pc)
+        .collect();
+    let header = CompactTable {
+        base,
+        entries: &entries,
+        functions: &functions,
+    }
+    .header();
+
+    let mut out = Vec::with_capacity(
+        mem::size_of::<CompactHeader>()
+            + entries.len() * mem::size_of::<CompactEntry>()
+            + functions.len() * mem::size_of::<u32>(),
+    );
+    macro_rules! put {
+        ($($value:expr),*) => {
+            $(out.extend_from_slice(&match endian {
+                RunTimeEndian::Little => $value.to_le_bytes(),
+                RunTimeEndian::Big => $value.to_be_bytes(),
+            });)*
+        };
+    }
+    out.extend_from_slice(&header.magic);
+    put!(
+        header.version,
+        header.reserved,
+        header.len,
+        header.functions,
+        header.base
+    );
+    for entry in &entries {
+        put!(entry.pc, entry.cfa_off, entry.ra_off, entry.fp_off);
+        out.extend_from_slice(&[entry.cfa_reg, entry.flags]);
+        put!(entry.reserved);
+    }
+    for function in &functions {
+        put!(function);
+    }
+    Ok(out)
+}
+
+fn run(input: &str, output: &str) -> Result<()> {
+    let data = fs::read(input)?;
+    let file = object::File::parse(&*data)?;
+    let regs = registers(file.architecture()).ok_or("unsupported architecture")?;
+    let endian = if file.is_little_endian() {
+        RunTimeEndian::Little
+    } else {
+        RunTimeEndian::Big
+    };
+
+    let section = file
+        .section_by_name(".eh_frame")
+        .ok_or("no .eh_frame section")?;
+    let mut eh_frame = EhFrame::new(section.data()?, endian);
+    eh_frame.set_address_size(if file.is_64() { 8 } else { 4 });
+    if file.architecture() == Architecture::Aarch64 {
+        eh_frame.set_vendor(Vendor::AArch64);
+    }
+    let mut bases = BaseAddresses::default().set_eh_frame(section.address());
+    if let Some(text) = file.section_by_name(".text") {
+        bases = bases.set_text(text.address());
+    }
+    if let Some(got) = file.section_by_name(".got") {
+        bases = bases.set_got(got.address());
+    }
+
+    let table = convert(&eh_frame, &bases, &regs)?;
+    fs::write(output, write_table(&table, endian)?)?;
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("usage: {} <input ELF> <output>", args[0]);
+        process::exit(2);
+    }
+    if let Err(err) = run(&args[1], &args[2]) {
+        eprintln!("{}: {}", args[1], err);
+        process::exit(1);
+    }
+}
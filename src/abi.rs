@@ -3,7 +3,7 @@ use core::ops;
 
 use crate::util::*;
 
-#[cfg(not(feature = "unwinder"))]
+#[cfg(all(not(feature = "unwinder"), not(target_arch = "arm")))]
 use crate::arch::Arch;
 #[cfg(feature = "unwinder")]
 pub use crate::unwinder::*;
@@ -23,6 +23,8 @@ impl UnwindReasonCode {
     pub const HANDLER_FOUND: Self = Self(6);
     pub const INSTALL_CONTEXT: Self = Self(7);
     pub const CONTINUE_UNWIND: Self = Self(8);
+    /// ARM EHABI reports all errors with this code.
+    pub const FAILURE: Self = Self(9);
 }
 
 #[repr(transparent)]
@@ -58,18 +60,61 @@ impl UnwindAction {
     }
 }
 
+/// What ARM EHABI personality routines are asked to do, in place of `UnwindAction`.
+#[cfg(target_arch = "arm")]
+#[repr(transparent)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct UnwindState(pub c_int);
+
+#[cfg(target_arch = "arm")]
+impl UnwindState {
+    pub const VIRTUAL_UNWIND_FRAME: Self = Self(0);
+    pub const UNWIND_FRAME_STARTING: Self = Self(1);
+    pub const UNWIND_FRAME_RESUME: Self = Self(2);
+    pub const ACTION_MASK: Self = Self(3);
+    pub const FORCE_UNWIND: Self = Self(8);
+    pub const END_OF_STACK: Self = Self(16);
+
+    #[inline]
+    pub const fn action(&self) -> Self {
+        Self(self.0 & Self::ACTION_MASK.0)
+    }
+
+    #[inline]
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+#[cfg(target_arch = "arm")]
+impl ops::BitOr for UnwindState {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
 pub type UnwindExceptionCleanupFn = unsafe extern "C" fn(UnwindReasonCode, *mut UnwindException);
 
+/// The exception class as passed to stop functions. ARM EHABI defines it as an array, so it is
+/// passed by pointer.
+#[cfg(not(target_arch = "arm"))]
+pub type UnwindExceptionClass = u64;
+#[cfg(target_arch = "arm")]
+pub type UnwindExceptionClass = *const [u8; 8];
+
 pub type UnwindStopFn = unsafe extern "C" fn(
     c_int,
     UnwindAction,
-    u64,
+    UnwindExceptionClass,
     *mut UnwindException,
     &mut UnwindContext<'_>,
     *mut c_void,
 ) -> UnwindReasonCode;
 
-#[cfg(not(feature = "unwinder"))]
+#[cfg(all(not(feature = "unwinder"), not(target_arch = "arm")))]
 #[repr(C)]
 pub struct UnwindException {
     pub exception_class: u64,
@@ -77,6 +122,40 @@ pub struct UnwindException {
     private: [usize; Arch::UNWIND_PRIVATE_DATA_SIZE],
 }
 
+/// The `_Unwind_Control_Block` of ARM EHABI. Its caches are shared between the unwinder and the
+/// personality routines, so its layout is fixed whichever unwinder is used.
+#[cfg(target_arch = "arm")]
+#[repr(C)]
+pub struct UnwindException {
+    pub exception_class: u64,
+    pub exception_cleanup: Option<UnwindExceptionCleanupFn>,
+    pub(crate) unwinder_cache: [usize; 5],
+    pub(crate) barrier_cache: BarrierCache,
+    pub(crate) cleanup_cache: [usize; 4],
+    pub(crate) pr_cache: PrCache,
+}
+
+#[cfg(target_arch = "arm")]
+#[repr(C)]
+pub(crate) struct BarrierCache {
+    /// The stack pointer of the frame with the handler, recorded by its personality routine in
+    /// phase 1.
+    pub sp: usize,
+    pub bitpattern: [usize; 5],
+}
+
+#[cfg(target_arch = "arm")]
+#[repr(C)]
+pub(crate) struct PrCache {
+    /// The start of the function of the frame being unwound.
+    pub fnstart: usize,
+    /// Its exception handling table entry.
+    pub ehtp: *const u32,
+    /// Bit 0 is set if the entry is in `.ARM.exidx` rather than `.ARM.extab`.
+    pub additional: usize,
+    pub reserved: usize,
+}
+
 pub type UnwindTraceFn =
     extern "C" fn(ctx: &UnwindContext<'_>, arg: *mut c_void) -> UnwindReasonCode;
 
@@ -87,6 +166,7 @@ pub struct UnwindContext<'a> {
     phantom: core::marker::PhantomData<&'a ()>,
 }
 
+#[cfg(not(target_arch = "arm"))]
 pub type PersonalityRoutine = unsafe extern "C" fn(
     c_int,
     UnwindAction,
@@ -95,6 +175,56 @@ pub type PersonalityRoutine = unsafe extern "C" fn(
     &mut UnwindContext<'_>,
 ) -> UnwindReasonCode;
 
+#[cfg(target_arch = "arm")]
+pub type PersonalityRoutine = unsafe extern "C" fn(
+    UnwindState,
+    *mut UnwindException,
+    &mut UnwindContext<'_>,
+) -> UnwindReasonCode;
+
+/// Register classes of the virtual register set of ARM EHABI.
+#[cfg(target_arch = "arm")]
+#[repr(transparent)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct UnwindVrsRegClass(pub c_int);
+
+#[cfg(target_arch = "arm")]
+impl UnwindVrsRegClass {
+    pub const CORE: Self = Self(0);
+    pub const VFP: Self = Self(1);
+    pub const WMMXD: Self = Self(3);
+    pub const WMMXC: Self = Self(4);
+}
+
+/// How registers are represented in memory by `_Unwind_VRS_Get`, `_Unwind_VRS_Set` and
+/// `_Unwind_VRS_Pop`.
+#[cfg(target_arch = "arm")]
+#[repr(transparent)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct UnwindVrsDataRepresentation(pub c_int);
+
+#[cfg(target_arch = "arm")]
+impl UnwindVrsDataRepresentation {
+    pub const UINT32: Self = Self(0);
+    /// Doubles saved with `FSTMFDX`, followed by a padding word.
+    pub const VFPX: Self = Self(1);
+    pub const UINT64: Self = Self(3);
+    pub const FLOAT: Self = Self(4);
+    pub const DOUBLE: Self = Self(5);
+}
+
+#[cfg(target_arch = "arm")]
+#[repr(transparent)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct UnwindVrsResult(pub c_int);
+
+#[cfg(target_arch = "arm")]
+impl UnwindVrsResult {
+    pub const OK: Self = Self(0);
+    pub const NOT_IMPLEMENTED: Self = Self(1);
+    pub const FAILED: Self = Self(2);
+}
+
 #[cfg(not(feature = "unwinder"))]
 macro_rules! binding {
     () => {};
@@ -130,6 +260,7 @@ macro_rules! binding {
     };
 }
 
+#[cfg(any(feature = "unwinder", not(target_arch = "arm")))]
 binding! {
     extern "C" fn _Unwind_GetGR(unwind_ctx: &UnwindContext<'_>, index: c_int) -> usize;
     extern "C" fn _Unwind_GetCFA(unwind_ctx: &UnwindContext<'_>) -> usize;
@@ -147,6 +278,9 @@ binding! {
         unwind_ctx: &mut UnwindContext<'_>,
         value: usize,
     );
+}
+
+binding! {
     extern "C" fn _Unwind_GetLanguageSpecificData(unwind_ctx: &UnwindContext<'_>) -> *mut c_void;
     extern "C" fn _Unwind_GetRegionStart(unwind_ctx: &UnwindContext<'_>) -> usize;
     extern "C" fn _Unwind_GetTextRelBase(unwind_ctx: &UnwindContext<'_>) -> usize;
@@ -170,3 +304,83 @@ binding! {
         trace_argument: *mut c_void,
     ) -> UnwindReasonCode;
 }
+
+#[cfg(target_arch = "arm")]
+binding! {
+    extern "C" fn _Unwind_VRS_Get(
+        unwind_ctx: &UnwindContext<'_>,
+        class: UnwindVrsRegClass,
+        regno: u32,
+        representation: UnwindVrsDataRepresentation,
+        value: *mut c_void,
+    ) -> UnwindVrsResult;
+    extern "C" fn _Unwind_VRS_Set(
+        unwind_ctx: &mut UnwindContext<'_>,
+        class: UnwindVrsRegClass,
+        regno: u32,
+        representation: UnwindVrsDataRepresentation,
+        value: *mut c_void,
+    ) -> UnwindVrsResult;
+    unsafe extern "C" fn __gnu_unwind_frame(
+        exception: *mut UnwindException,
+        unwind_ctx: &mut UnwindContext<'_>,
+    ) -> UnwindReasonCode;
+}
+
+// libgcc only provides these as inline functions for ARM EHABI.
+#[cfg(all(not(feature = "unwinder"), target_arch = "arm"))]
+mod ehabi {
+    use super::*;
+
+    const SP: c_int = 13;
+    const PC: c_int = 15;
+
+    #[allow(non_snake_case)]
+    pub fn _Unwind_GetGR(unwind_ctx: &UnwindContext<'_>, index: c_int) -> usize {
+        let mut value = 0usize;
+        _Unwind_VRS_Get(
+            unwind_ctx,
+            UnwindVrsRegClass::CORE,
+            index as _,
+            UnwindVrsDataRepresentation::UINT32,
+            &mut value as *mut usize as _,
+        );
+        value
+    }
+
+    #[allow(non_snake_case)]
+    pub fn _Unwind_GetCFA(unwind_ctx: &UnwindContext<'_>) -> usize {
+        _Unwind_GetGR(unwind_ctx, SP)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn _Unwind_SetGR(unwind_ctx: &mut UnwindContext<'_>, index: c_int, mut value: usize) {
+        _Unwind_VRS_Set(
+            unwind_ctx,
+            UnwindVrsRegClass::CORE,
+            index as _,
+            UnwindVrsDataRepresentation::UINT32,
+            &mut value as *mut usize as _,
+        );
+    }
+
+    // The Thumb bit is not part of the address.
+    #[allow(non_snake_case)]
+    pub fn _Unwind_GetIP(unwind_ctx: &UnwindContext<'_>) -> usize {
+        _Unwind_GetGR(unwind_ctx, PC) & !1
+    }
+
+    #[allow(non_snake_case)]
+    pub fn _Unwind_GetIPInfo(unwind_ctx: &UnwindContext<'_>, ip_before_insn: &mut c_int) -> usize {
+        *ip_before_insn = 0;
+        _Unwind_GetIP(unwind_ctx)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn _Unwind_SetIP(unwind_ctx: &mut UnwindContext<'_>, value: usize) {
+        let thumb = _Unwind_GetGR(unwind_ctx, PC) & 1;
+        _Unwind_SetGR(unwind_ctx, PC, value | thumb);
+    }
+}
+#[cfg(all(not(feature = "unwinder"), target_arch = "arm"))]
+pub use ehabi::*;
@@ -7,6 +7,7 @@ mod x86_64 {
     #[allow(unused)]
     impl Arch {
         pub const SP: Register = X86_64::RSP;
+        pub const FP: Register = X86_64::RBP;
         pub const RA: Register = X86_64::RA;
 
         pub const UNWIND_DATA_REG: (Register, Register) = (X86_64::RAX, X86_64::RDX);
@@ -25,6 +26,7 @@ mod x86 {
     #[allow(unused)]
     impl Arch {
         pub const SP: Register = X86::ESP;
+        pub const FP: Register = X86::EBP;
         pub const RA: Register = X86::RA;
 
         pub const UNWIND_DATA_REG: (Register, Register) = (X86::EAX, X86::EDX);
@@ -43,6 +45,7 @@ mod riscv {
     #[allow(unused)]
     impl Arch {
         pub const SP: Register = RiscV::SP;
+        pub const FP: Register = RiscV::S0;
         pub const RA: Register = RiscV::RA;
 
         pub const UNWIND_DATA_REG: (Register, Register) = (RiscV::A0, RiscV::A1);
@@ -61,6 +64,7 @@ mod aarch64 {
     #[allow(unused)]
     impl Arch {
         pub const SP: Register = AArch64::SP;
+        pub const FP: Register = AArch64::X29;
         pub const RA: Register = AArch64::X30;
 
         pub const UNWIND_DATA_REG: (Register, Register) = (AArch64::X0, AArch64::X1);
@@ -70,11 +74,93 @@ mod aarch64 {
 #[cfg(target_arch = "aarch64")]
 pub use aarch64::*;
 
+#[cfg(target_arch = "loongarch64")]
+mod loongarch64 {
+    use gimli::{LoongArch, Register};
+
+    pub struct Arch;
+
+    #[allow(unused)]
+    impl Arch {
+        pub const SP: Register = LoongArch::SP;
+        pub const FP: Register = LoongArch::FP;
+        pub const RA: Register = LoongArch::RA;
+
+        pub const UNWIND_DATA_REG: (Register, Register) = (LoongArch::A0, LoongArch::A1);
+        pub const UNWIND_PRIVATE_DATA_SIZE: usize = 2;
+    }
+}
+#[cfg(target_arch = "loongarch64")]
+pub use loongarch64::*;
+
+#[cfg(target_arch = "powerpc64")]
+mod powerpc64 {
+    use gimli::{PowerPc64, Register};
+
+    pub struct Arch;
+
+    #[allow(unused)]
+    impl Arch {
+        pub const SP: Register = PowerPc64::R1;
+        pub const FP: Register = PowerPc64::R31;
+        pub const RA: Register = PowerPc64::LR;
+
+        pub const UNWIND_DATA_REG: (Register, Register) = (PowerPc64::R3, PowerPc64::R4);
+        pub const UNWIND_PRIVATE_DATA_SIZE: usize = 2;
+    }
+}
+#[cfg(target_arch = "powerpc64")]
+pub use powerpc64::*;
+
+#[cfg(target_arch = "s390x")]
+mod s390x {
+    use gimli::Register;
+
+    pub struct Arch;
+
+    // gimli doesn't name the s390x registers.
+    #[allow(unused)]
+    impl Arch {
+        pub const SP: Register = Register(15);
+        pub const FP: Register = Register(11);
+        pub const RA: Register = Register(14);
+
+        pub const UNWIND_DATA_REG: (Register, Register) = (Register(6), Register(7));
+        pub const UNWIND_PRIVATE_DATA_SIZE: usize = 2;
+    }
+}
+#[cfg(target_arch = "s390x")]
+pub use s390x::*;
+
+#[cfg(target_arch = "arm")]
+mod arm {
+    use gimli::{Arm, Register};
+
+    pub struct Arch;
+
+    #[allow(unused)]
+    impl Arch {
+        pub const SP: Register = Arm::SP;
+        pub const FP: Register = Arm::R11;
+        pub const RA: Register = Arm::LR;
+
+        pub const UNWIND_DATA_REG: (Register, Register) = (Arm::R0, Arm::R1);
+    }
+}
+// Only personality routines need it without the unwinder.
+#[cfg(target_arch = "arm")]
+#[allow(unused_imports)]
+pub use arm::*;
+
 #[cfg(not(any(
     target_arch = "x86_64",
     target_arch = "x86",
     target_arch = "riscv64",
     target_arch = "riscv32",
-    target_arch = "aarch64"
+    target_arch = "aarch64",
+    target_arch = "loongarch64",
+    target_arch = "powerpc64",
+    target_arch = "s390x",
+    target_arch = "arm"
 )))]
 compile_error!("Current architecture is not supported");
@@ -0,0 +1,149 @@
+//! A compact unwind table format, precomputed from DWARF CFI on the host.
+//!
+//! The table describes, for ranges of code, how to find the canonical frame address (CFA) from the
+//! stack pointer or frame pointer, and where the return address and frame pointer are saved
+//! relative to it. Code whose unwind information can't be expressed this way, e.g. because it
+//! saves other registers or needs a personality routine, is marked as such and unwound using
+//! DWARF CFI instead.
+//!
+//! Tables are produced by the `unwinding-compact` tool in this repository, and installed with
+//! [`set_compact_unwind_table`].
+//!
+//! A table consists of a [`CompactHeader`], the [`CompactEntry`]s sorted by PC, and the sorted
+//! offsets of the functions they describe, all in the byte order of the target. The offsets let
+//! the start of the function covering a PC be found without scanning its entries.
+
+use core::mem;
+use core::slice;
+
+/// The magic bytes a compact unwind table starts with.
+pub const MAGIC: [u8; 4] = *b"UWCT";
+
+/// The current version of the format.
+pub const VERSION: u16 = 2;
+
+/// The header of a compact unwind table.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CompactHeader {
+    /// [`MAGIC`].
+    pub magic: [u8; 4],
+    /// [`VERSION`].
+    pub version: u16,
+    pub reserved: u16,
+    /// Number of entries following the header.
+    pub len: u32,
+    /// Number of function offsets following the entries.
+    pub functions: u32,
+    /// The link-time address the PCs of the entries are relative to.
+    pub base: u64,
+}
+
+/// The CFA is the stack pointer plus `cfa_off`.
+pub const CFA_SP: u8 = 0;
+/// The CFA is the frame pointer plus `cfa_off`.
+pub const CFA_FP: u8 = 1;
+/// The code has no compact unwind information; use DWARF CFI.
+pub const CFA_UNDEFINED: u8 = 2;
+
+/// The return address has not been saved, and is still in its register.
+pub const RA_IN_REGISTER: i16 = i16::MIN;
+/// The frame pointer has not been saved, and still has the caller's value.
+pub const FP_UNCHANGED: i16 = i16::MIN;
+
+/// The entry is at the start of a function.
+pub const FLAG_FUNCTION_START: u8 = 1;
+
+/// Describes the code from its `pc` up to the `pc` of the next entry.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CompactEntry {
+    /// Offset from [`CompactHeader::base`].
+    pub pc: u32,
+    pub cfa_off: i32,
+    /// Offset from the CFA where the return address is saved, or [`RA_IN_REGISTER`].
+    pub ra_off: i16,
+    /// Offset from the CFA where the frame pointer is saved, or [`FP_UNCHANGED`].
+    pub fp_off: i16,
+    /// One of [`CFA_SP`], [`CFA_FP`] or [`CFA_UNDEFINED`].
+    pub cfa_reg: u8,
+    /// A combination of `FLAG_*` values.
+    pub flags: u8,
+    pub reserved: u16,
+}
+
+/// A validated compact unwind table.
+#[derive(Clone, Copy, Debug)]
+pub struct CompactTable<'a> {
+    pub base: u64,
+    pub entries: &'a [CompactEntry],
+    /// The offsets from `base` of the functions described by `entries`, sorted.
+    pub functions: &'a [u32],
+}
+
+impl<'a> CompactTable<'a> {
+    /// Checks the header of `table` and returns its entries.
+    ///
+    /// Returns `None` if `table` is not a compact unwind table of the current version, is
+    /// truncated or is not aligned to 8 bytes.
+    pub fn parse(table: &'a [u8]) -> Option<Self> {
+        if table
+            .as_ptr()
+            .align_offset(mem::align_of::<CompactHeader>())
+            != 0
+            || table.len() < mem::size_of::<CompactHeader>()
+        {
+            return None;
+        }
+        let header = unsafe { &*(table.as_ptr() as *const CompactHeader) };
+        if header.magic != MAGIC || header.version != VERSION {
+            return None;
+        }
+        let len = header.len as usize;
+        let functions = header.functions as usize;
+        let entries = &table[mem::size_of::<CompactHeader>()..];
+        if entries.len() / mem::size_of::<CompactEntry>() < len {
+            return None;
+        }
+        let offsets = &entries[len * mem::size_of::<CompactEntry>()..];
+        if offsets.len() / mem::size_of::<u32>() < functions {
+            return None;
+        }
+        Some(Self {
+            base: header.base,
+            entries: unsafe { slice::from_raw_parts(entries.as_ptr() as *const CompactEntry, len) },
+            functions: unsafe { slice::from_raw_parts(offsets.as_ptr() as *const u32, functions) },
+        })
+    }
+
+    /// Returns the header describing this table.
+    pub fn header(&self) -> CompactHeader {
+        CompactHeader {
+            magic: MAGIC,
+            version: VERSION,
+            reserved: 0,
+            len: self.entries.len() as u32,
+            functions: self.functions.len() as u32,
+            base: self.base,
+        }
+    }
+
+    /// Finds the entry covering the link-time address `pc`, along with the start of its function.
+    pub fn lookup(&self, pc: u64) -> Option<(&'a CompactEntry, u64)> {
+        let offset = u32::try_from(pc.checked_sub(self.base)?).ok()?;
+        let idx = match self.entries.binary_search_by_key(&offset, |entry| entry.pc) {
+            Ok(idx) => idx,
+            Err(0) => return None,
+            Err(idx) => idx - 1,
+        };
+        let entry = &self.entries[idx];
+        let start = match self.functions.partition_point(|&start| start <= offset) {
+            0 => entry.pc,
+            idx => self.functions[idx - 1],
+        };
+        Some((entry, self.base + start as u64))
+    }
+}
+
+#[cfg(feature = "unwinder")]
+pub use crate::unwinder::{set_compact_unwind_table, SetCompactUnwindTableError};
@@ -0,0 +1,161 @@
+use core::fmt;
+use core::ops;
+use gimli::{Arm, Register};
+
+#[repr(C)]
+#[derive(Clone, Default)]
+pub struct Context {
+    pub r: [usize; 16],
+    pub d: [u64; 32],
+}
+
+impl fmt::Debug for Context {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut fmt = fmt.debug_struct("Context");
+        for i in 0..=15 {
+            fmt.field(Arm::register_name(Register(i as _)).unwrap(), &self.r[i]);
+        }
+        for i in 0..=31 {
+            fmt.field(
+                Arm::register_name(Register((i + 256) as _)).unwrap(),
+                &self.d[i],
+            );
+        }
+        fmt.finish()
+    }
+}
+
+impl ops::Index<Register> for Context {
+    type Output = usize;
+
+    fn index(&self, reg: Register) -> &usize {
+        match reg {
+            Register(0..=15) => &self.r[reg.0 as usize],
+            _ => unimplemented!(),
+        }
+    }
+}
+
+impl ops::IndexMut<gimli::Register> for Context {
+    fn index_mut(&mut self, reg: Register) -> &mut usize {
+        match reg {
+            Register(0..=15) => &mut self.r[reg.0 as usize],
+            _ => unimplemented!(),
+        }
+    }
+}
+
+// Only instructions available in Thumb-1 are used, so the same code assembles for ARM, Thumb-2 and
+// ARMv6-M.
+macro_rules! save {
+    ($($fp:ident)?) => {
+        // No need to save caller-saved registers here.
+        core::arch::naked_asm!(
+            "
+            push {{r4, lr}}
+            sub sp, sp, #320
+            mov r2, r0
+            mov r3, sp
+            str r4, [r3, #0x10]
+            str r5, [r3, #0x14]
+            str r6, [r3, #0x18]
+            str r7, [r3, #0x1C]
+            mov r4, r8
+            str r4, [r3, #0x20]
+            mov r4, r9
+            str r4, [r3, #0x24]
+            mov r4, r10
+            str r4, [r3, #0x28]
+            mov r4, r11
+            str r4, [r3, #0x2C]
+            add r4, sp, #328
+            str r4, [r3, #0x34]
+            ldr r4, [sp, #324]
+            str r4, [r3, #0x38]
+            str r4, [r3, #0x3C]
+            ",
+            save!(maybesavefp($($fp)?)),
+            "
+            mov r0, r3
+            blx r2
+
+            add sp, sp, #320
+            pop {{r4, pc}}
+            ",
+        );
+    };
+    (maybesavefp(fp)) => {
+        "
+        add r4, r3, #0x80
+        vstmia r4, {{d8-d15}}
+        "
+    };
+    (maybesavefp()) => { "" };
+}
+
+#[naked]
+pub extern "C-unwind" fn save_context(f: extern "C" fn(&mut Context, *mut ()), ptr: *mut ()) {
+    unsafe {
+        #[cfg(target_feature = "vfp2")]
+        save!(fp);
+        #[cfg(not(target_feature = "vfp2"))]
+        save!();
+    }
+}
+
+macro_rules! restore {
+    (mayberestore(fp)) => {
+        "
+        add r1, r0, #0x80
+        vldmia r1, {{d8-d15}}
+        "
+    };
+    (mayberestore()) => { "" };
+    ($ctx:expr$(, $fp:ident)?) => {
+        // r0 and pc are popped off the new stack at last, so they are written just below it.
+        core::arch::asm!(
+            restore!(mayberestore($($fp)?)),
+            "
+            ldr r1, [r0, #0x34]
+            subs r1, #8
+            ldr r2, [r0, #0x00]
+            str r2, [r1, #0]
+            ldr r2, [r0, #0x3C]
+            str r2, [r1, #4]
+            ldr r2, [r0, #0x20]
+            mov r8, r2
+            ldr r2, [r0, #0x24]
+            mov r9, r2
+            ldr r2, [r0, #0x28]
+            mov r10, r2
+            ldr r2, [r0, #0x2C]
+            mov r11, r2
+            ldr r2, [r0, #0x30]
+            mov r12, r2
+            ldr r2, [r0, #0x38]
+            mov lr, r2
+            mov sp, r1
+
+            ldr r1, [r0, #0x04]
+            ldr r2, [r0, #0x08]
+            ldr r3, [r0, #0x0C]
+            ldr r4, [r0, #0x10]
+            ldr r5, [r0, #0x14]
+            ldr r6, [r0, #0x18]
+            ldr r7, [r0, #0x1C]
+            pop {{r0, pc}}
+            ",
+            in("r0") $ctx,
+            options(noreturn)
+        );
+    };
+}
+
+pub unsafe fn restore_context(ctx: &Context) -> ! {
+    unsafe {
+        #[cfg(target_feature = "vfp2")]
+        restore!(ctx, fp);
+        #[cfg(not(target_feature = "vfp2"))]
+        restore!(ctx);
+    }
+}
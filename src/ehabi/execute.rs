@@ -0,0 +1,226 @@
+//! The unwind instructions of EHABI, and the personality routines that only run them.
+
+use super::arch::*;
+use super::UnwindContext;
+use crate::abi::*;
+
+const SP: usize = 13;
+const LR: usize = 14;
+const PC: usize = 15;
+
+/// The bytes of unwind instructions, packed most significant byte first into words.
+pub struct Instructions {
+    data: u32,
+    next: *const u32,
+    bytes_left: u32,
+    words_left: u32,
+}
+
+impl Instructions {
+    /// Instructions starting with the `bytes` low bytes of `*ptr`, and carrying on for `words`
+    /// more words.
+    pub unsafe fn new(ptr: *const u32, bytes: u32, words: u32) -> Self {
+        Self {
+            data: unsafe { *ptr } << (32 - bytes * 8),
+            next: unsafe { ptr.add(1) },
+            bytes_left: bytes,
+            words_left: words,
+        }
+    }
+
+    unsafe fn next(&mut self) -> u8 {
+        if self.bytes_left == 0 {
+            // Running out of instructions means finish.
+            if self.words_left == 0 {
+                return 0xb0;
+            }
+            self.words_left -= 1;
+            self.data = unsafe { *self.next };
+            self.next = unsafe { self.next.add(1) };
+            self.bytes_left = 4;
+        }
+        self.bytes_left -= 1;
+        let byte = (self.data >> 24) as u8;
+        self.data <<= 8;
+        byte
+    }
+}
+
+/// Pops the core registers in `mask` off the stack. The stack pointer is left as popped if it is
+/// in the mask.
+pub unsafe fn pop_core(ctx: &mut Context, mask: u32) {
+    let mut vsp = ctx.r[SP] as *const usize;
+    for i in 0..16 {
+        if mask & (1 << i) != 0 {
+            ctx.r[i] = unsafe { *vsp };
+            vsp = unsafe { vsp.add(1) };
+        }
+    }
+    if mask & (1 << SP) == 0 {
+        ctx.r[SP] = vsp as usize;
+    }
+}
+
+/// Pops `count` doubles into the VFP registers from `start`. Those saved with `FSTMFDX` are
+/// followed by a padding word.
+pub unsafe fn pop_vfp(
+    ctx: &mut Context,
+    start: usize,
+    count: usize,
+    fstmx: bool,
+) -> Result<(), ()> {
+    if start + count > ctx.d.len() {
+        return Err(());
+    }
+    let mut vsp = ctx.r[SP] as *const u64;
+    for i in start..start + count {
+        ctx.d[i] = unsafe { vsp.read_unaligned() };
+        vsp = unsafe { vsp.add(1) };
+    }
+    ctx.r[SP] = vsp as usize + if fstmx { 4 } else { 0 };
+    Ok(())
+}
+
+/// Unwinds a frame by running its unwind instructions.
+pub unsafe fn execute(ctx: &mut Context, mut insns: Instructions) -> Result<(), ()> {
+    let mut pc_popped = false;
+    loop {
+        let op = unsafe { insns.next() };
+        match op {
+            0x00..=0x3f => ctx.r[SP] = ctx.r[SP].wrapping_add(((op as usize & 0x3f) << 2) + 4),
+            0x40..=0x7f => ctx.r[SP] = ctx.r[SP].wrapping_sub(((op as usize & 0x3f) << 2) + 4),
+            0x80..=0x8f => {
+                let mask = ((op as u32 & 0xf) << 8 | unsafe { insns.next() } as u32) << 4;
+                // Refuse to unwind.
+                if mask == 0 {
+                    return Err(());
+                }
+                unsafe { pop_core(ctx, mask) };
+                pc_popped |= mask & (1 << PC) != 0;
+            }
+            0x90..=0x9f => {
+                let reg = op as usize & 0xf;
+                if reg == SP || reg == PC {
+                    return Err(());
+                }
+                ctx.r[SP] = ctx.r[reg];
+            }
+            0xa0..=0xaf => {
+                let mut mask = ((1 << ((op & 7) + 1)) - 1) << 4;
+                if op & 8 != 0 {
+                    mask |= 1 << LR;
+                }
+                unsafe { pop_core(ctx, mask) };
+            }
+            0xb0 => break,
+            0xb1 => {
+                let mask = unsafe { insns.next() } as u32;
+                if mask == 0 || mask & 0xf0 != 0 {
+                    return Err(());
+                }
+                unsafe { pop_core(ctx, mask) };
+            }
+            0xb2 => {
+                let mut offset = 0;
+                let mut shift = 0;
+                loop {
+                    let byte = unsafe { insns.next() };
+                    offset |= (byte as usize & 0x7f) << shift;
+                    shift += 7;
+                    if byte & 0x80 == 0 || shift >= usize::BITS {
+                        break;
+                    }
+                }
+                ctx.r[SP] = ctx.r[SP].wrapping_add(0x204 + (offset << 2));
+            }
+            0xb3 | 0xc8 | 0xc9 => {
+                let regs = unsafe { insns.next() } as usize;
+                let start = (regs >> 4) + if op == 0xc8 { 16 } else { 0 };
+                unsafe { pop_vfp(ctx, start, (regs & 0xf) + 1, op == 0xb3)? };
+            }
+            0xb8..=0xbf => unsafe { pop_vfp(ctx, 8, (op as usize & 7) + 1, true)? },
+            0xd0..=0xd7 => unsafe { pop_vfp(ctx, 8, (op as usize & 7) + 1, false)? },
+            // Spare, or iWMMXt registers, which are not supported.
+            _ => return Err(()),
+        }
+    }
+
+    if !pc_popped {
+        ctx.r[PC] = ctx.r[LR];
+    }
+    Ok(())
+}
+
+/// Unwinds a frame whose personality routine follows the GCC layout of `.ARM.extab` entries: the
+/// personality routine, then a word with the count of further words of unwind instructions in its
+/// top byte and the first three bytes of instructions, then the LSDA.
+#[no_mangle]
+pub unsafe extern "C" fn __gnu_unwind_frame(
+    exception: *mut UnwindException,
+    unwind_ctx: &mut UnwindContext<'_>,
+) -> UnwindReasonCode {
+    let ehtp = unsafe { (*exception).pr_cache.ehtp.add(1) };
+    let insns = unsafe { Instructions::new(ehtp, 3, *ehtp >> 24) };
+    match unsafe { execute(unwind_ctx.ctx, insns) } {
+        Ok(()) => UnwindReasonCode::NO_REASON,
+        Err(()) => UnwindReasonCode::FAILURE,
+    }
+}
+
+unsafe fn unwind_cpp_pr(
+    exception: *mut UnwindException,
+    unwind_ctx: &mut UnwindContext<'_>,
+    index: u32,
+) -> UnwindReasonCode {
+    let ehtp = unsafe { (*exception).pr_cache.ehtp };
+    let (insns, descriptors) = unsafe {
+        if index == 0 {
+            (Instructions::new(ehtp, 3, 0), ehtp.add(1))
+        } else {
+            let words = (*ehtp >> 16) & 0xff;
+            (
+                Instructions::new(ehtp, 2, words),
+                ehtp.add(1 + words as usize),
+            )
+        }
+    };
+
+    // Entries in `.ARM.extab` are followed by a list of the cleanups and handlers in the frame.
+    // GCC and LLVM always leave it empty, as they use personality routines of their own for them,
+    // so running them is not supported.
+    if unsafe { (*exception).pr_cache.additional } & 1 == 0 && unsafe { *descriptors } != 0 {
+        return UnwindReasonCode::FAILURE;
+    }
+
+    match unsafe { execute(unwind_ctx.ctx, insns) } {
+        Ok(()) => UnwindReasonCode::CONTINUE_UNWIND,
+        Err(()) => UnwindReasonCode::FAILURE,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn __aeabi_unwind_cpp_pr0(
+    _state: UnwindState,
+    exception: *mut UnwindException,
+    unwind_ctx: &mut UnwindContext<'_>,
+) -> UnwindReasonCode {
+    unsafe { unwind_cpp_pr(exception, unwind_ctx, 0) }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn __aeabi_unwind_cpp_pr1(
+    _state: UnwindState,
+    exception: *mut UnwindException,
+    unwind_ctx: &mut UnwindContext<'_>,
+) -> UnwindReasonCode {
+    unsafe { unwind_cpp_pr(exception, unwind_ctx, 1) }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn __aeabi_unwind_cpp_pr2(
+    _state: UnwindState,
+    exception: *mut UnwindException,
+    unwind_ctx: &mut UnwindContext<'_>,
+) -> UnwindReasonCode {
+    unsafe { unwind_cpp_pr(exception, unwind_ctx, 2) }
+}
@@ -0,0 +1,116 @@
+//! Lookup of the `.ARM.exidx` entry covering an address.
+
+#[cfg(any(feature = "fde-static", feature = "fde-phdr-dl"))]
+use core::slice;
+
+/// An entry of `.ARM.exidx`: the start of a function, and either an inline entry, a reference to
+/// one in `.ARM.extab`, or `EXIDX_CANTUNWIND`.
+pub type IndexEntry = [u32; 2];
+
+pub struct Entry {
+    pub fnstart: usize,
+    /// The second word of the index entry.
+    pub content: *const u32,
+}
+
+/// Decodes a place-relative 31-bit offset.
+pub unsafe fn prel31(ptr: *const u32) -> usize {
+    let offset = ((unsafe { *ptr } as i32) << 1) >> 1;
+    (ptr as usize).wrapping_add(offset as usize)
+}
+
+pub fn find_entry(pc: usize) -> Option<Entry> {
+    #[cfg(feature = "fde-static")]
+    if let Some(entry) = search(static_table(), pc) {
+        return Some(entry);
+    }
+    #[cfg(feature = "fde-phdr-dl")]
+    if let Some(entry) = search_dl_phdr(pc) {
+        return Some(entry);
+    }
+    #[cfg(feature = "fde-registry")]
+    if let Some(entry) = super::registry::find_entry(pc) {
+        return Some(entry);
+    }
+    None
+}
+
+// Entries are sorted by the start of the function, and each one covers the code up to the next.
+#[cfg_attr(
+    not(any(
+        feature = "fde-static",
+        feature = "fde-phdr-dl",
+        feature = "fde-registry"
+    )),
+    allow(dead_code)
+)]
+pub fn search(table: &'static [IndexEntry], pc: usize) -> Option<Entry> {
+    let idx = table.partition_point(|e| unsafe { prel31(&e[0]) } <= pc);
+    let entry = &table[idx.checked_sub(1)?];
+    Some(Entry {
+        fnstart: unsafe { prel31(&entry[0]) },
+        content: &entry[1],
+    })
+}
+
+#[cfg(feature = "fde-static")]
+fn static_table() -> &'static [IndexEntry] {
+    extern "C" {
+        static __exidx_start: IndexEntry;
+        static __exidx_end: IndexEntry;
+    }
+
+    unsafe {
+        let start = &__exidx_start as *const IndexEntry;
+        let end = &__exidx_end as *const IndexEntry;
+        slice::from_raw_parts(start, end.offset_from(start) as usize)
+    }
+}
+
+#[cfg(feature = "fde-phdr-dl")]
+fn search_dl_phdr(pc: usize) -> Option<Entry> {
+    use crate::util::*;
+    use core::ffi::c_void;
+    use libc::{dl_iterate_phdr, dl_phdr_info, PT_LOAD};
+
+    const PT_ARM_EXIDX: u32 = 0x70000001;
+
+    struct CallbackData {
+        pc: usize,
+        result: Option<Entry>,
+    }
+
+    unsafe extern "C" fn phdr_callback(
+        info: *mut dl_phdr_info,
+        _size: usize,
+        data: *mut c_void,
+    ) -> c_int {
+        unsafe {
+            let data = &mut *(data as *mut CallbackData);
+            let base = (*info).dlpi_addr as usize;
+            let phdrs = slice::from_raw_parts((*info).dlpi_phdr, (*info).dlpi_phnum as usize);
+
+            let contains_pc = phdrs.iter().any(|phdr| {
+                let start = base + phdr.p_vaddr as usize;
+                let end = start + phdr.p_memsz as usize;
+                phdr.p_type == PT_LOAD && (start..end).contains(&data.pc)
+            });
+            if !contains_pc {
+                return 0;
+            }
+
+            if let Some(phdr) = phdrs.iter().find(|x| x.p_type == PT_ARM_EXIDX) {
+                let table = slice::from_raw_parts(
+                    (base + phdr.p_vaddr as usize) as *const IndexEntry,
+                    phdr.p_memsz as usize / core::mem::size_of::<IndexEntry>(),
+                );
+                data.result = search(table, data.pc);
+            }
+            1
+        }
+    }
+
+    let mut data = CallbackData { pc, result: None };
+    unsafe { dl_iterate_phdr(Some(phdr_callback), &mut data as *mut CallbackData as _) };
+    data.result
+}
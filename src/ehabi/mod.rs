@@ -0,0 +1,524 @@
+//! The unwinder for 32-bit ARM, which has an exception handling ABI of its own (EHABI) in place of
+//! DWARF CFI. Frames are described by `.ARM.exidx` and `.ARM.extab`, and it is up to the
+//! personality routine of each frame to unwind it.
+
+mod arch;
+mod execute;
+mod exidx;
+#[cfg(feature = "fde-registry")]
+mod registry;
+
+use core::ffi::c_void;
+use core::mem::{self, MaybeUninit};
+use core::ptr;
+
+use crate::abi::*;
+use crate::util::*;
+use arch::*;
+use execute::{pop_core, pop_vfp};
+
+pub use execute::{
+    __aeabi_unwind_cpp_pr0, __aeabi_unwind_cpp_pr1, __aeabi_unwind_cpp_pr2, __gnu_unwind_frame,
+};
+
+#[cfg(any(
+    feature = "fde-phdr-aux",
+    feature = "fde-phdr-raw",
+    feature = "fde-gnu-eh-frame-hdr",
+    feature = "fde-static-regions",
+    feature = "fde-debug-frame",
+    feature = "fde-custom",
+    feature = "fde-compact",
+    feature = "frame-stepper",
+    feature = "stack-switch",
+    feature = "trap-frame",
+    feature = "signal-safe"
+))]
+compile_error!("Only `fde-phdr-dl`, `fde-static` and `fde-registry` are supported with ARM EHABI");
+
+#[cfg(feature = "fde-registry")]
+pub mod exidx_registry {
+    pub use super::exidx::IndexEntry;
+    pub use super::registry::{deregister_exidx, register_exidx};
+}
+
+const SP: usize = 13;
+const PC: usize = 15;
+
+// The slots of `unwinder_cache`, as libgcc uses them.
+const FORCED_STOP_FN: usize = 0;
+const PR_ADDR: usize = 1;
+const FORCED_STOP_ARG: usize = 3;
+
+fn with_context<T, F: FnOnce(&mut Context) -> T>(f: F) -> T {
+    use core::mem::ManuallyDrop;
+
+    union Data<T, F> {
+        f: ManuallyDrop<F>,
+        t: ManuallyDrop<T>,
+    }
+
+    extern "C" fn delegate<T, F: FnOnce(&mut Context) -> T>(ctx: &mut Context, ptr: *mut ()) {
+        // SAFETY: This function is called exactly once; it extracts the function, call it and
+        // store the return value. This function is `extern "C"` so we don't need to worry about
+        // unwinding past it.
+        unsafe {
+            let data = &mut *ptr.cast::<Data<T, F>>();
+            let t = ManuallyDrop::take(&mut data.f)(ctx);
+            data.t = ManuallyDrop::new(t);
+        }
+    }
+
+    let mut data = Data {
+        f: ManuallyDrop::new(f),
+    };
+    save_context(delegate::<T, F>, ptr::addr_of_mut!(data).cast());
+    unsafe { ManuallyDrop::into_inner(data.t) }
+}
+
+pub struct UnwindContext<'a> {
+    ctx: &'a mut Context,
+    exception: *mut UnwindException,
+}
+
+#[no_mangle]
+pub extern "C" fn _Unwind_VRS_Get(
+    unwind_ctx: &UnwindContext<'_>,
+    class: UnwindVrsRegClass,
+    regno: u32,
+    representation: UnwindVrsDataRepresentation,
+    value: *mut c_void,
+) -> UnwindVrsResult {
+    let regno = regno as usize;
+    match (class, representation) {
+        (UnwindVrsRegClass::CORE, UnwindVrsDataRepresentation::UINT32) => {
+            if regno > PC {
+                return UnwindVrsResult::FAILED;
+            }
+            unsafe { *(value as *mut usize) = unwind_ctx.ctx.r[regno] };
+        }
+        (
+            UnwindVrsRegClass::VFP,
+            UnwindVrsDataRepresentation::DOUBLE | UnwindVrsDataRepresentation::VFPX,
+        ) => {
+            if regno >= unwind_ctx.ctx.d.len() {
+                return UnwindVrsResult::FAILED;
+            }
+            unsafe { (value as *mut u64).write_unaligned(unwind_ctx.ctx.d[regno]) };
+        }
+        _ => return UnwindVrsResult::NOT_IMPLEMENTED,
+    }
+    UnwindVrsResult::OK
+}
+
+#[no_mangle]
+pub extern "C" fn _Unwind_VRS_Set(
+    unwind_ctx: &mut UnwindContext<'_>,
+    class: UnwindVrsRegClass,
+    regno: u32,
+    representation: UnwindVrsDataRepresentation,
+    value: *mut c_void,
+) -> UnwindVrsResult {
+    let regno = regno as usize;
+    match (class, representation) {
+        (UnwindVrsRegClass::CORE, UnwindVrsDataRepresentation::UINT32) => {
+            if regno > PC {
+                return UnwindVrsResult::FAILED;
+            }
+            unwind_ctx.ctx.r[regno] = unsafe { *(value as *const usize) };
+        }
+        (
+            UnwindVrsRegClass::VFP,
+            UnwindVrsDataRepresentation::DOUBLE | UnwindVrsDataRepresentation::VFPX,
+        ) => {
+            if regno >= unwind_ctx.ctx.d.len() {
+                return UnwindVrsResult::FAILED;
+            }
+            unwind_ctx.ctx.d[regno] = unsafe { (value as *const u64).read_unaligned() };
+        }
+        _ => return UnwindVrsResult::NOT_IMPLEMENTED,
+    }
+    UnwindVrsResult::OK
+}
+
+/// Pops registers off the stack of the frame. For core registers, `discriminator` is a mask of
+/// them; for VFP registers, the first register is in its top half and the count in the bottom.
+#[no_mangle]
+pub unsafe extern "C" fn _Unwind_VRS_Pop(
+    unwind_ctx: &mut UnwindContext<'_>,
+    class: UnwindVrsRegClass,
+    discriminator: u32,
+    representation: UnwindVrsDataRepresentation,
+) -> UnwindVrsResult {
+    match (class, representation) {
+        (UnwindVrsRegClass::CORE, UnwindVrsDataRepresentation::UINT32) => {
+            unsafe { pop_core(unwind_ctx.ctx, discriminator & 0xffff) };
+            UnwindVrsResult::OK
+        }
+        (
+            UnwindVrsRegClass::VFP,
+            UnwindVrsDataRepresentation::DOUBLE | UnwindVrsDataRepresentation::VFPX,
+        ) => {
+            let start = (discriminator >> 16) as usize;
+            let count = (discriminator & 0xffff) as usize;
+            let fstmx = representation == UnwindVrsDataRepresentation::VFPX;
+            match unsafe { pop_vfp(unwind_ctx.ctx, start, count, fstmx) } {
+                Ok(()) => UnwindVrsResult::OK,
+                Err(()) => UnwindVrsResult::FAILED,
+            }
+        }
+        _ => UnwindVrsResult::NOT_IMPLEMENTED,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn _Unwind_GetGR(unwind_ctx: &UnwindContext<'_>, index: c_int) -> usize {
+    unwind_ctx.ctx.r[index as usize]
+}
+
+#[no_mangle]
+pub extern "C" fn _Unwind_GetCFA(unwind_ctx: &UnwindContext<'_>) -> usize {
+    unwind_ctx.ctx.r[SP]
+}
+
+#[no_mangle]
+pub extern "C" fn _Unwind_SetGR(unwind_ctx: &mut UnwindContext<'_>, index: c_int, value: usize) {
+    unwind_ctx.ctx.r[index as usize] = value;
+}
+
+// The Thumb bit is not part of the address.
+#[no_mangle]
+pub extern "C" fn _Unwind_GetIP(unwind_ctx: &UnwindContext<'_>) -> usize {
+    unwind_ctx.ctx.r[PC] & !1
+}
+
+#[no_mangle]
+pub extern "C" fn _Unwind_GetIPInfo(
+    unwind_ctx: &UnwindContext<'_>,
+    ip_before_insn: &mut c_int,
+) -> usize {
+    *ip_before_insn = 0;
+    _Unwind_GetIP(unwind_ctx)
+}
+
+#[no_mangle]
+pub extern "C" fn _Unwind_SetIP(unwind_ctx: &mut UnwindContext<'_>, value: usize) {
+    unwind_ctx.ctx.r[PC] = value | (unwind_ctx.ctx.r[PC] & 1);
+}
+
+// The LSDA follows the personality routine and the unwind instructions of `.ARM.extab` entries
+// in the GCC layout. Entries for the personality routines of EHABI itself have none.
+#[no_mangle]
+pub extern "C" fn _Unwind_GetLanguageSpecificData(unwind_ctx: &UnwindContext<'_>) -> *mut c_void {
+    unsafe {
+        let pr_cache = &(*unwind_ctx.exception).pr_cache;
+        let ehtp = pr_cache.ehtp;
+        if pr_cache.additional & 1 != 0 || *ehtp & 0x8000_0000 != 0 {
+            return ptr::null_mut();
+        }
+        ehtp.add(2 + (*ehtp.add(1) >> 24) as usize) as _
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn _Unwind_GetRegionStart(unwind_ctx: &UnwindContext<'_>) -> usize {
+    unsafe { (*unwind_ctx.exception).pr_cache.fnstart }
+}
+
+#[no_mangle]
+pub extern "C" fn _Unwind_GetTextRelBase(_unwind_ctx: &UnwindContext<'_>) -> usize {
+    0
+}
+
+#[no_mangle]
+pub extern "C" fn _Unwind_GetDataRelBase(_unwind_ctx: &UnwindContext<'_>) -> usize {
+    0
+}
+
+#[no_mangle]
+pub extern "C" fn _Unwind_FindEnclosingFunction(pc: *mut c_void) -> *mut c_void {
+    exidx::find_entry(pc as usize - 1)
+        .map(|e| e.fnstart as _)
+        .unwrap_or_default()
+}
+
+/// Looks up the frame at `ctx`, and caches its entry in the UCB as personality routines expect.
+unsafe fn find_personality(
+    exception: *mut UnwindException,
+    ctx: &Context,
+) -> Result<PersonalityRoutine, UnwindReasonCode> {
+    const EXIDX_CANTUNWIND: u32 = 1;
+
+    // The return address may be just past the end of the function if it ends with a call. The
+    // instruction before it is at least 2 bytes long, even in Thumb code.
+    let entry =
+        exidx::find_entry(ctx.r[PC].wrapping_sub(2)).ok_or(UnwindReasonCode::END_OF_STACK)?;
+    let content = unsafe { *entry.content };
+    if content == EXIDX_CANTUNWIND {
+        return Err(UnwindReasonCode::END_OF_STACK);
+    }
+
+    // Compact entries are either inline, or in `.ARM.extab`.
+    let (ehtp, additional) = if content & 0x8000_0000 != 0 {
+        (entry.content, 1)
+    } else {
+        (unsafe { exidx::prel31(entry.content) } as *const u32, 0)
+    };
+    let first = unsafe { *ehtp };
+    let personality = if first & 0x8000_0000 != 0 {
+        match first >> 24 {
+            0x80 => __aeabi_unwind_cpp_pr0 as PersonalityRoutine,
+            0x81 => __aeabi_unwind_cpp_pr1,
+            0x82 => __aeabi_unwind_cpp_pr2,
+            _ => return Err(UnwindReasonCode::FAILURE),
+        }
+    } else {
+        unsafe { mem::transmute::<usize, PersonalityRoutine>(exidx::prel31(ehtp)) }
+    };
+
+    unsafe {
+        (*exception).pr_cache.fnstart = entry.fnstart;
+        (*exception).pr_cache.ehtp = ehtp;
+        (*exception).pr_cache.additional = additional;
+        (*exception).unwinder_cache[PR_ADDR] = personality as usize;
+    }
+    Ok(personality)
+}
+
+#[inline(never)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn _Unwind_RaiseException(
+    exception: *mut UnwindException,
+) -> UnwindReasonCode {
+    with_context(|saved_ctx| {
+        unsafe { (*exception).unwinder_cache[FORCED_STOP_FN] = 0 };
+
+        // Phase 1: Search for handler
+        let mut ctx = saved_ctx.clone();
+        loop {
+            let personality = match unsafe { find_personality(exception, &ctx) } {
+                Ok(v) => v,
+                Err(code) => return code,
+            };
+            let code = unsafe {
+                personality(
+                    UnwindState::VIRTUAL_UNWIND_FRAME,
+                    exception,
+                    &mut UnwindContext {
+                        ctx: &mut ctx,
+                        exception,
+                    },
+                )
+            };
+            match code {
+                UnwindReasonCode::CONTINUE_UNWIND => (),
+                UnwindReasonCode::HANDLER_FOUND => break,
+                _ => return UnwindReasonCode::FAILURE,
+            }
+        }
+
+        let code = raise_exception_phase2(exception, saved_ctx);
+        match code {
+            UnwindReasonCode::INSTALL_CONTEXT => unsafe { restore_context(saved_ctx) },
+            _ => code,
+        }
+    })
+}
+
+// Personality routines unwind the frames themselves, as well as running their cleanups. The one of
+// the frame with the handler found in phase 1 finds it again.
+fn raise_exception_phase2(exception: *mut UnwindException, ctx: &mut Context) -> UnwindReasonCode {
+    loop {
+        let personality = match unsafe { find_personality(exception, ctx) } {
+            Ok(v) => v,
+            Err(_) => return UnwindReasonCode::FAILURE,
+        };
+        let code = unsafe {
+            personality(
+                UnwindState::UNWIND_FRAME_STARTING,
+                exception,
+                &mut UnwindContext { ctx, exception },
+            )
+        };
+        match code {
+            UnwindReasonCode::CONTINUE_UNWIND => (),
+            UnwindReasonCode::INSTALL_CONTEXT => return UnwindReasonCode::INSTALL_CONTEXT,
+            _ => return UnwindReasonCode::FAILURE,
+        }
+    }
+}
+
+#[inline(never)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn _Unwind_ForcedUnwind(
+    exception: *mut UnwindException,
+    stop: UnwindStopFn,
+    stop_arg: *mut c_void,
+) -> UnwindReasonCode {
+    with_context(|ctx| {
+        unsafe {
+            (*exception).unwinder_cache[FORCED_STOP_FN] = stop as usize;
+            (*exception).unwinder_cache[FORCED_STOP_ARG] = stop_arg as usize;
+        }
+
+        let code = force_unwind_phase2(exception, ctx);
+        match code {
+            UnwindReasonCode::INSTALL_CONTEXT => unsafe { restore_context(ctx) },
+            _ => code,
+        }
+    })
+}
+
+fn force_unwind_phase2(exception: *mut UnwindException, ctx: &mut Context) -> UnwindReasonCode {
+    let (stop, stop_arg) = unsafe {
+        (
+            mem::transmute::<usize, UnwindStopFn>((*exception).unwinder_cache[FORCED_STOP_FN]),
+            (*exception).unwinder_cache[FORCED_STOP_ARG] as *mut c_void,
+        )
+    };
+    loop {
+        let personality = unsafe { find_personality(exception, ctx) };
+
+        // As with libgcc, the stop function is called once more with `END_OF_STACK` when there are
+        // no frames left.
+        let code = unsafe {
+            stop(
+                1,
+                UnwindAction::FORCE_UNWIND
+                    | UnwindAction::CLEANUP_PHASE
+                    | if personality.is_err() {
+                        UnwindAction::END_OF_STACK
+                    } else {
+                        UnwindAction::empty()
+                    },
+                ptr::addr_of!((*exception).exception_class).cast(),
+                exception,
+                &mut UnwindContext { ctx, exception },
+                stop_arg,
+            )
+        };
+        match code {
+            UnwindReasonCode::NO_REASON => (),
+            _ => return UnwindReasonCode::FAILURE,
+        }
+
+        let personality = match personality {
+            Ok(v) => v,
+            Err(code) => return code,
+        };
+        let code = unsafe {
+            personality(
+                UnwindState::UNWIND_FRAME_STARTING | UnwindState::FORCE_UNWIND,
+                exception,
+                &mut UnwindContext { ctx, exception },
+            )
+        };
+        match code {
+            UnwindReasonCode::CONTINUE_UNWIND => (),
+            UnwindReasonCode::INSTALL_CONTEXT => return UnwindReasonCode::INSTALL_CONTEXT,
+            _ => return UnwindReasonCode::FAILURE,
+        }
+    }
+}
+
+// Unwinding carries on from `_Unwind_Resume`, through the frame of the landing pad, whose personality
+// routine finds nothing to do at the call.
+#[inline(never)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn _Unwind_Resume(exception: *mut UnwindException) -> ! {
+    with_context(|ctx| {
+        let code = match unsafe { (*exception).unwinder_cache[FORCED_STOP_FN] } {
+            0 => raise_exception_phase2(exception, ctx),
+            _ => force_unwind_phase2(exception, ctx),
+        };
+        assert!(code == UnwindReasonCode::INSTALL_CONTEXT);
+
+        unsafe { restore_context(ctx) }
+    })
+}
+
+#[inline(never)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn _Unwind_Resume_or_Rethrow(
+    exception: *mut UnwindException,
+) -> UnwindReasonCode {
+    if unsafe { (*exception).unwinder_cache[FORCED_STOP_FN] } == 0 {
+        return unsafe { _Unwind_RaiseException(exception) };
+    }
+
+    with_context(|ctx| {
+        let code = force_unwind_phase2(exception, ctx);
+        assert!(code == UnwindReasonCode::INSTALL_CONTEXT);
+
+        unsafe { restore_context(ctx) }
+    })
+}
+
+/// Called by the personality routine of C++ when a handler is done with an exception, so that
+/// the unwinder can free what it keeps for it. Nothing is kept here.
+#[no_mangle]
+pub extern "C" fn _Unwind_Complete(_exception: *mut UnwindException) {}
+
+#[no_mangle]
+pub unsafe extern "C" fn _Unwind_DeleteException(exception: *mut UnwindException) {
+    if let Some(cleanup) = unsafe { (*exception).exception_cleanup } {
+        unsafe { cleanup(UnwindReasonCode::FOREIGN_EXCEPTION_CAUGHT, exception) };
+    }
+}
+
+#[inline(never)]
+#[no_mangle]
+pub extern "C-unwind" fn _Unwind_Backtrace(
+    trace: UnwindTraceFn,
+    trace_argument: *mut c_void,
+) -> UnwindReasonCode {
+    with_context(|ctx| {
+        let mut ctx = ctx.clone();
+        let mut skipping = cfg!(feature = "hide-trace");
+
+        // Personality routines find the entry of the frame in the UCB, so a backtrace needs one
+        // too. They are asked to unwind the frame with forced unwinding, so they don't stop at
+        // handlers.
+        let mut ucb = MaybeUninit::<UnwindException>::zeroed();
+        let exception = ucb.as_mut_ptr();
+        // Without the Thumb bit.
+        let this_fn = _Unwind_Backtrace as extern "C-unwind" fn(_, _) -> _ as usize & !1;
+
+        loop {
+            let personality = match unsafe { find_personality(exception, &ctx) } {
+                Ok(v) => v,
+                Err(code) => return code,
+            };
+            if !skipping {
+                let code = trace(
+                    &UnwindContext {
+                        ctx: &mut ctx,
+                        exception,
+                    },
+                    trace_argument,
+                );
+                match code {
+                    UnwindReasonCode::NO_REASON => (),
+                    _ => return UnwindReasonCode::FAILURE,
+                }
+            } else if unsafe { (*exception).pr_cache.fnstart } == this_fn {
+                skipping = false;
+            }
+
+            let code = unsafe {
+                personality(
+                    UnwindState::VIRTUAL_UNWIND_FRAME | UnwindState::FORCE_UNWIND,
+                    exception,
+                    &mut UnwindContext {
+                        ctx: &mut ctx,
+                        exception,
+                    },
+                )
+            };
+            match code {
+                UnwindReasonCode::CONTINUE_UNWIND => (),
+                _ => return UnwindReasonCode::FAILURE,
+            }
+        }
+    })
+}
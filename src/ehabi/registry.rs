@@ -0,0 +1,88 @@
+//! Dynamic registration of `.ARM.exidx` tables, for code that is neither linked into the image nor
+//! loaded by the dynamic linker, such as JIT-compiled code. This takes the place of
+//! `__register_frame`, which registers DWARF CFI.
+
+use super::exidx::{search, Entry, IndexEntry};
+use alloc::vec::Vec;
+use core::ops::Range;
+use core::slice;
+
+// The code range, and the start and number of entries of the table, of each registration.
+struct Registration {
+    text: Range<usize>,
+    table: usize,
+    len: usize,
+}
+
+type Tables = Vec<Registration>;
+
+/// Registers the `.ARM.exidx` table from `exidx_start` to `exidx_end`, which describes the code
+/// from `text_start` to `text_end`.
+///
+/// # Safety
+///
+/// The table must be sorted by function start, as linkers emit it, and it and the `.ARM.extab`
+/// entries it refers to must stay valid until it is deregistered with [`deregister_exidx`].
+pub unsafe fn register_exidx(
+    text_start: *const u8,
+    text_end: *const u8,
+    exidx_start: *const IndexEntry,
+    exidx_end: *const IndexEntry,
+) {
+    let len = unsafe { exidx_end.offset_from(exidx_start) } as usize;
+    with_tables(|tables| {
+        tables.push(Registration {
+            text: text_start as usize..text_end as usize,
+            table: exidx_start as usize,
+            len,
+        })
+    });
+}
+
+/// Deregisters the `.ARM.exidx` table starting at `exidx_start`. Returns `false` if it wasn't
+/// registered.
+pub fn deregister_exidx(exidx_start: *const IndexEntry) -> bool {
+    with_tables(|tables| {
+        let Some(idx) = tables.iter().position(|r| r.table == exidx_start as usize) else {
+            return false;
+        };
+        tables.swap_remove(idx);
+        true
+    })
+}
+
+pub fn find_entry(pc: usize) -> Option<Entry> {
+    with_tables(|tables| {
+        let registration = tables.iter().find(|r| r.text.contains(&pc))?;
+        let table = unsafe {
+            slice::from_raw_parts(registration.table as *const IndexEntry, registration.len)
+        };
+        search(table, pc)
+    })
+}
+
+fn with_tables<R>(f: impl FnOnce(&mut Tables) -> R) -> R {
+    #[cfg(feature = "libc")]
+    {
+        static mut MUTEX: libc::pthread_mutex_t = libc::PTHREAD_MUTEX_INITIALIZER;
+        static mut TABLES: Tables = Vec::new();
+
+        struct LockGuard;
+        impl Drop for LockGuard {
+            fn drop(&mut self) {
+                unsafe { libc::pthread_mutex_unlock(core::ptr::addr_of_mut!(MUTEX)) };
+            }
+        }
+
+        unsafe { libc::pthread_mutex_lock(core::ptr::addr_of_mut!(MUTEX)) };
+        let _guard = LockGuard;
+        f(unsafe { &mut *core::ptr::addr_of_mut!(TABLES) })
+    }
+    #[cfg(not(feature = "libc"))]
+    {
+        static TABLES: spin::Mutex<Tables> = spin::Mutex::new(Vec::new());
+        f(&mut TABLES.lock())
+    }
+    #[cfg(not(any(feature = "libc", feature = "spin")))]
+    compile_error!("Either feature \"libc\" or \"spin\" must be enabled to use \"fde-registry\".");
+}
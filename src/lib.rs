@@ -1,6 +1,9 @@
 #![doc = include_str!("../README.md")]
 #![feature(naked_functions)]
-#![feature(non_exhaustive_omitted_patterns_lint)]
+#![cfg_attr(
+    all(feature = "unwinder", not(target_arch = "arm")),
+    feature(non_exhaustive_omitted_patterns_lint)
+)]
 // lang_items is an internal feature. `internal_features` lint is added recently
 // so also allow unknown lints to prevent warning in older nightly versions.
 #![cfg_attr(
@@ -21,6 +24,18 @@
     feature(core_intrinsics)
 )]
 #![cfg_attr(feature = "panic-handler", feature(thread_local))]
+#![cfg_attr(
+    all(
+        not(target_arch = "arm"),
+        any(
+            all(feature = "signal-safe", feature = "fde-phdr-dl"),
+            feature = "fde-static",
+            feature = "fde-static-regions",
+            feature = "fde-gnu-eh-frame-hdr"
+        )
+    ),
+    feature(linkage)
+)]
 #![warn(rust_2018_idioms)]
 #![warn(unsafe_op_in_unsafe_fn)]
 #![no_std]
@@ -28,21 +43,79 @@
 #[cfg(feature = "alloc")]
 extern crate alloc;
 
-#[cfg(feature = "unwinder")]
+#[cfg(all(feature = "unwinder", target_arch = "arm"))]
+mod ehabi;
+#[cfg(all(feature = "unwinder", not(target_arch = "arm")))]
 mod unwinder;
+#[cfg(all(feature = "unwinder", target_arch = "arm"))]
+use ehabi as unwinder;
 
 #[cfg(all(feature = "unwinder", feature = "fde-custom"))]
 pub use unwinder::custom_eh_frame_finder;
+#[cfg(all(feature = "unwinder", target_arch = "arm", feature = "fde-registry"))]
+pub use unwinder::exidx_registry;
+#[cfg(all(
+    feature = "unwinder",
+    feature = "fde-custom",
+    target_has_atomic = "ptr"
+))]
+pub use unwinder::fde_finder;
+#[cfg(all(
+    feature = "unwinder",
+    not(target_arch = "arm"),
+    target_has_atomic = "ptr",
+    any(
+        feature = "fde-static",
+        feature = "fde-static-regions",
+        feature = "fde-gnu-eh-frame-hdr"
+    )
+))]
+pub use unwinder::fde_table;
+#[cfg(all(feature = "unwinder", feature = "frame-stepper"))]
+pub use unwinder::frame_stepper;
+#[cfg(all(
+    feature = "unwinder",
+    not(target_arch = "arm"),
+    feature = "fde-phdr-dl",
+    feature = "signal-safe"
+))]
+pub use unwinder::phdr_snapshot;
+#[cfg(all(
+    feature = "unwinder",
+    any(
+        all(
+            target_arch = "x86_64",
+            target_pointer_width = "64",
+            target_os = "linux",
+            feature = "libc"
+        ),
+        all(
+            target_arch = "aarch64",
+            any(target_os = "linux", target_os = "android"),
+            feature = "shadow-call-stack"
+        )
+    )
+))]
+pub use unwinder::shadow_stack;
+#[cfg(all(feature = "unwinder", feature = "stack-switch"))]
+pub use unwinder::stack_switch;
+#[cfg(all(feature = "unwinder", feature = "fde-static-regions"))]
+pub use unwinder::static_regions;
+#[cfg(all(feature = "unwinder", feature = "trap-frame"))]
+pub use unwinder::trap_frame;
 
 pub mod abi;
 
 mod arch;
 mod util;
 
+#[cfg(feature = "fde-compact")]
+pub mod compact_unwind;
+
 #[cfg(feature = "print")]
 pub mod print;
 
-#[cfg(feature = "personality")]
+#[cfg(any(feature = "personality", feature = "personality-gcc"))]
 mod personality;
 #[cfg(all(not(feature = "personality"), feature = "personality-dummy"))]
 mod personality_dummy;
@@ -1,3 +1,4 @@
+use core::ffi::{c_int, c_void};
 use core::mem::ManuallyDrop;
 
 use crate::abi::*;
@@ -25,12 +26,81 @@ pub fn begin_panic<E: Exception>(exception: E) -> UnwindReasonCode {
     }
 }
 
+/// Exception class of the exceptions thrown by [`force_unwind`], until they are stopped.
+const FORCED_UNWIND_CLASS: [u8; 8] = *b"UNWDFORC";
+
+/// What [`force_unwind`] does at a frame, as decided by its stop function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopAction {
+    /// Run the cleanups of the frame and go on to its caller.
+    Continue,
+    /// Stop the forced unwind. From this frame on, the exception is unwound as if it had been
+    /// thrown by [`begin_panic`], so the next [`catch_unwind`] for it returns it.
+    Stop,
+}
+
+/// Forcibly unwinds the stack with `exception`, running the cleanups of every frame on the way.
+///
+/// Before each frame is unwound, `stop` is called with its context to decide whether the unwind
+/// stops there. Until it does, [`catch_unwind`] doesn't catch the exception: it is resumed once the
+/// cleanups of the closure have run. Once it stops, the exception is caught by the next
+/// [`catch_unwind`] for it, which returns `Err(Some(exception))`.
+///
+/// The process is aborted if the end of the stack is reached, so this only returns if the
+/// unwinder fails before running any cleanup.
+pub fn force_unwind<E: Exception>(
+    exception: E,
+    stop: fn(&UnwindContext<'_>) -> StopAction,
+) -> UnwindReasonCode {
+    unsafe extern "C" fn exception_cleanup<E: Exception>(
+        _unwind_code: UnwindReasonCode,
+        exception: *mut UnwindException,
+    ) {
+        unsafe { E::unwrap(exception) };
+    }
+
+    extern "C" fn stop_fn<E: Exception>(
+        _version: c_int,
+        actions: UnwindAction,
+        _exception_class: UnwindExceptionClass,
+        exception: *mut UnwindException,
+        unwind_ctx: &mut UnwindContext<'_>,
+        stop_arg: *mut c_void,
+    ) -> UnwindReasonCode {
+        // There's no frame left to go back to once cleanups have run.
+        if actions.contains(UnwindAction::END_OF_STACK) {
+            core::intrinsics::abort();
+        }
+        // The class argument is a pointer on ARM EHABI, so read it from the exception instead.
+        if unsafe { (*exception).exception_class } == u64::from_ne_bytes(FORCED_UNWIND_CLASS) {
+            let stop: fn(&UnwindContext<'_>) -> StopAction =
+                unsafe { core::mem::transmute(stop_arg) };
+            if stop(unwind_ctx) == StopAction::Stop {
+                unsafe { (*exception).exception_class = u64::from_ne_bytes(E::CLASS) };
+            }
+        }
+        UnwindReasonCode::NO_REASON
+    }
+
+    let ex = E::wrap(exception);
+    unsafe {
+        (*ex).exception_class = u64::from_ne_bytes(FORCED_UNWIND_CLASS);
+        (*ex).exception_cleanup = Some(exception_cleanup::<E>);
+        _Unwind_ForcedUnwind(ex, stop_fn::<E>, stop as *mut c_void)
+    }
+}
+
+/// Calls `f`, catching the exceptions of type `E` it throws.
+///
+/// Exceptions of other types are deleted and reported as `Err(None)`. Forced unwinds of
+/// [`force_unwind`] that haven't been stopped yet are not caught: they are resumed once the
+/// cleanups of `f` have run.
 pub fn catch_unwind<E: Exception, R, F: FnOnce() -> R>(f: F) -> Result<R, Option<E>> {
     #[repr(C)]
-    union Data<F, R, E> {
+    union Data<F, R> {
         f: ManuallyDrop<F>,
         r: ManuallyDrop<R>,
-        p: ManuallyDrop<Option<E>>,
+        p: *mut UnwindException,
     }
 
     let mut data = Data {
@@ -39,33 +109,32 @@ pub fn catch_unwind<E: Exception, R, F: FnOnce() -> R>(f: F) -> Result<R, Option
 
     let data_ptr = &mut data as *mut _ as *mut u8;
     unsafe {
-        return if core::intrinsics::catch_unwind(do_call::<F, R>, data_ptr, do_catch::<E>) == 0 {
-            Ok(ManuallyDrop::into_inner(data.r))
-        } else {
-            Err(ManuallyDrop::into_inner(data.p))
-        };
+        if core::intrinsics::catch_unwind(do_call::<F, R>, data_ptr, do_catch) == 0 {
+            return Ok(ManuallyDrop::into_inner(data.r));
+        }
+
+        let exception = data.p;
+        let class = (*exception).exception_class;
+        if class == u64::from_ne_bytes(FORCED_UNWIND_CLASS) {
+            _Unwind_Resume_or_Rethrow(exception);
+        } else if class == u64::from_ne_bytes(E::CLASS) {
+            return Err(Some(E::unwrap(exception)));
+        }
+        _Unwind_DeleteException(exception);
+        return Err(None);
     }
 
     #[inline]
     fn do_call<F: FnOnce() -> R, R>(data: *mut u8) {
         unsafe {
-            let data = &mut *(data as *mut Data<F, R, ()>);
+            let data = &mut *(data as *mut Data<F, R>);
             let f = ManuallyDrop::take(&mut data.f);
             data.r = ManuallyDrop::new(f());
         }
     }
 
     #[cold]
-    fn do_catch<E: Exception>(data: *mut u8, exception: *mut u8) {
-        unsafe {
-            let data = &mut *(data as *mut ManuallyDrop<Option<E>>);
-            let exception = exception as *mut UnwindException;
-            if (*exception).exception_class != u64::from_ne_bytes(E::CLASS) {
-                _Unwind_DeleteException(exception);
-                *data = ManuallyDrop::new(None);
-                return;
-            }
-            *data = ManuallyDrop::new(Some(E::unwrap(exception)));
-        }
+    fn do_catch(data: *mut u8, exception: *mut u8) {
+        unsafe { *(data as *mut *mut UnwindException) = exception as *mut UnwindException };
     }
 }
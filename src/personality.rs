@@ -146,6 +146,54 @@ fn find_eh_action(
     Ok(EHAction::Terminate)
 }
 
+// The end of the image the frame's unwind information was found in, as recorded by the FDE finder.
+// Other unwinders don't expose it.
+#[cfg(all(feature = "unwinder", not(target_arch = "arm")))]
+fn image_end(unwind_ctx: &UnwindContext<'_>) -> Option<usize> {
+    unwind_ctx.image_end()
+}
+
+#[cfg(not(all(feature = "unwinder", not(target_arch = "arm"))))]
+fn image_end(_unwind_ctx: &UnwindContext<'_>) -> Option<usize> {
+    None
+}
+
+// Find the LSDA of the frame, bounded by the end of its image if that is known.
+fn lsda(unwind_ctx: &UnwindContext<'_>) -> Option<StaticSlice> {
+    let lsda = _Unwind_GetLanguageSpecificData(unwind_ctx);
+    if lsda.is_null() {
+        return None;
+    }
+
+    let lsda = lsda as usize;
+    let lsda_len = match image_end(unwind_ctx) {
+        Some(end) => end.checked_sub(lsda)?,
+        // Without the bounds of the image, the LSDA can't be bounded.
+        None => (isize::MAX as usize).min(usize::MAX - lsda),
+    };
+    Some(EndianSlice::new(
+        unsafe { get_slice(lsda, lsda_len) },
+        NativeEndian,
+    ))
+}
+
+fn install_landing_pad(
+    unwind_ctx: &mut UnwindContext<'_>,
+    exception: *mut UnwindException,
+    lpad: usize,
+) -> UnwindReasonCode {
+    _Unwind_SetGR(
+        unwind_ctx,
+        Arch::UNWIND_DATA_REG.0 .0 as _,
+        exception as usize,
+    );
+    _Unwind_SetGR(unwind_ctx, Arch::UNWIND_DATA_REG.1 .0 as _, 0);
+    _Unwind_SetIP(unwind_ctx, lpad);
+    UnwindReasonCode::INSTALL_CONTEXT
+}
+
+#[cfg(feature = "personality")]
+#[cfg(not(target_arch = "arm"))]
 #[lang = "eh_personality"]
 unsafe fn rust_eh_personality(
     version: c_int,
@@ -157,13 +205,30 @@ unsafe fn rust_eh_personality(
     if version != 1 {
         return UnwindReasonCode::FATAL_PHASE1_ERROR;
     }
+    rust_personality(actions, exception, unwind_ctx)
+}
 
-    let lsda = _Unwind_GetLanguageSpecificData(unwind_ctx);
-    if lsda.is_null() {
-        return UnwindReasonCode::CONTINUE_UNWIND;
-    }
+#[cfg(feature = "personality")]
+#[cfg(target_arch = "arm")]
+#[lang = "eh_personality"]
+unsafe fn rust_eh_personality(
+    state: UnwindState,
+    exception: *mut UnwindException,
+    unwind_ctx: &mut UnwindContext<'_>,
+) -> UnwindReasonCode {
+    unsafe { ehabi_personality(state, exception, unwind_ctx, rust_personality) }
+}
 
-    let mut lsda = EndianSlice::new(unsafe { get_unlimited_slice(lsda as _) }, NativeEndian);
+#[cfg(feature = "personality")]
+fn rust_personality(
+    actions: UnwindAction,
+    exception: *mut UnwindException,
+    unwind_ctx: &mut UnwindContext<'_>,
+) -> UnwindReasonCode {
+    let mut lsda = match lsda(unwind_ctx) {
+        Some(v) => v,
+        None => return UnwindReasonCode::CONTINUE_UNWIND,
+    };
     let eh_action = match find_eh_action(&mut lsda, unwind_ctx) {
         Ok(v) => v,
         Err(_) => return UnwindReasonCode::FATAL_PHASE1_ERROR,
@@ -183,16 +248,117 @@ unsafe fn rust_eh_personality(
                 UnwindReasonCode::CONTINUE_UNWIND
             }
             EHAction::Cleanup(lpad) | EHAction::Catch(lpad) | EHAction::Filter(lpad) => {
-                _Unwind_SetGR(
-                    unwind_ctx,
-                    Arch::UNWIND_DATA_REG.0 .0 as _,
-                    exception as usize,
-                );
-                _Unwind_SetGR(unwind_ctx, Arch::UNWIND_DATA_REG.1 .0 as _, 0);
-                _Unwind_SetIP(unwind_ctx, lpad);
-                UnwindReasonCode::INSTALL_CONTEXT
+                install_landing_pad(unwind_ctx, exception, lpad)
             }
             EHAction::Terminate => UnwindReasonCode::FATAL_PHASE2_ERROR,
         }
     }
 }
+
+/// The personality routine of C code built with `-fexceptions`, as provided by libgcc. C code only
+/// has cleanups, which are run in the cleanup phase; code not covered by the call-site table is
+/// unwound through.
+#[cfg(feature = "personality-gcc")]
+#[cfg(not(target_arch = "arm"))]
+#[no_mangle]
+pub unsafe extern "C" fn __gcc_personality_v0(
+    version: c_int,
+    actions: UnwindAction,
+    _exception_class: u64,
+    exception: *mut UnwindException,
+    unwind_ctx: &mut UnwindContext<'_>,
+) -> UnwindReasonCode {
+    if version != 1 {
+        return UnwindReasonCode::FATAL_PHASE1_ERROR;
+    }
+    gcc_personality(actions, exception, unwind_ctx)
+}
+
+/// The personality routine of C code built with `-fexceptions`, as provided by libgcc. C code only
+/// has cleanups, which are run in the cleanup phase; code not covered by the call-site table is
+/// unwound through.
+#[cfg(feature = "personality-gcc")]
+#[cfg(target_arch = "arm")]
+#[no_mangle]
+pub unsafe extern "C" fn __gcc_personality_v0(
+    state: UnwindState,
+    exception: *mut UnwindException,
+    unwind_ctx: &mut UnwindContext<'_>,
+) -> UnwindReasonCode {
+    unsafe { ehabi_personality(state, exception, unwind_ctx, gcc_personality) }
+}
+
+#[cfg(feature = "personality-gcc")]
+fn gcc_personality(
+    actions: UnwindAction,
+    exception: *mut UnwindException,
+    unwind_ctx: &mut UnwindContext<'_>,
+) -> UnwindReasonCode {
+    if !actions.contains(UnwindAction::CLEANUP_PHASE) {
+        return UnwindReasonCode::CONTINUE_UNWIND;
+    }
+
+    let mut lsda = match lsda(unwind_ctx) {
+        Some(v) => v,
+        None => return UnwindReasonCode::CONTINUE_UNWIND,
+    };
+    match find_eh_action(&mut lsda, unwind_ctx) {
+        Ok(EHAction::Cleanup(lpad) | EHAction::Catch(lpad) | EHAction::Filter(lpad)) => {
+            install_landing_pad(unwind_ctx, exception, lpad)
+        }
+        Ok(EHAction::None | EHAction::Terminate) => UnwindReasonCode::CONTINUE_UNWIND,
+        Err(_) => UnwindReasonCode::FATAL_PHASE2_ERROR,
+    }
+}
+
+// ARM EHABI personality routines are told the state of the unwinder rather than the actions to
+// take, and unwind the frame themselves unless they install a landing pad.
+#[cfg(target_arch = "arm")]
+unsafe fn ehabi_personality(
+    state: UnwindState,
+    exception: *mut UnwindException,
+    unwind_ctx: &mut UnwindContext<'_>,
+    personality: fn(UnwindAction, *mut UnwindException, &mut UnwindContext<'_>) -> UnwindReasonCode,
+) -> UnwindReasonCode {
+    let force = state.contains(UnwindState::FORCE_UNWIND);
+    let actions = match state.action() {
+        // Backtraces virtually unwind with forced unwinding, so no handler is looked for.
+        UnwindState::VIRTUAL_UNWIND_FRAME if force => {
+            return unsafe { continue_unwind(exception, unwind_ctx) }
+        }
+        UnwindState::VIRTUAL_UNWIND_FRAME => UnwindAction::SEARCH_PHASE,
+        UnwindState::UNWIND_FRAME_STARTING if force => {
+            UnwindAction::CLEANUP_PHASE | UnwindAction::FORCE_UNWIND
+        }
+        UnwindState::UNWIND_FRAME_STARTING => UnwindAction::CLEANUP_PHASE,
+        // Resuming after a cleanup, which the unwinder here never asks for.
+        UnwindState::UNWIND_FRAME_RESUME => {
+            return unsafe { continue_unwind(exception, unwind_ctx) }
+        }
+        _ => return UnwindReasonCode::FAILURE,
+    };
+
+    // libgcc finds the LSDA and the start of the function through the UCB, which it expects in r12.
+    _Unwind_SetGR(unwind_ctx, 12, exception as usize);
+
+    match personality(actions, exception, unwind_ctx) {
+        UnwindReasonCode::CONTINUE_UNWIND => unsafe { continue_unwind(exception, unwind_ctx) },
+        UnwindReasonCode::HANDLER_FOUND => {
+            unsafe { (*exception).barrier_cache.sp = _Unwind_GetGR(unwind_ctx, Arch::SP.0 as _) };
+            UnwindReasonCode::HANDLER_FOUND
+        }
+        UnwindReasonCode::INSTALL_CONTEXT => UnwindReasonCode::INSTALL_CONTEXT,
+        _ => UnwindReasonCode::FAILURE,
+    }
+}
+
+#[cfg(target_arch = "arm")]
+unsafe fn continue_unwind(
+    exception: *mut UnwindException,
+    unwind_ctx: &mut UnwindContext<'_>,
+) -> UnwindReasonCode {
+    match unsafe { __gnu_unwind_frame(exception, unwind_ctx) } {
+        UnwindReasonCode::NO_REASON => UnwindReasonCode::CONTINUE_UNWIND,
+        _ => UnwindReasonCode::FAILURE,
+    }
+}
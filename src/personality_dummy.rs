@@ -1,6 +1,8 @@
 use crate::abi::*;
+#[cfg(not(target_arch = "arm"))]
 use crate::util::*;
 
+#[cfg(not(target_arch = "arm"))]
 #[lang = "eh_personality"]
 unsafe extern "C" fn personality(
     version: c_int,
@@ -14,3 +16,17 @@ unsafe extern "C" fn personality(
     }
     UnwindReasonCode::CONTINUE_UNWIND
 }
+
+// EHABI personality routines unwind the frame themselves.
+#[cfg(target_arch = "arm")]
+#[lang = "eh_personality"]
+unsafe extern "C" fn personality(
+    _state: UnwindState,
+    exception: *mut UnwindException,
+    ctx: &mut UnwindContext<'_>,
+) -> UnwindReasonCode {
+    match unsafe { __gnu_unwind_frame(exception, ctx) } {
+        UnwindReasonCode::NO_REASON => UnwindReasonCode::CONTINUE_UNWIND,
+        _ => UnwindReasonCode::FAILURE,
+    }
+}
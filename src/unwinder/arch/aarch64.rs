@@ -11,6 +11,8 @@ pub struct Context {
     pub gp: [usize; 31],
     pub sp: usize,
     pub fp: [usize; 32],
+    /// The SVE vector length in 64-bit granules, which CFI expressions of SVE frames refer to.
+    pub vg: usize,
 }
 
 impl fmt::Debug for Context {
@@ -29,6 +31,7 @@ impl fmt::Debug for Context {
                 &self.fp[i],
             );
         }
+        fmt.field("vg", &self.vg);
         fmt.finish()
     }
 }
@@ -40,7 +43,11 @@ impl ops::Index<Register> for Context {
         match reg {
             Register(0..=30) => &self.gp[reg.0 as usize],
             AArch64::SP => &self.sp,
+            AArch64::VG => &self.vg,
             Register(64..=95) => &self.fp[(reg.0 - 64) as usize],
+            // Only the low 64 bits of the SVE registers, aliasing the FP registers, are preserved
+            // across calls.
+            Register(96..=127) => &self.fp[(reg.0 - 96) as usize],
             _ => unimplemented!(),
         }
     }
@@ -51,12 +58,45 @@ impl ops::IndexMut<gimli::Register> for Context {
         match reg {
             Register(0..=30) => &mut self.gp[reg.0 as usize],
             AArch64::SP => &mut self.sp,
+            AArch64::VG => &mut self.vg,
             Register(64..=95) => &mut self.fp[(reg.0 - 64) as usize],
+            Register(96..=127) => &mut self.fp[(reg.0 - 96) as usize],
             _ => unimplemented!(),
         }
     }
 }
 
+impl Context {
+    /// The value of VG, for expressions that read it.
+    ///
+    /// VG is only saved with the context if the unwinder is built with SVE. Otherwise it is read
+    /// now, if the CPU is known to support SVE, and `None` is returned if it isn't.
+    #[cfg(feature = "dwarf-expr")]
+    pub(crate) fn vg(&self) -> Option<usize> {
+        if self.vg != 0 {
+            return Some(self.vg);
+        }
+        #[cfg(all(
+            not(target_feature = "sve"),
+            feature = "libc",
+            any(target_os = "linux", target_os = "android")
+        ))]
+        if unsafe { libc::getauxval(libc::AT_HWCAP) } & libc::HWCAP_SVE != 0 {
+            let vg: usize;
+            // cntd x0, encoded so that it assembles without SVE enabled.
+            unsafe {
+                core::arch::asm!(
+                    ".inst 0x04e0e3e0",
+                    out("x0") vg,
+                    options(nomem, nostack, preserves_flags)
+                )
+            };
+            return Some(vg);
+        }
+        None
+    }
+}
+
 macro_rules! save {
     (gp$(, $fp:ident)?) => {
         // No need to save caller-saved registers here.
@@ -66,25 +106,27 @@ macro_rules! save {
             .cfi_def_cfa_offset 16
             .cfi_offset x29, -16
             .cfi_offset x30, -8
-            sub sp, sp, 512
-            .cfi_def_cfa_offset 528
+            sub sp, sp, 528
+            .cfi_def_cfa_offset 544
             mov x8, x0
             mov x0, sp
             ",
             save!(maybesavefp($($fp)?)),
+            save_vg!(),
+            // x18 is not caller-saved where it holds the shadow call stack pointer.
             "
-            str x19, [sp, 0x98]
+            stp x18, x19, [sp, 0x90]
             stp x20, x21, [sp, 0xA0]
             stp x22, x23, [sp, 0xB0]
             stp x24, x25, [sp, 0xC0]
             stp x26, x27, [sp, 0xD0]
             stp x28, x29, [sp, 0xE0]
-            add x2, sp, 528
+            add x2, sp, 544
             stp x30, x2, [sp, 0xF0]
 
             blr x8
 
-            add sp, sp, 512
+            add sp, sp, 528
             .cfi_def_cfa_offset 16
             ldp x29, x30, [sp], 16
             .cfi_def_cfa_offset 0
@@ -105,6 +147,27 @@ macro_rules! save {
     (maybesavefp()) => { "" };
 }
 
+#[cfg(target_feature = "sve")]
+macro_rules! save_vg {
+    () => {
+        "
+        .arch_extension sve
+        cntd x9
+        str x9, [sp, 0x200]
+        "
+    };
+}
+
+// Without SVE, VG is left as 0 and read when it is needed, by `Context::vg`.
+#[cfg(not(target_feature = "sve"))]
+macro_rules! save_vg {
+    () => {
+        "
+        str xzr, [sp, 0x200]
+        "
+    };
+}
+
 #[naked]
 pub extern "C-unwind" fn save_context(f: extern "C" fn(&mut Context, *mut ()), ptr: *mut ()) {
     unsafe {
@@ -175,3 +238,20 @@ pub unsafe fn restore_context(ctx: &Context) -> ! {
         restore!(ctx, gp);
     }
 }
+
+/// Strips the pointer authentication code from a return address signed by `paciasp` or
+/// `pacibsp`.
+#[inline]
+pub fn strip_return_address(ra: usize) -> usize {
+    let mut ra = ra;
+    // `xpaclri`, which is in the hint space, so it's a no-op on cores without pointer
+    // authentication.
+    unsafe {
+        core::arch::asm!(
+            "hint #7",
+            inout("x30") ra,
+            options(nomem, nostack, preserves_flags, pure)
+        );
+    }
+    ra
+}
@@ -0,0 +1,245 @@
+use core::fmt;
+use core::ops;
+use gimli::{LoongArch, Register};
+
+// The integer and floating-point registers.
+pub const MAX_REG_RULES: usize = 64;
+
+#[cfg(all(target_feature = "f", not(target_feature = "d")))]
+compile_error!("LoongArch with only F extension is not supported");
+
+#[repr(C)]
+#[derive(Clone, Default)]
+pub struct Context {
+    pub gp: [usize; 32],
+    #[cfg(target_feature = "d")]
+    pub fp: [usize; 32],
+}
+
+impl fmt::Debug for Context {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut fmt = fmt.debug_struct("Context");
+        for i in 0..=31 {
+            fmt.field(
+                LoongArch::register_name(Register(i as _)).unwrap(),
+                &self.gp[i],
+            );
+        }
+        #[cfg(target_feature = "d")]
+        for i in 0..=31 {
+            fmt.field(
+                LoongArch::register_name(Register((i + 32) as _)).unwrap(),
+                &self.fp[i],
+            );
+        }
+        fmt.finish()
+    }
+}
+
+impl ops::Index<Register> for Context {
+    type Output = usize;
+
+    fn index(&self, reg: Register) -> &usize {
+        match reg {
+            Register(0..=31) => &self.gp[reg.0 as usize],
+            #[cfg(target_feature = "d")]
+            Register(32..=63) => &self.fp[(reg.0 - 32) as usize],
+            _ => unimplemented!(),
+        }
+    }
+}
+
+impl ops::IndexMut<gimli::Register> for Context {
+    fn index_mut(&mut self, reg: Register) -> &mut usize {
+        match reg {
+            Register(0..=31) => &mut self.gp[reg.0 as usize],
+            #[cfg(target_feature = "d")]
+            Register(32..=63) => &mut self.fp[(reg.0 - 32) as usize],
+            _ => unimplemented!(),
+        }
+    }
+}
+
+macro_rules! code {
+    (save_gp) => {
+        "
+        st.d $zero, $sp, 0x00
+        st.d $ra, $sp, 0x08
+        st.d $tp, $sp, 0x10
+        st.d $t0, $sp, 0x18
+        st.d $r21, $sp, 0xA8
+        st.d $fp, $sp, 0xB0
+        st.d $s0, $sp, 0xB8
+        st.d $s1, $sp, 0xC0
+        st.d $s2, $sp, 0xC8
+        st.d $s3, $sp, 0xD0
+        st.d $s4, $sp, 0xD8
+        st.d $s5, $sp, 0xE0
+        st.d $s6, $sp, 0xE8
+        st.d $s7, $sp, 0xF0
+        st.d $s8, $sp, 0xF8
+        "
+    };
+    (save_fp) => {
+        "
+        fst.d $fs0, $sp, 0x1C0
+        fst.d $fs1, $sp, 0x1C8
+        fst.d $fs2, $sp, 0x1D0
+        fst.d $fs3, $sp, 0x1D8
+        fst.d $fs4, $sp, 0x1E0
+        fst.d $fs5, $sp, 0x1E8
+        fst.d $fs6, $sp, 0x1F0
+        fst.d $fs7, $sp, 0x1F8
+        "
+    };
+    (restore_gp) => {
+        "
+        ld.d $ra, $a0, 0x08
+        ld.d $tp, $a0, 0x10
+        ld.d $sp, $a0, 0x18
+        ld.d $a1, $a0, 0x28
+        ld.d $a2, $a0, 0x30
+        ld.d $a3, $a0, 0x38
+        ld.d $a4, $a0, 0x40
+        ld.d $a5, $a0, 0x48
+        ld.d $a6, $a0, 0x50
+        ld.d $a7, $a0, 0x58
+        ld.d $t0, $a0, 0x60
+        ld.d $t1, $a0, 0x68
+        ld.d $t2, $a0, 0x70
+        ld.d $t3, $a0, 0x78
+        ld.d $t4, $a0, 0x80
+        ld.d $t5, $a0, 0x88
+        ld.d $t6, $a0, 0x90
+        ld.d $t7, $a0, 0x98
+        ld.d $t8, $a0, 0xA0
+        ld.d $r21, $a0, 0xA8
+        ld.d $fp, $a0, 0xB0
+        ld.d $s0, $a0, 0xB8
+        ld.d $s1, $a0, 0xC0
+        ld.d $s2, $a0, 0xC8
+        ld.d $s3, $a0, 0xD0
+        ld.d $s4, $a0, 0xD8
+        ld.d $s5, $a0, 0xE0
+        ld.d $s6, $a0, 0xE8
+        ld.d $s7, $a0, 0xF0
+        ld.d $s8, $a0, 0xF8
+        "
+    };
+    (restore_fp) => {
+        "
+        fld.d $fa0, $a0, 0x100
+        fld.d $fa1, $a0, 0x108
+        fld.d $fa2, $a0, 0x110
+        fld.d $fa3, $a0, 0x118
+        fld.d $fa4, $a0, 0x120
+        fld.d $fa5, $a0, 0x128
+        fld.d $fa6, $a0, 0x130
+        fld.d $fa7, $a0, 0x138
+        fld.d $ft0, $a0, 0x140
+        fld.d $ft1, $a0, 0x148
+        fld.d $ft2, $a0, 0x150
+        fld.d $ft3, $a0, 0x158
+        fld.d $ft4, $a0, 0x160
+        fld.d $ft5, $a0, 0x168
+        fld.d $ft6, $a0, 0x170
+        fld.d $ft7, $a0, 0x178
+        fld.d $ft8, $a0, 0x180
+        fld.d $ft9, $a0, 0x188
+        fld.d $ft10, $a0, 0x190
+        fld.d $ft11, $a0, 0x198
+        fld.d $ft12, $a0, 0x1A0
+        fld.d $ft13, $a0, 0x1A8
+        fld.d $ft14, $a0, 0x1B0
+        fld.d $ft15, $a0, 0x1B8
+        fld.d $fs0, $a0, 0x1C0
+        fld.d $fs1, $a0, 0x1C8
+        fld.d $fs2, $a0, 0x1D0
+        fld.d $fs3, $a0, 0x1D8
+        fld.d $fs4, $a0, 0x1E0
+        fld.d $fs5, $a0, 0x1E8
+        fld.d $fs6, $a0, 0x1F0
+        fld.d $fs7, $a0, 0x1F8
+        "
+    };
+}
+
+#[naked]
+pub extern "C-unwind" fn save_context(f: extern "C" fn(&mut Context, *mut ()), ptr: *mut ()) {
+    // No need to save caller-saved registers here.
+    #[cfg(target_feature = "d")]
+    unsafe {
+        core::arch::naked_asm!(
+            "
+            move $t0, $sp
+            addi.d $sp, $sp, -0x210
+            .cfi_def_cfa_offset 0x210
+            st.d $ra, $sp, 0x200
+            .cfi_offset 1, -16
+            ",
+            code!(save_gp),
+            code!(save_fp),
+            "
+            move $t0, $a0
+            move $a0, $sp
+            jirl $ra, $t0, 0
+            ld.d $ra, $sp, 0x200
+            addi.d $sp, $sp, 0x210
+            .cfi_def_cfa_offset 0
+            .cfi_restore 1
+            ret
+            ",
+        );
+    }
+    #[cfg(not(target_feature = "d"))]
+    unsafe {
+        core::arch::naked_asm!(
+            "
+            move $t0, $sp
+            addi.d $sp, $sp, -0x110
+            .cfi_def_cfa_offset 0x110
+            st.d $ra, $sp, 0x100
+            .cfi_offset 1, -16
+            ",
+            code!(save_gp),
+            "
+            move $t0, $a0
+            move $a0, $sp
+            jirl $ra, $t0, 0
+            ld.d $ra, $sp, 0x100
+            addi.d $sp, $sp, 0x110
+            .cfi_def_cfa_offset 0
+            .cfi_restore 1
+            ret
+            ",
+        );
+    }
+}
+
+pub unsafe fn restore_context(ctx: &Context) -> ! {
+    #[cfg(target_feature = "d")]
+    unsafe {
+        core::arch::asm!(
+            code!(restore_fp),
+            code!(restore_gp),
+            "
+            ld.d $a0, $a0, 0x20
+            ret
+            ",
+            in("$a0") ctx,
+            options(noreturn)
+        );
+    }
+    #[cfg(not(target_feature = "d"))]
+    unsafe {
+        core::arch::asm!(
+            code!(restore_gp),
+            "
+            ld.d $a0, $a0, 0x20
+            ret
+            ",
+            in("$a0") ctx,
+            options(noreturn)
+        );
+    }
+}
@@ -0,0 +1,378 @@
+use core::fmt;
+use core::ops;
+use gimli::{PowerPc64, Register};
+
+// The registers up to the last vector register.
+pub const MAX_REG_RULES: usize = 109;
+
+// ELFv1 function pointers point to descriptors in `.opd` rather than to code.
+#[cfg(all(target_endian = "big", not(target_abi = "elfv2")))]
+compile_error!("Only the ELFv2 ABI is supported on PowerPC64");
+
+#[repr(C)]
+#[derive(Clone, Default)]
+pub struct Context {
+    pub gp: [usize; 32],
+    pub fp: [usize; 32],
+    pub lr: usize,
+    /// Each condition register field is a register of its own in DWARF, but they are saved and
+    /// restored together. Field `i` is kept in place in the word of entry `i`.
+    pub cr: [usize; 8],
+    /// The callee-saved vector registers, v20 to v31.
+    #[cfg(target_feature = "altivec")]
+    pub vr: [u128; 12],
+}
+
+impl Context {
+    /// The callee-saved vector register `reg`, which is too wide to be indexed as a `usize`.
+    #[cfg(target_feature = "altivec")]
+    pub fn vr_mut(&mut self, reg: Register) -> Option<&mut u128> {
+        match reg {
+            Register(97..=108) => Some(&mut self.vr[(reg.0 - 97) as usize]),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for Context {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut fmt = fmt.debug_struct("Context");
+        for i in 0..=31 {
+            fmt.field(
+                PowerPc64::register_name(Register(i as _)).unwrap(),
+                &self.gp[i],
+            );
+        }
+        for i in 0..=31 {
+            fmt.field(
+                PowerPc64::register_name(Register((i + 32) as _)).unwrap(),
+                &self.fp[i],
+            );
+        }
+        fmt.field("lr", &self.lr);
+        for i in 0..=7 {
+            fmt.field(
+                PowerPc64::register_name(Register((i + 68) as _)).unwrap(),
+                &self.cr[i],
+            );
+        }
+        #[cfg(target_feature = "altivec")]
+        for i in 0..=11 {
+            fmt.field(
+                PowerPc64::register_name(Register((i + 97) as _)).unwrap(),
+                &self.vr[i],
+            );
+        }
+        fmt.finish()
+    }
+}
+
+impl ops::Index<Register> for Context {
+    type Output = usize;
+
+    fn index(&self, reg: Register) -> &usize {
+        match reg {
+            Register(0..=31) => &self.gp[reg.0 as usize],
+            Register(32..=63) => &self.fp[(reg.0 - 32) as usize],
+            PowerPc64::LR => &self.lr,
+            Register(68..=75) => &self.cr[(reg.0 - 68) as usize],
+            _ => unimplemented!(),
+        }
+    }
+}
+
+impl ops::IndexMut<gimli::Register> for Context {
+    fn index_mut(&mut self, reg: Register) -> &mut usize {
+        match reg {
+            Register(0..=31) => &mut self.gp[reg.0 as usize],
+            Register(32..=63) => &mut self.fp[(reg.0 - 32) as usize],
+            PowerPc64::LR => &mut self.lr,
+            Register(68..=75) => &mut self.cr[(reg.0 - 68) as usize],
+            _ => unimplemented!(),
+        }
+    }
+}
+
+/// Whether `reg` is saved as a 32-bit word rather than a `usize`.
+pub fn is_word_register(reg: Register) -> bool {
+    matches!(reg, Register(68..=75))
+}
+
+macro_rules! save {
+    ($frame:literal$(, $vr:ident)?) => {
+        // No need to save caller-saved registers here. The context is placed above the linkage
+        // area of the callee, which is 32 bytes in ELFv2.
+        core::arch::naked_asm!(
+            "
+            mflr 0
+            std 0, 16(1)
+            stdu 1, -{frame}(1)
+            .cfi_def_cfa_offset {frame}
+            .cfi_offset 65, 16
+            addi 5, 1, 32
+
+            std 2, 0x10(5)
+            std 13, 0x68(5)
+            std 14, 0x70(5)
+            std 15, 0x78(5)
+            std 16, 0x80(5)
+            std 17, 0x88(5)
+            std 18, 0x90(5)
+            std 19, 0x98(5)
+            std 20, 0xA0(5)
+            std 21, 0xA8(5)
+            std 22, 0xB0(5)
+            std 23, 0xB8(5)
+            std 24, 0xC0(5)
+            std 25, 0xC8(5)
+            std 26, 0xD0(5)
+            std 27, 0xD8(5)
+            std 28, 0xE0(5)
+            std 29, 0xE8(5)
+            std 30, 0xF0(5)
+            std 31, 0xF8(5)
+            addi 6, 1, {frame}
+            std 6, 0x08(5)
+
+            stfd 14, 0x170(5)
+            stfd 15, 0x178(5)
+            stfd 16, 0x180(5)
+            stfd 17, 0x188(5)
+            stfd 18, 0x190(5)
+            stfd 19, 0x198(5)
+            stfd 20, 0x1A0(5)
+            stfd 21, 0x1A8(5)
+            stfd 22, 0x1B0(5)
+            stfd 23, 0x1B8(5)
+            stfd 24, 0x1C0(5)
+            stfd 25, 0x1C8(5)
+            stfd 26, 0x1D0(5)
+            stfd 27, 0x1D8(5)
+            stfd 28, 0x1E0(5)
+            stfd 29, 0x1E8(5)
+            stfd 30, 0x1F0(5)
+            stfd 31, 0x1F8(5)
+
+            std 0, 0x200(5)
+            mfcr 6
+            std 6, 0x208(5)
+            std 6, 0x210(5)
+            std 6, 0x218(5)
+            std 6, 0x220(5)
+            std 6, 0x228(5)
+            std 6, 0x230(5)
+            std 6, 0x238(5)
+            std 6, 0x240(5)
+            ",
+            save!(maybesavevr($($vr)?)),
+            // Calls through a pointer go to the global entry point, which expects its own address
+            // in r12, and may clobber the TOC pointer.
+            "
+            std 2, 24(1)
+            mr 12, 3
+            mtctr 12
+            mr 3, 5
+            bctrl
+            ld 2, 24(1)
+
+            addi 1, 1, {frame}
+            .cfi_def_cfa_offset 0
+            ld 0, 16(1)
+            mtlr 0
+            .cfi_restore 65
+            blr
+            ",
+            frame = const $frame,
+        );
+    };
+    (maybesavevr(vr)) => {
+        "
+        li 6, 0x250
+        stvx 20, 5, 6
+        li 6, 0x260
+        stvx 21, 5, 6
+        li 6, 0x270
+        stvx 22, 5, 6
+        li 6, 0x280
+        stvx 23, 5, 6
+        li 6, 0x290
+        stvx 24, 5, 6
+        li 6, 0x2A0
+        stvx 25, 5, 6
+        li 6, 0x2B0
+        stvx 26, 5, 6
+        li 6, 0x2C0
+        stvx 27, 5, 6
+        li 6, 0x2D0
+        stvx 28, 5, 6
+        li 6, 0x2E0
+        stvx 29, 5, 6
+        li 6, 0x2F0
+        stvx 30, 5, 6
+        li 6, 0x300
+        stvx 31, 5, 6
+        "
+    };
+    (maybesavevr()) => { "" };
+}
+
+#[naked]
+pub extern "C-unwind" fn save_context(f: extern "C" fn(&mut Context, *mut ()), ptr: *mut ()) {
+    unsafe {
+        #[cfg(target_feature = "altivec")]
+        save!(0x330, vr);
+        #[cfg(not(target_feature = "altivec"))]
+        save!(0x270);
+    }
+}
+
+macro_rules! restore {
+    (mayberestore(vr)) => {
+        "
+        li 4, 0x250
+        lvx 20, 3, 4
+        li 4, 0x260
+        lvx 21, 3, 4
+        li 4, 0x270
+        lvx 22, 3, 4
+        li 4, 0x280
+        lvx 23, 3, 4
+        li 4, 0x290
+        lvx 24, 3, 4
+        li 4, 0x2A0
+        lvx 25, 3, 4
+        li 4, 0x2B0
+        lvx 26, 3, 4
+        li 4, 0x2C0
+        lvx 27, 3, 4
+        li 4, 0x2D0
+        lvx 28, 3, 4
+        li 4, 0x2E0
+        lvx 29, 3, 4
+        li 4, 0x2F0
+        lvx 30, 3, 4
+        li 4, 0x300
+        lvx 31, 3, 4
+        "
+    };
+    (mayberestore()) => { "" };
+    ($ctx:expr$(, $vr:ident)?) => {
+        // Each condition register field is taken from its own entry.
+        core::arch::asm!(
+            restore!(mayberestore($($vr)?)),
+            "
+            ld 4, 0x208(3)
+            mtcrf 0x80, 4
+            ld 4, 0x210(3)
+            mtcrf 0x40, 4
+            ld 4, 0x218(3)
+            mtcrf 0x20, 4
+            ld 4, 0x220(3)
+            mtcrf 0x10, 4
+            ld 4, 0x228(3)
+            mtcrf 0x08, 4
+            ld 4, 0x230(3)
+            mtcrf 0x04, 4
+            ld 4, 0x238(3)
+            mtcrf 0x02, 4
+            ld 4, 0x240(3)
+            mtcrf 0x01, 4
+            ld 4, 0x200(3)
+            mtlr 4
+
+            lfd 0, 0x100(3)
+            lfd 1, 0x108(3)
+            lfd 2, 0x110(3)
+            lfd 3, 0x118(3)
+            lfd 4, 0x120(3)
+            lfd 5, 0x128(3)
+            lfd 6, 0x130(3)
+            lfd 7, 0x138(3)
+            lfd 8, 0x140(3)
+            lfd 9, 0x148(3)
+            lfd 10, 0x150(3)
+            lfd 11, 0x158(3)
+            lfd 12, 0x160(3)
+            lfd 13, 0x168(3)
+            lfd 14, 0x170(3)
+            lfd 15, 0x178(3)
+            lfd 16, 0x180(3)
+            lfd 17, 0x188(3)
+            lfd 18, 0x190(3)
+            lfd 19, 0x198(3)
+            lfd 20, 0x1A0(3)
+            lfd 21, 0x1A8(3)
+            lfd 22, 0x1B0(3)
+            lfd 23, 0x1B8(3)
+            lfd 24, 0x1C0(3)
+            lfd 25, 0x1C8(3)
+            lfd 26, 0x1D0(3)
+            lfd 27, 0x1D8(3)
+            lfd 28, 0x1E0(3)
+            lfd 29, 0x1E8(3)
+            lfd 30, 0x1F0(3)
+            lfd 31, 0x1F8(3)
+
+            ld 0, 0x00(3)
+            ld 1, 0x08(3)
+            ld 2, 0x10(3)
+            ld 4, 0x20(3)
+            ld 5, 0x28(3)
+            ld 6, 0x30(3)
+            ld 7, 0x38(3)
+            ld 8, 0x40(3)
+            ld 9, 0x48(3)
+            ld 10, 0x50(3)
+            ld 11, 0x58(3)
+            ld 12, 0x60(3)
+            ld 13, 0x68(3)
+            ld 14, 0x70(3)
+            ld 15, 0x78(3)
+            ld 16, 0x80(3)
+            ld 17, 0x88(3)
+            ld 18, 0x90(3)
+            ld 19, 0x98(3)
+            ld 20, 0xA0(3)
+            ld 21, 0xA8(3)
+            ld 22, 0xB0(3)
+            ld 23, 0xB8(3)
+            ld 24, 0xC0(3)
+            ld 25, 0xC8(3)
+            ld 26, 0xD0(3)
+            ld 27, 0xD8(3)
+            ld 28, 0xE0(3)
+            ld 29, 0xE8(3)
+            ld 30, 0xF0(3)
+            ld 31, 0xF8(3)
+            ld 3, 0x18(3)
+            blr
+            ",
+            in("r3") $ctx,
+            options(noreturn)
+        );
+    };
+}
+
+pub unsafe fn restore_context(ctx: &Context) -> ! {
+    unsafe {
+        #[cfg(target_feature = "altivec")]
+        restore!(ctx, vr);
+        #[cfg(not(target_feature = "altivec"))]
+        restore!(ctx);
+    }
+}
+
+/// Recovers the TOC pointer of a caller that didn't save it itself.
+///
+/// Calls that may leave the module go through a linker stub, which saves the TOC pointer in the
+/// TOC save slot of the caller's linkage area, and the caller reloads it with `ld r2, 24(r1)`
+/// after the call. The stub has no CFI, so the caller's TOC pointer is taken from the slot if
+/// that is the instruction it returns to, as libgcc does.
+pub fn restore_toc(ctx: &mut Context) {
+    const LD_R2_24_R1: u32 = 0xE8410018;
+
+    let ra = ctx.lr;
+    if ra != 0 && unsafe { *(ra as *const u32) } == LD_R2_24_R1 {
+        ctx.gp[2] = unsafe { *((ctx.gp[1] + 24) as *const usize) };
+    }
+}
@@ -5,24 +5,32 @@ use gimli::{Register, RiscV};
 // Match DWARF_FRAME_REGISTERS in libgcc
 pub const MAX_REG_RULES: usize = 65;
 
-#[cfg(all(target_feature = "f", not(target_feature = "d")))]
-compile_error!("RISC-V with only F extension is not supported");
+// The ILP32E and LP64E ABIs are soft-float only.
+#[cfg(all(target_feature = "e", target_feature = "f"))]
+compile_error!("RISC-V with both E and F extensions is not supported");
 
 #[repr(C)]
 #[derive(Clone, Default)]
 pub struct Context {
+    #[cfg(not(target_feature = "e"))]
     pub gp: [usize; 32],
+    /// Only x0 to x15 exist with the E extension.
+    #[cfg(target_feature = "e")]
+    pub gp: [usize; 16],
     #[cfg(target_feature = "d")]
     pub fp: [u64; 32],
+    /// The single-precision registers, if only the F extension is implemented.
+    #[cfg(all(target_feature = "f", not(target_feature = "d")))]
+    pub fp: [usize; 32],
 }
 
 impl fmt::Debug for Context {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut fmt = fmt.debug_struct("Context");
-        for i in 0..=31 {
-            fmt.field(RiscV::register_name(Register(i as _)).unwrap(), &self.gp[i]);
+        for (i, gp) in self.gp.iter().enumerate() {
+            fmt.field(RiscV::register_name(Register(i as _)).unwrap(), gp);
         }
-        #[cfg(target_feature = "d")]
+        #[cfg(target_feature = "f")]
         for i in 0..=31 {
             fmt.field(
                 RiscV::register_name(Register((i + 32) as _)).unwrap(),
@@ -38,9 +46,14 @@ impl ops::Index<Register> for Context {
 
     fn index(&self, reg: Register) -> &usize {
         match reg {
+            #[cfg(not(target_feature = "e"))]
             Register(0..=31) => &self.gp[reg.0 as usize],
-            // We cannot support indexing fp here. It is 64-bit if D extension is implemented,
-            // and 32-bit if only F extension is implemented.
+            #[cfg(target_feature = "e")]
+            Register(0..=15) => &self.gp[reg.0 as usize],
+            #[cfg(all(target_feature = "f", not(target_feature = "d")))]
+            Register(32..=63) => &self.fp[(reg.0 - 32) as usize],
+            // We cannot support indexing fp here if the D extension is implemented, as it is
+            // 64-bit.
             _ => unimplemented!(),
         }
     }
@@ -49,9 +62,14 @@ impl ops::Index<Register> for Context {
 impl ops::IndexMut<gimli::Register> for Context {
     fn index_mut(&mut self, reg: Register) -> &mut usize {
         match reg {
+            #[cfg(not(target_feature = "e"))]
             Register(0..=31) => &mut self.gp[reg.0 as usize],
-            // We cannot support indexing fp here. It is 64-bit if D extension is implemented,
-            // and 32-bit if only F extension is implemented.
+            #[cfg(target_feature = "e")]
+            Register(0..=15) => &mut self.gp[reg.0 as usize],
+            #[cfg(all(target_feature = "f", not(target_feature = "d")))]
+            Register(32..=63) => &mut self.fp[(reg.0 - 32) as usize],
+            // We cannot support indexing fp here if the D extension is implemented, as it is
+            // 64-bit.
             _ => unimplemented!(),
         }
     }
@@ -165,84 +183,162 @@ macro_rules! code {
         fld ft11, 0x178(a0)
         "
     };
+    (save_gp_e) => {
+        "
+        sw x0, 0x00(sp)
+        sw ra, 0x04(sp)
+        sw t0, 0x08(sp)
+        sw gp, 0x0C(sp)
+        sw tp, 0x10(sp)
+        sw s0, 0x20(sp)
+        sw s1, 0x24(sp)
+        "
+    };
+    (restore_gp_e) => {
+        "
+        lw ra, 0x04(a0)
+        lw sp, 0x08(a0)
+        lw gp, 0x0C(a0)
+        lw tp, 0x10(a0)
+        lw t0, 0x14(a0)
+        lw t1, 0x18(a0)
+        lw t2, 0x1C(a0)
+        lw s0, 0x20(a0)
+        lw s1, 0x24(a0)
+        lw a1, 0x2C(a0)
+        lw a2, 0x30(a0)
+        lw a3, 0x34(a0)
+        lw a4, 0x38(a0)
+        lw a5, 0x3C(a0)
+        "
+    };
+    (save_fp_single) => {
+        "
+        fsw fs0, 0xA0(sp)
+        fsw fs1, 0xA4(sp)
+        fsw fs2, 0xC8(sp)
+        fsw fs3, 0xCC(sp)
+        fsw fs4, 0xD0(sp)
+        fsw fs5, 0xD4(sp)
+        fsw fs6, 0xD8(sp)
+        fsw fs7, 0xDC(sp)
+        fsw fs8, 0xE0(sp)
+        fsw fs9, 0xE4(sp)
+        fsw fs10, 0xE8(sp)
+        fsw fs11, 0xEC(sp)
+        "
+    };
+    (restore_fp_single) => {
+        "
+        flw ft0, 0x80(a0)
+        flw ft1, 0x84(a0)
+        flw ft2, 0x88(a0)
+        flw ft3, 0x8C(a0)
+        flw ft4, 0x90(a0)
+        flw ft5, 0x94(a0)
+        flw ft6, 0x98(a0)
+        flw ft7, 0x9C(a0)
+        flw fs0, 0xA0(a0)
+        flw fs1, 0xA4(a0)
+        flw fa0, 0xA8(a0)
+        flw fa1, 0xAC(a0)
+        flw fa2, 0xB0(a0)
+        flw fa3, 0xB4(a0)
+        flw fa4, 0xB8(a0)
+        flw fa5, 0xBC(a0)
+        flw fa6, 0xC0(a0)
+        flw fa7, 0xC4(a0)
+        flw fs2, 0xC8(a0)
+        flw fs3, 0xCC(a0)
+        flw fs4, 0xD0(a0)
+        flw fs5, 0xD4(a0)
+        flw fs6, 0xD8(a0)
+        flw fs7, 0xDC(a0)
+        flw fs8, 0xE0(a0)
+        flw fs9, 0xE4(a0)
+        flw fs10, 0xE8(a0)
+        flw fs11, 0xEC(a0)
+        flw ft8, 0xF0(a0)
+        flw ft9, 0xF4(a0)
+        flw ft10, 0xF8(a0)
+        flw ft11, 0xFC(a0)
+        "
+    };
 }
 
-#[naked]
-pub extern "C-unwind" fn save_context(f: extern "C" fn(&mut Context, *mut ()), ptr: *mut ()) {
-    // No need to save caller-saved registers here.
-    #[cfg(target_feature = "d")]
-    unsafe {
+macro_rules! save {
+    ($frame:literal, $ra:literal$(, $code:ident)*) => {
+        // No need to save caller-saved registers here.
         core::arch::naked_asm!(
             "
             mv t0, sp
-            add sp, sp, -0x190
-            .cfi_def_cfa_offset 0x190
-            sw ra, 0x180(sp)
+            add sp, sp, -{frame}
+            .cfi_def_cfa_offset {frame}
+            sw ra, {ra}(sp)
             .cfi_offset ra, -16
             ",
-            code!(save_gp),
-            code!(save_fp),
+            $(code!($code),)*
             "
             mv t0, a0
             mv a0, sp
             jalr t0
-            lw ra, 0x180(sp)
-            add sp, sp, 0x190
+            lw ra, {ra}(sp)
+            add sp, sp, {frame}
             .cfi_def_cfa_offset 0
             .cfi_restore ra
             ret
             ",
+            frame = const $frame,
+            ra = const $ra,
         );
-    }
-    #[cfg(not(target_feature = "d"))]
+    };
+}
+
+#[naked]
+pub extern "C-unwind" fn save_context(f: extern "C" fn(&mut Context, *mut ()), ptr: *mut ()) {
     unsafe {
-        core::arch::naked_asm!(
-            "
-            mv t0, sp
-            add sp, sp, -0x90
-            .cfi_def_cfa_offset 0x90
-            sw ra, 0x80(sp)
-            .cfi_offset ra, -16
-            ",
-            code!(save_gp),
-            "
-            mv t0, a0
-            mv a0, sp
-            jalr t0
-            lw ra, 0x80(sp)
-            add sp, sp, 0x90
-            .cfi_def_cfa_offset 0
-            .cfi_restore ra
-            ret
-            ",
-        );
+        #[cfg(target_feature = "e")]
+        save!(0x50, 0x40, save_gp_e);
+        #[cfg(all(not(target_feature = "e"), target_feature = "d"))]
+        save!(0x190, 0x180, save_gp, save_fp);
+        #[cfg(all(
+            not(target_feature = "e"),
+            target_feature = "f",
+            not(target_feature = "d")
+        ))]
+        save!(0x110, 0x100, save_gp, save_fp_single);
+        #[cfg(all(not(target_feature = "e"), not(target_feature = "f")))]
+        save!(0x90, 0x80, save_gp);
     }
 }
 
-pub unsafe fn restore_context(ctx: &Context) -> ! {
-    #[cfg(target_feature = "d")]
-    unsafe {
+macro_rules! restore {
+    ($ctx:expr$(, $code:ident)*) => {
         core::arch::asm!(
-            code!(restore_fp),
-            code!(restore_gp),
+            $(code!($code),)*
             "
             lw a0, 0x28(a0)
             ret
             ",
-            in("a0") ctx,
+            in("a0") $ctx,
             options(noreturn)
         );
-    }
-    #[cfg(not(target_feature = "d"))]
+    };
+}
+
+pub unsafe fn restore_context(ctx: &Context) -> ! {
     unsafe {
-        core::arch::asm!(
-            code!(restore_gp),
-            "
-            lw a0, 0x28(a0)
-            ret
-            ",
-            in("a0") ctx,
-            options(noreturn)
-        );
+        #[cfg(target_feature = "e")]
+        restore!(ctx, restore_gp_e);
+        #[cfg(all(not(target_feature = "e"), target_feature = "d"))]
+        restore!(ctx, restore_fp, restore_gp);
+        #[cfg(all(
+            not(target_feature = "e"),
+            target_feature = "f",
+            not(target_feature = "d")
+        ))]
+        restore!(ctx, restore_fp_single, restore_gp);
+        #[cfg(all(not(target_feature = "e"), not(target_feature = "f")))]
+        restore!(ctx, restore_gp);
     }
 }
@@ -5,24 +5,33 @@ use gimli::{Register, RiscV};
 // Match DWARF_FRAME_REGISTERS in libgcc
 pub const MAX_REG_RULES: usize = 65;
 
-#[cfg(all(target_feature = "f", not(target_feature = "d")))]
-compile_error!("RISC-V with only F extension is not supported");
+// The ILP32E and LP64E ABIs are soft-float only.
+#[cfg(all(target_feature = "e", target_feature = "f"))]
+compile_error!("RISC-V with both E and F extensions is not supported");
 
 #[repr(C)]
 #[derive(Clone, Default)]
 pub struct Context {
+    #[cfg(not(target_feature = "e"))]
     pub gp: [usize; 32],
+    /// Only x0 to x15 exist with the E extension.
+    #[cfg(target_feature = "e")]
+    pub gp: [usize; 16],
     #[cfg(target_feature = "d")]
     pub fp: [usize; 32],
+    /// The single-precision registers are kept in the low half of each entry if only the F
+    /// extension is implemented.
+    #[cfg(all(target_feature = "f", not(target_feature = "d")))]
+    pub fp: [usize; 32],
 }
 
 impl fmt::Debug for Context {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut fmt = fmt.debug_struct("Context");
-        for i in 0..=31 {
-            fmt.field(RiscV::register_name(Register(i as _)).unwrap(), &self.gp[i]);
+        for (i, gp) in self.gp.iter().enumerate() {
+            fmt.field(RiscV::register_name(Register(i as _)).unwrap(), gp);
         }
-        #[cfg(target_feature = "d")]
+        #[cfg(target_feature = "f")]
         for i in 0..=31 {
             fmt.field(
                 RiscV::register_name(Register((i + 32) as _)).unwrap(),
@@ -38,8 +47,11 @@ impl ops::Index<Register> for Context {
 
     fn index(&self, reg: Register) -> &usize {
         match reg {
+            #[cfg(not(target_feature = "e"))]
             Register(0..=31) => &self.gp[reg.0 as usize],
-            #[cfg(target_feature = "d")]
+            #[cfg(target_feature = "e")]
+            Register(0..=15) => &self.gp[reg.0 as usize],
+            #[cfg(target_feature = "f")]
             Register(32..=63) => &self.fp[(reg.0 - 32) as usize],
             _ => unimplemented!(),
         }
@@ -49,14 +61,23 @@ impl ops::Index<Register> for Context {
 impl ops::IndexMut<gimli::Register> for Context {
     fn index_mut(&mut self, reg: Register) -> &mut usize {
         match reg {
+            #[cfg(not(target_feature = "e"))]
             Register(0..=31) => &mut self.gp[reg.0 as usize],
-            #[cfg(target_feature = "d")]
+            #[cfg(target_feature = "e")]
+            Register(0..=15) => &mut self.gp[reg.0 as usize],
+            #[cfg(target_feature = "f")]
             Register(32..=63) => &mut self.fp[(reg.0 - 32) as usize],
             _ => unimplemented!(),
         }
     }
 }
 
+/// Whether `reg` is saved as a 32-bit word rather than a `usize`.
+#[cfg(all(target_feature = "f", not(target_feature = "d")))]
+pub fn is_word_register(reg: Register) -> bool {
+    matches!(reg, Register(32..=63))
+}
+
 macro_rules! code {
     (save_gp) => {
         "
@@ -165,84 +186,162 @@ macro_rules! code {
         fld ft11, 0x1F8(a0)
         "
     };
+    (save_gp_e) => {
+        "
+        sd x0, 0x00(sp)
+        sd ra, 0x08(sp)
+        sd t0, 0x10(sp)
+        sd gp, 0x18(sp)
+        sd tp, 0x20(sp)
+        sd s0, 0x40(sp)
+        sd s1, 0x48(sp)
+        "
+    };
+    (restore_gp_e) => {
+        "
+        ld ra, 0x08(a0)
+        ld sp, 0x10(a0)
+        ld gp, 0x18(a0)
+        ld tp, 0x20(a0)
+        ld t0, 0x28(a0)
+        ld t1, 0x30(a0)
+        ld t2, 0x38(a0)
+        ld s0, 0x40(a0)
+        ld s1, 0x48(a0)
+        ld a1, 0x58(a0)
+        ld a2, 0x60(a0)
+        ld a3, 0x68(a0)
+        ld a4, 0x70(a0)
+        ld a5, 0x78(a0)
+        "
+    };
+    (save_fp_single) => {
+        "
+        fsw fs0, 0x140(sp)
+        fsw fs1, 0x148(sp)
+        fsw fs2, 0x190(sp)
+        fsw fs3, 0x198(sp)
+        fsw fs4, 0x1A0(sp)
+        fsw fs5, 0x1A8(sp)
+        fsw fs6, 0x1B0(sp)
+        fsw fs7, 0x1B8(sp)
+        fsw fs8, 0x1C0(sp)
+        fsw fs9, 0x1C8(sp)
+        fsw fs10, 0x1D0(sp)
+        fsw fs11, 0x1D8(sp)
+        "
+    };
+    (restore_fp_single) => {
+        "
+        flw ft0, 0x100(a0)
+        flw ft1, 0x108(a0)
+        flw ft2, 0x110(a0)
+        flw ft3, 0x118(a0)
+        flw ft4, 0x120(a0)
+        flw ft5, 0x128(a0)
+        flw ft6, 0x130(a0)
+        flw ft7, 0x138(a0)
+        flw fs0, 0x140(a0)
+        flw fs1, 0x148(a0)
+        flw fa0, 0x150(a0)
+        flw fa1, 0x158(a0)
+        flw fa2, 0x160(a0)
+        flw fa3, 0x168(a0)
+        flw fa4, 0x170(a0)
+        flw fa5, 0x178(a0)
+        flw fa6, 0x180(a0)
+        flw fa7, 0x188(a0)
+        flw fs2, 0x190(a0)
+        flw fs3, 0x198(a0)
+        flw fs4, 0x1A0(a0)
+        flw fs5, 0x1A8(a0)
+        flw fs6, 0x1B0(a0)
+        flw fs7, 0x1B8(a0)
+        flw fs8, 0x1C0(a0)
+        flw fs9, 0x1C8(a0)
+        flw fs10, 0x1D0(a0)
+        flw fs11, 0x1D8(a0)
+        flw ft8, 0x1E0(a0)
+        flw ft9, 0x1E8(a0)
+        flw ft10, 0x1F0(a0)
+        flw ft11, 0x1F8(a0)
+        "
+    };
 }
 
-#[naked]
-pub extern "C-unwind" fn save_context(f: extern "C" fn(&mut Context, *mut ()), ptr: *mut ()) {
-    // No need to save caller-saved registers here.
-    #[cfg(target_feature = "d")]
-    unsafe {
+macro_rules! save {
+    ($frame:literal, $ra:literal$(, $code:ident)*) => {
+        // No need to save caller-saved registers here.
         core::arch::naked_asm!(
             "
             mv t0, sp
-            add sp, sp, -0x210
-            .cfi_def_cfa_offset 0x210
-            sd ra, 0x200(sp)
+            add sp, sp, -{frame}
+            .cfi_def_cfa_offset {frame}
+            sd ra, {ra}(sp)
             .cfi_offset ra, -16
             ",
-            code!(save_gp),
-            code!(save_fp),
+            $(code!($code),)*
             "
             mv t0, a0
             mv a0, sp
             jalr t0
-            ld ra, 0x200(sp)
-            add sp, sp, 0x210
+            ld ra, {ra}(sp)
+            add sp, sp, {frame}
             .cfi_def_cfa_offset 0
             .cfi_restore ra
             ret
             ",
+            frame = const $frame,
+            ra = const $ra,
         );
-    }
-    #[cfg(not(target_feature = "d"))]
+    };
+}
+
+#[naked]
+pub extern "C-unwind" fn save_context(f: extern "C" fn(&mut Context, *mut ()), ptr: *mut ()) {
     unsafe {
-        core::arch::naked_asm!(
-            "
-            mv t0, sp
-            add sp, sp, -0x110
-            .cfi_def_cfa_offset 0x110
-            sd ra, 0x100(sp)
-            .cfi_offset ra, -16
-            ",
-            code!(save_gp),
-            "
-            mv t0, a0
-            mv a0, sp
-            jalr t0
-            ld ra, 0x100(sp)
-            add sp, sp, 0x110
-            .cfi_def_cfa_offset 0
-            .cfi_restore ra
-            ret
-            ",
-        );
+        #[cfg(target_feature = "e")]
+        save!(0x90, 0x80, save_gp_e);
+        #[cfg(all(not(target_feature = "e"), target_feature = "d"))]
+        save!(0x210, 0x200, save_gp, save_fp);
+        #[cfg(all(
+            not(target_feature = "e"),
+            target_feature = "f",
+            not(target_feature = "d")
+        ))]
+        save!(0x210, 0x200, save_gp, save_fp_single);
+        #[cfg(all(not(target_feature = "e"), not(target_feature = "f")))]
+        save!(0x110, 0x100, save_gp);
     }
 }
 
-pub unsafe fn restore_context(ctx: &Context) -> ! {
-    #[cfg(target_feature = "d")]
-    unsafe {
+macro_rules! restore {
+    ($ctx:expr$(, $code:ident)*) => {
         core::arch::asm!(
-            code!(restore_fp),
-            code!(restore_gp),
+            $(code!($code),)*
             "
             ld a0, 0x50(a0)
             ret
             ",
-            in("a0") ctx,
+            in("a0") $ctx,
             options(noreturn)
         );
-    }
-    #[cfg(not(target_feature = "d"))]
+    };
+}
+
+pub unsafe fn restore_context(ctx: &Context) -> ! {
     unsafe {
-        core::arch::asm!(
-            code!(restore_gp),
-            "
-            ld a0, 0x50(a0)
-            ret
-            ",
-            in("a0") ctx,
-            options(noreturn)
-        );
+        #[cfg(target_feature = "e")]
+        restore!(ctx, restore_gp_e);
+        #[cfg(all(not(target_feature = "e"), target_feature = "d"))]
+        restore!(ctx, restore_fp, restore_gp);
+        #[cfg(all(
+            not(target_feature = "e"),
+            target_feature = "f",
+            not(target_feature = "d")
+        ))]
+        restore!(ctx, restore_fp_single, restore_gp);
+        #[cfg(all(not(target_feature = "e"), not(target_feature = "f")))]
+        restore!(ctx, restore_gp);
     }
 }
@@ -0,0 +1,184 @@
+use core::fmt;
+use core::ops;
+use gimli::Register;
+
+// The registers up to the PSW address, which is described by signal frames.
+pub const MAX_REG_RULES: usize = 66;
+
+// gimli doesn't know the s390x registers. The floating-point registers aren't numbered in order.
+const FP_REGS: [usize; 16] = [0, 2, 4, 6, 1, 3, 5, 7, 8, 10, 12, 14, 9, 11, 13, 15];
+
+const GP_NAMES: [&str; 16] = [
+    "r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7", "r8", "r9", "r10", "r11", "r12", "r13", "r14",
+    "r15",
+];
+const FP_NAMES: [&str; 16] = [
+    "f0", "f1", "f2", "f3", "f4", "f5", "f6", "f7", "f8", "f9", "f10", "f11", "f12", "f13", "f14",
+    "f15",
+];
+const AR_NAMES: [&str; 16] = [
+    "a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7", "a8", "a9", "a10", "a11", "a12", "a13", "a14",
+    "a15",
+];
+
+#[repr(C)]
+#[derive(Clone, Default)]
+pub struct Context {
+    pub gp: [usize; 16],
+    /// Indexed by the number of the register rather than its DWARF number.
+    pub fp: [usize; 16],
+    /// The access registers are 32 bits wide, and are kept zero-extended.
+    pub ar: [usize; 16],
+}
+
+impl fmt::Debug for Context {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut fmt = fmt.debug_struct("Context");
+        for (i, gp) in self.gp.iter().enumerate() {
+            fmt.field(GP_NAMES[i], gp);
+        }
+        for (i, fp) in self.fp.iter().enumerate() {
+            fmt.field(FP_NAMES[i], fp);
+        }
+        for (i, ar) in self.ar.iter().enumerate() {
+            fmt.field(AR_NAMES[i], ar);
+        }
+        fmt.finish()
+    }
+}
+
+impl ops::Index<Register> for Context {
+    type Output = usize;
+
+    fn index(&self, reg: Register) -> &usize {
+        match reg {
+            Register(0..=15) => &self.gp[reg.0 as usize],
+            Register(16..=31) => &self.fp[FP_REGS[(reg.0 - 16) as usize]],
+            Register(48..=63) => &self.ar[(reg.0 - 48) as usize],
+            _ => unimplemented!(),
+        }
+    }
+}
+
+impl ops::IndexMut<gimli::Register> for Context {
+    fn index_mut(&mut self, reg: Register) -> &mut usize {
+        match reg {
+            Register(0..=15) => &mut self.gp[reg.0 as usize],
+            Register(16..=31) => &mut self.fp[FP_REGS[(reg.0 - 16) as usize]],
+            Register(48..=63) => &mut self.ar[(reg.0 - 48) as usize],
+            _ => unimplemented!(),
+        }
+    }
+}
+
+/// Whether `reg` is saved as a 32-bit word rather than a `usize`.
+pub fn is_word_register(reg: Register) -> bool {
+    matches!(reg, Register(48..=63))
+}
+
+#[naked]
+pub extern "C-unwind" fn save_context(f: extern "C" fn(&mut Context, *mut ()), ptr: *mut ()) {
+    // No need to save caller-saved registers here. The context is placed above the 160-byte
+    // register save area our callee may use, and the CFA is 160 bytes above the stack pointer on
+    // entry.
+    unsafe {
+        core::arch::naked_asm!(
+            "
+            stmg %r14, %r15, 112(%r15)
+            .cfi_offset 14, -48
+            .cfi_offset 15, -40
+            aghi %r15, -0x220
+            .cfi_def_cfa_offset 0x2C0
+            la %r4, 160(%r15)
+
+            stmg %r6, %r14, 0x30(%r4)
+            la %r0, 0x220(%r15)
+            stg %r0, 0x78(%r4)
+
+            std %f8, 0xC0(%r4)
+            std %f9, 0xC8(%r4)
+            std %f10, 0xD0(%r4)
+            std %f11, 0xD8(%r4)
+            std %f12, 0xE0(%r4)
+            std %f13, 0xE8(%r4)
+            std %f14, 0xF0(%r4)
+            std %f15, 0xF8(%r4)
+
+            xc 0x100(128, %r4), 0x100(%r4)
+            ear %r0, %a0
+            st %r0, 0x104(%r4)
+            ear %r0, %a1
+            st %r0, 0x10C(%r4)
+            ear %r0, %a2
+            st %r0, 0x114(%r4)
+            ear %r0, %a3
+            st %r0, 0x11C(%r4)
+            ear %r0, %a4
+            st %r0, 0x124(%r4)
+            ear %r0, %a5
+            st %r0, 0x12C(%r4)
+            ear %r0, %a6
+            st %r0, 0x134(%r4)
+            ear %r0, %a7
+            st %r0, 0x13C(%r4)
+            ear %r0, %a8
+            st %r0, 0x144(%r4)
+            ear %r0, %a9
+            st %r0, 0x14C(%r4)
+            ear %r0, %a10
+            st %r0, 0x154(%r4)
+            ear %r0, %a11
+            st %r0, 0x15C(%r4)
+            ear %r0, %a12
+            st %r0, 0x164(%r4)
+            ear %r0, %a13
+            st %r0, 0x16C(%r4)
+            ear %r0, %a14
+            st %r0, 0x174(%r4)
+            ear %r0, %a15
+            st %r0, 0x17C(%r4)
+
+            lgr %r1, %r2
+            lgr %r2, %r4
+            basr %r14, %r1
+
+            lmg %r14, %r15, 0x290(%r15)
+            .cfi_def_cfa_offset 160
+            .cfi_restore 14
+            .cfi_restore 15
+            br %r14
+            ",
+        );
+    }
+}
+
+pub unsafe fn restore_context(ctx: &Context) -> ! {
+    // The access registers only hold the thread pointer, which unwinding doesn't change.
+    unsafe {
+        core::arch::asm!(
+            "
+            ld %f0, 0x80(%r2)
+            ld %f1, 0x88(%r2)
+            ld %f2, 0x90(%r2)
+            ld %f3, 0x98(%r2)
+            ld %f4, 0xA0(%r2)
+            ld %f5, 0xA8(%r2)
+            ld %f6, 0xB0(%r2)
+            ld %f7, 0xB8(%r2)
+            ld %f8, 0xC0(%r2)
+            ld %f9, 0xC8(%r2)
+            ld %f10, 0xD0(%r2)
+            ld %f11, 0xD8(%r2)
+            ld %f12, 0xE0(%r2)
+            ld %f13, 0xE8(%r2)
+            ld %f14, 0xF0(%r2)
+            ld %f15, 0xF8(%r2)
+
+            lmg %r0, %r15, 0(%r2)
+            br %r14
+            ",
+            in("r2") ctx,
+            options(noreturn)
+        );
+    }
+}
@@ -5,13 +5,47 @@ use gimli::{Register, X86_64};
 // Match DWARF_FRAME_REGISTERS in libgcc
 pub const MAX_REG_RULES: usize = 17;
 
+/// The storage of a register, which is 64 bits wide even on x32. There `usize` only covers the low
+/// half, and values written as a `usize` are zero-extended, as pointers are.
+#[cfg(target_pointer_width = "64")]
+pub type Word = usize;
+#[cfg(target_pointer_width = "32")]
+pub type Word = u64;
+
 #[repr(C)]
 #[derive(Clone, Default)]
 pub struct Context {
-    pub registers: [usize; 16],
-    pub ra: usize,
-    pub mcxsr: usize,
-    pub fcw: usize,
+    pub registers: [Word; 16],
+    pub ra: Word,
+    pub mcxsr: Word,
+    pub fcw: Word,
+    /// The shadow stack pointer, or 0 if shadow stacks are not enabled. A context on a different
+    /// shadow stack can't be installed.
+    pub ssp: Word,
+}
+
+impl Context {
+    /// The full value of `reg`.
+    pub fn wide(&self, reg: Register) -> &Word {
+        match reg {
+            Register(0..=15) => &self.registers[reg.0 as usize],
+            X86_64::RA => &self.ra,
+            X86_64::MXCSR => &self.mcxsr,
+            X86_64::FCW => &self.fcw,
+            _ => unimplemented!(),
+        }
+    }
+
+    /// The full value of `reg`.
+    pub fn wide_mut(&mut self, reg: Register) -> &mut Word {
+        match reg {
+            Register(0..=15) => &mut self.registers[reg.0 as usize],
+            X86_64::RA => &mut self.ra,
+            X86_64::MXCSR => &mut self.mcxsr,
+            X86_64::FCW => &mut self.fcw,
+            _ => unimplemented!(),
+        }
+    }
 }
 
 impl fmt::Debug for Context {
@@ -26,6 +60,7 @@ impl fmt::Debug for Context {
         fmt.field("ra", &self.ra)
             .field("mcxsr", &self.mcxsr)
             .field("fcw", &self.fcw)
+            .field("ssp", &self.ssp)
             .finish()
     }
 }
@@ -34,25 +69,19 @@ impl ops::Index<Register> for Context {
     type Output = usize;
 
     fn index(&self, reg: Register) -> &usize {
-        match reg {
-            Register(0..=15) => &self.registers[reg.0 as usize],
-            X86_64::RA => &self.ra,
-            X86_64::MXCSR => &self.mcxsr,
-            X86_64::FCW => &self.fcw,
-            _ => unimplemented!(),
-        }
+        // The low half comes first.
+        unsafe { &*(self.wide(reg) as *const Word).cast::<usize>() }
     }
 }
 
 impl ops::IndexMut<gimli::Register> for Context {
     fn index_mut(&mut self, reg: Register) -> &mut usize {
-        match reg {
-            Register(0..=15) => &mut self.registers[reg.0 as usize],
-            X86_64::RA => &mut self.ra,
-            X86_64::MXCSR => &mut self.mcxsr,
-            X86_64::FCW => &mut self.fcw,
-            _ => unimplemented!(),
+        let value = self.wide_mut(reg);
+        #[cfg(target_pointer_width = "32")]
+        {
+            *value &= u32::MAX as Word;
         }
+        unsafe { &mut *(value as *mut Word).cast::<usize>() }
     }
 }
 
@@ -62,13 +91,13 @@ pub extern "C-unwind" fn save_context(f: extern "C" fn(&mut Context, *mut ()), p
     unsafe {
         core::arch::naked_asm!(
             "
-            sub rsp, 0x98
-            .cfi_def_cfa_offset 0xA0
+            sub rsp, 0xA8
+            .cfi_def_cfa_offset 0xB0
             mov [rsp + 0x18], rbx
             mov [rsp + 0x30], rbp
 
             /* Adjust the stack to account for the return address */
-            lea rax, [rsp + 0xA0]
+            lea rax, [rsp + 0xB0]
             mov [rsp + 0x38], rax
 
             mov [rsp + 0x60], r12
@@ -77,16 +106,28 @@ pub extern "C-unwind" fn save_context(f: extern "C" fn(&mut Context, *mut ()), p
             mov [rsp + 0x78], r15
 
             /* Return address */
-            mov rax, [rsp + 0x98]
+            mov rax, [rsp + 0xA8]
             mov [rsp + 0x80], rax
 
             stmxcsr [rsp + 0x88]
             fnstcw [rsp + 0x90]
 
+            /*
+            * Shadow stack pointer, adjusted like the stack. RDSSPQ is a no-op if
+            * shadow stacks are not enabled.
+            */
+            xor eax, eax
+            rdsspq rax
+            test rax, rax
+            jz 2f
+            add rax, 8
+            2:
+            mov [rsp + 0x98], rax
+
             mov rax, rdi
             mov rdi, rsp
             call rax
-            add rsp, 0x98
+            add rsp, 0xA8
             .cfi_def_cfa_offset 8
             ret
             "
@@ -105,6 +146,26 @@ pub unsafe fn restore_context(ctx: &Context) -> ! {
             ldmxcsr [rdi + 0x88]
             fldcw [rdi + 0x90]
 
+            /*
+            * Pop the return addresses of the frames unwound off the shadow stack,
+            * at most 255 at a time.
+            */
+            mov rcx, [rdi + 0x98]
+            test rcx, rcx
+            jz 3f
+            rdsspq rax
+            sub rcx, rax
+            jbe 3f
+            shr rcx, 3
+            2:
+            mov eax, 255
+            cmp rcx, rax
+            cmovb rax, rcx
+            incsspq rax
+            sub rcx, rax
+            jnz 2b
+            3:
+
             /* Restore return address */
             mov rax, [rdi + 0x80]
             push rax
@@ -131,9 +192,14 @@ pub unsafe fn restore_context(ctx: &Context) -> ! {
             /* RDI restored last */
             mov rdi, [rdi + 0x28]
 
-            ret
+            /*
+            * Jump rather than return, as the return address is not on the shadow
+            * stack.
+            */
+            lea rsp, [rsp + 8]
+            notrack jmp [rsp - 8]
             ",
-            in("rdi") ctx,
+            in("rdi") ctx as *const Context as u64,
             options(noreturn)
         );
     }
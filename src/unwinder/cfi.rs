@@ -0,0 +1,701 @@
+//! A small CFI parser and interpreter, used instead of gimli's with `cfi-minimal`.
+//!
+//! Only what rustc, LLVM and GCC emit is supported: `DW_CFA_set_loc` and function-relative or
+//! aligned pointers are not. Expressions are passed through to the DWARF expression evaluator,
+//! which is only available with `dwarf-expr`.
+
+use gimli::{
+    constants, BaseAddresses, CfaRule, Format, Pointer, Reader, Register, RegisterRule,
+    UnwindExpression,
+};
+
+use super::arch::MAX_REG_RULES;
+use super::find_fde::{FDESearchResult, FrameSection};
+use crate::util::StaticSlice;
+
+type Result<T> = core::result::Result<T, gimli::Error>;
+
+/// Maximum number of registers with a rule in a single row. Functions rarely save more than the
+/// callee-saved registers, so this is kept well below the number of registers on most targets.
+const MAX_RULES: usize = if MAX_REG_RULES < 32 {
+    MAX_REG_RULES
+} else {
+    32
+};
+
+/// Maximum depth of `DW_CFA_remember_state`.
+const MAX_STATES: usize = 2;
+
+const NO_RULE: (Register, RegisterRule<usize>) = (Register(0), RegisterRule::Undefined);
+
+const EMPTY_ROW: Row = Row::new();
+
+/// The section CIEs and FDEs are read from. They are laid out slightly differently in each.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    EhFrame,
+    #[cfg(feature = "fde-debug-frame")]
+    DebugFrame,
+}
+
+/// The parts of a CIE needed to parse its FDEs and run their instructions.
+struct Cie {
+    #[cfg(feature = "dwarf-expr")]
+    encoding: gimli::Encoding,
+    address_size: u8,
+    code_alignment: u64,
+    data_alignment: i64,
+    address_encoding: constants::DwEhPe,
+    lsda_encoding: constants::DwEhPe,
+    personality: Option<Pointer>,
+    signal_trampoline: bool,
+    has_augmentation_data: bool,
+    instructions: StaticSlice,
+}
+
+/// A parsed FDE, along with what it needs from its CIE.
+#[derive(Clone, Debug)]
+pub struct Fde {
+    // Only used to build FDE tables.
+    #[cfg_attr(
+        not(any(
+            feature = "fde-static",
+            feature = "fde-static-regions",
+            feature = "fde-gnu-eh-frame-hdr",
+            feature = "fde-registry",
+            all(feature = "fde-custom", target_has_atomic = "ptr")
+        )),
+        allow(dead_code)
+    )]
+    offset: usize,
+    initial_address: u64,
+    address_range: u64,
+    lsda: Option<Pointer>,
+    personality: Option<Pointer>,
+    signal_trampoline: bool,
+    code_alignment: u64,
+    data_alignment: i64,
+    #[cfg(feature = "dwarf-expr")]
+    encoding: gimli::Encoding,
+    cie_instructions: StaticSlice,
+    instructions: StaticSlice,
+}
+
+impl Fde {
+    /// The offset of the FDE in its section.
+    #[cfg_attr(
+        not(any(
+            feature = "fde-static",
+            feature = "fde-static-regions",
+            feature = "fde-gnu-eh-frame-hdr",
+            feature = "fde-registry",
+            all(feature = "fde-custom", target_has_atomic = "ptr")
+        )),
+        allow(dead_code)
+    )]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn initial_address(&self) -> u64 {
+        self.initial_address
+    }
+
+    #[cfg_attr(
+        not(any(
+            feature = "fde-static",
+            feature = "fde-static-regions",
+            feature = "fde-gnu-eh-frame-hdr",
+            feature = "fde-registry",
+            all(feature = "fde-custom", target_has_atomic = "ptr")
+        )),
+        allow(dead_code)
+    )]
+    pub fn len(&self) -> u64 {
+        self.address_range
+    }
+
+    pub fn contains(&self, address: u64) -> bool {
+        address
+            .checked_sub(self.initial_address)
+            .is_some_and(|offset| offset < self.address_range)
+    }
+
+    pub fn lsda(&self) -> Option<Pointer> {
+        self.lsda
+    }
+
+    pub fn personality(&self) -> Option<Pointer> {
+        self.personality
+    }
+
+    pub fn is_signal_trampoline(&self) -> bool {
+        self.signal_trampoline
+    }
+
+    /// The encoding of the DWARF expressions in the instructions.
+    #[cfg(feature = "dwarf-expr")]
+    pub fn encoding(&self) -> gimli::Encoding {
+        self.encoding
+    }
+}
+
+/// Reads a pointer encoded with `encoding`. `input` is part of `section`.
+fn read_pointer(
+    input: &mut StaticSlice,
+    encoding: constants::DwEhPe,
+    address_size: u8,
+    section: &StaticSlice,
+    bases: &BaseAddresses,
+) -> Result<Pointer> {
+    let bases = &bases.eh_frame;
+    let base = match encoding.application() {
+        constants::DW_EH_PE_absptr => 0,
+        constants::DW_EH_PE_pcrel => bases
+            .section
+            .ok_or(gimli::Error::PcRelativePointerButSectionBaseIsUndefined)?
+            .wrapping_add(input.offset_from(*section) as u64),
+        constants::DW_EH_PE_textrel => bases
+            .text
+            .ok_or(gimli::Error::TextRelativePointerButTextBaseIsUndefined)?,
+        constants::DW_EH_PE_datarel => bases
+            .data
+            .ok_or(gimli::Error::DataRelativePointerButDataBaseIsUndefined)?,
+        _ => return Err(gimli::Error::UnsupportedPointerEncoding),
+    };
+    let offset = read_offset(input, encoding, address_size)?;
+    let address = base.wrapping_add(offset);
+    Ok(if encoding.is_indirect() {
+        Pointer::Indirect(address)
+    } else {
+        Pointer::Direct(address)
+    })
+}
+
+/// Reads the value of a pointer encoded with `encoding`, without applying its base.
+fn read_offset(
+    input: &mut StaticSlice,
+    encoding: constants::DwEhPe,
+    address_size: u8,
+) -> Result<u64> {
+    match encoding.format() {
+        constants::DW_EH_PE_absptr => input.read_address(address_size),
+        constants::DW_EH_PE_uleb128 => input.read_uleb128(),
+        constants::DW_EH_PE_udata2 => input.read_u16().map(u64::from),
+        constants::DW_EH_PE_udata4 => input.read_u32().map(u64::from),
+        constants::DW_EH_PE_udata8 => input.read_u64(),
+        constants::DW_EH_PE_sleb128 => input.read_sleb128().map(|a| a as u64),
+        constants::DW_EH_PE_sdata2 => input.read_i16().map(|a| a as u64),
+        constants::DW_EH_PE_sdata4 => input.read_i32().map(|a| a as u64),
+        constants::DW_EH_PE_sdata8 => input.read_i64().map(|a| a as u64),
+        _ => Err(gimli::Error::UnknownPointerEncoding(encoding)),
+    }
+}
+
+fn read_encoding(input: &mut StaticSlice) -> Result<constants::DwEhPe> {
+    Ok(constants::DwEhPe(input.read_u8()?))
+}
+
+/// A CIE or FDE.
+struct Entry {
+    /// The offset of the entry in the section.
+    offset: usize,
+    #[cfg_attr(
+        not(any(feature = "fde-debug-frame", feature = "dwarf-expr")),
+        allow(dead_code)
+    )]
+    format: Format,
+    /// The CIE ID of a CIE, or the CIE pointer of an FDE.
+    id: u64,
+    /// The offset of `id` in the section.
+    id_offset: usize,
+    /// The rest of the entry after `id`.
+    contents: StaticSlice,
+}
+
+/// Reads the entry at the start of `input`, which is part of `section`. Returns `None` at the
+/// terminator of `.eh_frame`.
+fn read_entry(input: &mut StaticSlice, section: &StaticSlice) -> Result<Option<Entry>> {
+    let offset = input.offset_from(*section);
+    let (len, format) = input.read_initial_length()?;
+    if len == 0 {
+        return Ok(None);
+    }
+    let id_offset = input.offset_from(*section);
+    let mut contents = input.split(len)?;
+    let id = contents.read_offset(format)? as u64;
+    Ok(Some(Entry {
+        offset,
+        format,
+        id,
+        id_offset,
+        contents,
+    }))
+}
+
+/// Reads the entry at `offset` in `section`.
+fn entry_at(section: &StaticSlice, offset: usize) -> Result<Entry> {
+    let mut input = *section;
+    input.skip(offset)?;
+    read_entry(&mut input, section)?.ok_or(gimli::Error::NoEntryAtGivenOffset)
+}
+
+impl Entry {
+    fn is_cie(&self, kind: Kind) -> bool {
+        match kind {
+            Kind::EhFrame => self.id == 0,
+            #[cfg(feature = "fde-debug-frame")]
+            Kind::DebugFrame => match self.format {
+                Format::Dwarf32 => self.id == 0xffff_ffff,
+                Format::Dwarf64 => self.id == u64::MAX,
+            },
+        }
+    }
+}
+
+fn parse_cie(
+    section: &StaticSlice,
+    offset: usize,
+    kind: Kind,
+    bases: &BaseAddresses,
+) -> Result<Cie> {
+    let entry = entry_at(section, offset)?;
+    if !entry.is_cie(kind) {
+        return Err(gimli::Error::NotCieId);
+    }
+    let mut input = entry.contents;
+
+    let version = input.read_u8()?;
+    if !matches!(version, 1 | 3 | 4) {
+        return Err(gimli::Error::UnknownVersion(version.into()));
+    }
+    let augmentation = input.read_null_terminated_slice()?.slice();
+    let mut address_size = core::mem::size_of::<usize>() as u8;
+    if version == 4 {
+        address_size = input.read_address_size()?;
+        if input.read_u8()? != 0 {
+            return Err(gimli::Error::UnsupportedSegmentSize);
+        }
+    }
+    let code_alignment = input.read_uleb128()?;
+    let data_alignment = input.read_sleb128()?;
+    // The return address register is always the one of the architecture.
+    if version == 1 {
+        input.read_u8()?;
+    } else {
+        input.read_uleb128()?;
+    }
+
+    let mut cie = Cie {
+        #[cfg(feature = "dwarf-expr")]
+        encoding: gimli::Encoding {
+            address_size,
+            format: entry.format,
+            version: version.into(),
+        },
+        address_size,
+        code_alignment,
+        data_alignment,
+        address_encoding: constants::DW_EH_PE_absptr,
+        lsda_encoding: constants::DW_EH_PE_omit,
+        personality: None,
+        signal_trampoline: false,
+        has_augmentation_data: false,
+        instructions: input,
+    };
+    let Some((&b'z', augmentation)) = augmentation.split_first() else {
+        if !augmentation.is_empty() {
+            return Err(gimli::Error::UnknownAugmentation);
+        }
+        return Ok(cie);
+    };
+    let len = input.read_uleb128()?;
+    let mut data = input.split(len as usize)?;
+    for &c in augmentation {
+        match c {
+            b'L' => cie.lsda_encoding = read_encoding(&mut data)?,
+            b'P' => {
+                let encoding = read_encoding(&mut data)?;
+                let personality = read_pointer(&mut data, encoding, address_size, section, bases)?;
+                cie.personality = Some(personality);
+            }
+            b'R' => cie.address_encoding = read_encoding(&mut data)?,
+            b'S' => cie.signal_trampoline = true,
+            // Return addresses signed with the B key are stripped like those signed with the A key.
+            #[cfg(target_arch = "aarch64")]
+            b'B' => (),
+            _ => return Err(gimli::Error::UnknownAugmentation),
+        }
+    }
+    cie.has_augmentation_data = true;
+    cie.instructions = input;
+    Ok(cie)
+}
+
+/// Parses the FDE in `entry`.
+fn parse_fde(
+    section: &StaticSlice,
+    entry: Entry,
+    kind: Kind,
+    bases: &BaseAddresses,
+) -> Result<Fde> {
+    let cie_offset = match kind {
+        Kind::EhFrame => (entry.id_offset as u64).checked_sub(entry.id),
+        #[cfg(feature = "fde-debug-frame")]
+        Kind::DebugFrame => Some(entry.id),
+    }
+    .ok_or(gimli::Error::OffsetOutOfBounds)?;
+    let cie = parse_cie(section, cie_offset as usize, kind, bases)?;
+
+    let fde_offset = entry.offset;
+    let mut entry = entry.contents;
+    let initial_address = read_pointer(
+        &mut entry,
+        cie.address_encoding,
+        cie.address_size,
+        section,
+        bases,
+    )?
+    .direct()?;
+    let address_range = read_offset(&mut entry, cie.address_encoding, cie.address_size)?;
+    let mut lsda = None;
+    if cie.has_augmentation_data {
+        let len = entry.read_uleb128()?;
+        let mut data = entry.split(len as usize)?;
+        if cie.lsda_encoding != constants::DW_EH_PE_omit {
+            lsda = Some(read_pointer(
+                &mut data,
+                cie.lsda_encoding,
+                cie.address_size,
+                section,
+                bases,
+            )?);
+        }
+    }
+
+    Ok(Fde {
+        offset: fde_offset,
+        initial_address,
+        address_range,
+        lsda,
+        personality: cie.personality,
+        signal_trampoline: cie.signal_trampoline,
+        code_alignment: cie.code_alignment,
+        data_alignment: cie.data_alignment,
+        #[cfg(feature = "dwarf-expr")]
+        encoding: cie.encoding,
+        cie_instructions: cie.instructions,
+        instructions: entry,
+    })
+}
+
+/// Parses the FDE at `offset` in `section`.
+pub fn fde_from_offset(
+    section: &StaticSlice,
+    offset: usize,
+    kind: Kind,
+    bases: &BaseAddresses,
+) -> Result<Fde> {
+    let entry = entry_at(section, offset)?;
+    if entry.is_cie(kind) {
+        return Err(gimli::Error::NotFdePointer);
+    }
+    parse_fde(section, entry, kind, bases)
+}
+
+/// Iterates over the FDEs in `section`.
+pub fn fdes<'a>(
+    section: &'a StaticSlice,
+    kind: Kind,
+    bases: &'a BaseAddresses,
+) -> impl Iterator<Item = Result<Fde>> + 'a {
+    let mut input = *section;
+    core::iter::from_fn(move || loop {
+        if input.is_empty() {
+            return None;
+        }
+        match read_entry(&mut input, section) {
+            Ok(Some(entry)) if entry.is_cie(kind) => (),
+            Ok(Some(entry)) => return Some(parse_fde(section, entry, kind, bases)),
+            Ok(None) => return None,
+            Err(e) => {
+                input.empty();
+                return Some(Err(e));
+            }
+        }
+    })
+}
+
+/// The unwind rules in effect at a PC.
+#[derive(Clone, Debug)]
+pub struct Row {
+    cfa: CfaRule<usize>,
+    rules: [(Register, RegisterRule<usize>); MAX_RULES],
+    len: usize,
+    args_size: u64,
+}
+
+impl Row {
+    const fn new() -> Self {
+        Row {
+            cfa: CfaRule::RegisterAndOffset {
+                register: Register(0),
+                offset: 0,
+            },
+            rules: [NO_RULE; MAX_RULES],
+            len: 0,
+            args_size: 0,
+        }
+    }
+
+    pub fn cfa(&self) -> &CfaRule<usize> {
+        &self.cfa
+    }
+
+    pub fn registers(&self) -> impl Iterator<Item = &(Register, RegisterRule<usize>)> {
+        self.rules[..self.len].iter()
+    }
+
+    pub fn saved_args_size(&self) -> u64 {
+        self.args_size
+    }
+
+    fn register(&self, register: Register) -> RegisterRule<usize> {
+        self.registers()
+            .find(|(reg, _)| *reg == register)
+            .map_or(RegisterRule::Undefined, |(_, rule)| rule.clone())
+    }
+
+    fn set(&mut self, register: Register, rule: RegisterRule<usize>) -> Result<()> {
+        let idx = self.registers().position(|(reg, _)| *reg == register);
+        match (idx, rule) {
+            // Registers without a rule are undefined.
+            (Some(idx), RegisterRule::Undefined) => {
+                self.len -= 1;
+                self.rules.swap(idx, self.len);
+            }
+            (None, RegisterRule::Undefined) => (),
+            (Some(idx), rule) => self.rules[idx].1 = rule,
+            (None, rule) => {
+                if self.len == MAX_RULES {
+                    return Err(gimli::Error::TooManyRegisterRules);
+                }
+                self.rules[self.len] = (register, rule);
+                self.len += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_cfa_register(&mut self, register: Register) -> Result<()> {
+        match &mut self.cfa {
+            CfaRule::RegisterAndOffset { register: reg, .. } => *reg = register,
+            CfaRule::Expression(_) => return Err(gimli::Error::CfiInstructionInInvalidContext),
+        }
+        Ok(())
+    }
+
+    fn set_cfa_offset(&mut self, offset: i64) -> Result<()> {
+        match &mut self.cfa {
+            CfaRule::RegisterAndOffset { offset: off, .. } => *off = offset,
+            CfaRule::Expression(_) => return Err(gimli::Error::CfiInstructionInInvalidContext),
+        }
+        Ok(())
+    }
+}
+
+struct Interpreter<'a> {
+    fde: &'a Fde,
+    section: StaticSlice,
+    row: Row,
+    initial: Row,
+    states: [Row; MAX_STATES],
+    depth: usize,
+}
+
+impl Interpreter<'_> {
+    fn expression(&self, input: &mut StaticSlice) -> Result<UnwindExpression<usize>> {
+        let length = input.read_uleb128()? as usize;
+        let offset = input.offset_from(self.section);
+        input.skip(length)?;
+        Ok(UnwindExpression { offset, length })
+    }
+
+    fn offset(&self, factored: i64) -> RegisterRule<usize> {
+        RegisterRule::Offset(factored.wrapping_mul(self.fde.data_alignment))
+    }
+
+    /// Runs `instructions`, starting at `loc`, until the row covering `pc` is reached.
+    fn run(&mut self, mut input: StaticSlice, mut loc: u64, pc: u64) -> Result<()> {
+        while !input.is_empty() {
+            let opcode = input.read_u8()?;
+            let delta = match opcode >> 6 {
+                0x1 => Some(u64::from(opcode & 0x3f)),
+                0x2 => {
+                    let rule = self.offset(input.read_uleb128()? as i64);
+                    self.row.set(Register((opcode & 0x3f).into()), rule)?;
+                    None
+                }
+                0x3 => {
+                    self.restore(Register((opcode & 0x3f).into()))?;
+                    None
+                }
+                _ => match constants::DwCfa(opcode) {
+                    constants::DW_CFA_nop => None,
+                    constants::DW_CFA_advance_loc1 => Some(u64::from(input.read_u8()?)),
+                    constants::DW_CFA_advance_loc2 => Some(u64::from(input.read_u16()?)),
+                    constants::DW_CFA_advance_loc4 => Some(u64::from(input.read_u32()?)),
+                    constants::DW_CFA_offset_extended | constants::DW_CFA_offset_extended_sf => {
+                        let register = Register(input.read_uleb128_u16()?);
+                        let factored = if opcode == constants::DW_CFA_offset_extended.0 {
+                            input.read_uleb128()? as i64
+                        } else {
+                            input.read_sleb128()?
+                        };
+                        self.row.set(register, self.offset(factored))?;
+                        None
+                    }
+                    constants::DW_CFA_val_offset | constants::DW_CFA_val_offset_sf => {
+                        let register = Register(input.read_uleb128_u16()?);
+                        let factored = if opcode == constants::DW_CFA_val_offset.0 {
+                            input.read_uleb128()? as i64
+                        } else {
+                            input.read_sleb128()?
+                        };
+                        let offset = factored.wrapping_mul(self.fde.data_alignment);
+                        self.row.set(register, RegisterRule::ValOffset(offset))?;
+                        None
+                    }
+                    constants::DW_CFA_restore_extended => {
+                        self.restore(Register(input.read_uleb128_u16()?))?;
+                        None
+                    }
+                    constants::DW_CFA_undefined => {
+                        let register = Register(input.read_uleb128_u16()?);
+                        self.row.set(register, RegisterRule::Undefined)?;
+                        None
+                    }
+                    constants::DW_CFA_same_value => {
+                        let register = Register(input.read_uleb128_u16()?);
+                        self.row.set(register, RegisterRule::SameValue)?;
+                        None
+                    }
+                    constants::DW_CFA_register => {
+                        let register = Register(input.read_uleb128_u16()?);
+                        let rule = RegisterRule::Register(Register(input.read_uleb128_u16()?));
+                        self.row.set(register, rule)?;
+                        None
+                    }
+                    constants::DW_CFA_remember_state => {
+                        let state = self
+                            .states
+                            .get_mut(self.depth)
+                            .ok_or(gimli::Error::StackFull)?;
+                        *state = self.row.clone();
+                        self.depth += 1;
+                        None
+                    }
+                    constants::DW_CFA_restore_state => {
+                        if self.depth == 0 {
+                            return Err(gimli::Error::PopWithEmptyStack);
+                        }
+                        self.depth -= 1;
+                        self.row = self.states[self.depth].clone();
+                        None
+                    }
+                    constants::DW_CFA_def_cfa | constants::DW_CFA_def_cfa_sf => {
+                        let register = Register(input.read_uleb128_u16()?);
+                        let offset = if opcode == constants::DW_CFA_def_cfa.0 {
+                            input.read_uleb128()? as i64
+                        } else {
+                            input.read_sleb128()?.wrapping_mul(self.fde.data_alignment)
+                        };
+                        self.row.cfa = CfaRule::RegisterAndOffset { register, offset };
+                        None
+                    }
+                    constants::DW_CFA_def_cfa_register => {
+                        self.row
+                            .set_cfa_register(Register(input.read_uleb128_u16()?))?;
+                        None
+                    }
+                    constants::DW_CFA_def_cfa_offset => {
+                        self.row.set_cfa_offset(input.read_uleb128()? as i64)?;
+                        None
+                    }
+                    constants::DW_CFA_def_cfa_offset_sf => {
+                        let offset = input.read_sleb128()?.wrapping_mul(self.fde.data_alignment);
+                        self.row.set_cfa_offset(offset)?;
+                        None
+                    }
+                    constants::DW_CFA_def_cfa_expression => {
+                        self.row.cfa = CfaRule::Expression(self.expression(&mut input)?);
+                        None
+                    }
+                    constants::DW_CFA_expression | constants::DW_CFA_val_expression => {
+                        let register = Register(input.read_uleb128_u16()?);
+                        let expr = self.expression(&mut input)?;
+                        let rule = if opcode == constants::DW_CFA_expression.0 {
+                            RegisterRule::Expression(expr)
+                        } else {
+                            RegisterRule::ValExpression(expr)
+                        };
+                        self.row.set(register, rule)?;
+                        None
+                    }
+                    constants::DW_CFA_GNU_args_size => {
+                        self.row.args_size = input.read_uleb128()?;
+                        None
+                    }
+                    #[cfg(target_arch = "aarch64")]
+                    constants::DW_CFA_AARCH64_negate_ra_state => {
+                        let register = gimli::AArch64::RA_SIGN_STATE;
+                        let value = match self.row.register(register) {
+                            RegisterRule::Undefined => 0,
+                            RegisterRule::Constant(value) => value,
+                            _ => return Err(gimli::Error::CfiInstructionInInvalidContext),
+                        };
+                        self.row.set(register, RegisterRule::Constant(value ^ 1))?;
+                        None
+                    }
+                    opcode => return Err(gimli::Error::UnknownCallFrameInstruction(opcode)),
+                },
+            };
+
+            if let Some(delta) = delta {
+                loc = loc.wrapping_add(delta.wrapping_mul(self.fde.code_alignment));
+                if loc > pc {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn restore(&mut self, register: Register) -> Result<()> {
+        let rule = self.initial.register(register);
+        self.row.set(register, rule)
+    }
+}
+
+/// Finds the row of the FDE in `fde_result` covering `pc`, which is relative to the section.
+pub fn find_row(fde_result: &FDESearchResult, pc: usize) -> Result<Row> {
+    let fde = &fde_result.fde;
+    let section = match &fde_result.section {
+        FrameSection::EhFrame(eh_frame) => *gimli::Section::reader(eh_frame),
+        #[cfg(feature = "fde-debug-frame")]
+        FrameSection::DebugFrame { debug_frame, .. } => *gimli::Section::reader(debug_frame),
+    };
+    let mut interpreter = Interpreter {
+        fde,
+        section,
+        row: Row::new(),
+        initial: Row::new(),
+        states: [EMPTY_ROW; MAX_STATES],
+        depth: 0,
+    };
+    // The initial instructions of the CIE apply to the whole FDE.
+    interpreter.run(fde.cie_instructions, 0, u64::MAX)?;
+    interpreter.initial = interpreter.row.clone();
+    interpreter.run(fde.instructions, fde.initial_address, pc as u64)?;
+    Ok(interpreter.row)
+}
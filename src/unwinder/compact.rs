@@ -0,0 +1,118 @@
+use super::arch::Context;
+use crate::arch::Arch;
+use crate::compact_unwind::*;
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+static mut COMPACT_TABLE: Option<(CompactTable<'static>, usize)> = None;
+
+static COMPACT_TABLE_STATE: AtomicU32 = AtomicU32::new(UNINITIALIZED);
+
+const UNINITIALIZED: u32 = 0;
+const INITIALIZING: u32 = 1;
+const INITIALIZED: u32 = 2;
+
+/// The type returned by [`set_compact_unwind_table`] if [`set_compact_unwind_table`] has already
+/// been called.
+#[derive(Debug)]
+pub struct SetCompactUnwindTableError(());
+
+/// Sets the compact unwind table, consulted before DWARF CFI.
+///
+/// `bias` is added to the addresses in the table to get runtime addresses; it is zero for code
+/// that is not relocated at load time.
+///
+/// This function should only be called once during the lifetime of the program.
+///
+/// # Errors
+///
+/// An error is returned if this function has already been called during the lifetime of the
+/// program.
+pub fn set_compact_unwind_table(
+    table: CompactTable<'static>,
+    bias: usize,
+) -> Result<(), SetCompactUnwindTableError> {
+    match COMPACT_TABLE_STATE.compare_exchange(
+        UNINITIALIZED,
+        INITIALIZING,
+        Ordering::SeqCst,
+        Ordering::SeqCst,
+    ) {
+        Ok(UNINITIALIZED) => {
+            unsafe {
+                COMPACT_TABLE = Some((table, bias));
+            }
+            COMPACT_TABLE_STATE.store(INITIALIZED, Ordering::SeqCst);
+            Ok(())
+        }
+        Err(INITIALIZING) => {
+            while COMPACT_TABLE_STATE.load(Ordering::SeqCst) == INITIALIZING {
+                core::hint::spin_loop();
+            }
+            Err(SetCompactUnwindTableError(()))
+        }
+        Err(INITIALIZED) => Err(SetCompactUnwindTableError(())),
+        _ => {
+            unreachable!()
+        }
+    }
+}
+
+fn get_compact_unwind_table() -> Option<(CompactTable<'static>, usize)> {
+    if COMPACT_TABLE_STATE.load(Ordering::SeqCst) == INITIALIZED {
+        unsafe { COMPACT_TABLE }
+    } else {
+        None
+    }
+}
+
+/// A frame described by the compact unwind table.
+#[derive(Debug)]
+pub struct CompactFrame {
+    entry: CompactEntry,
+    pub function_start: usize,
+    // The value of the return address register, if it is not the PC the frame was looked up by.
+    ra: Option<usize>,
+}
+
+/// Looks up `pc` in the compact unwind table.
+///
+/// Returns `None` if there is no table, or it doesn't describe `pc` and DWARF CFI should be used.
+pub fn find_compact_frame(pc: usize, ra: Option<usize>) -> Option<CompactFrame> {
+    let (table, bias) = get_compact_unwind_table()?;
+    let (entry, start) = table.lookup(pc.wrapping_sub(bias) as u64)?;
+    if entry.cfa_reg != CFA_SP && entry.cfa_reg != CFA_FP {
+        return None;
+    }
+    Some(CompactFrame {
+        entry: *entry,
+        function_start: (start as usize).wrapping_add(bias),
+        ra,
+    })
+}
+
+impl CompactFrame {
+    pub fn unwind(&self, ctx: &Context) -> Context {
+        let entry = &self.entry;
+        let load = |addr: usize| unsafe { *(addr as *const usize) };
+
+        let cfa_reg = if entry.cfa_reg == CFA_FP {
+            Arch::FP
+        } else {
+            Arch::SP
+        };
+        let cfa = ctx[cfa_reg].wrapping_add(entry.cfa_off as usize);
+
+        let mut new_ctx = ctx.clone();
+        new_ctx[Arch::SP] = cfa;
+        new_ctx[Arch::RA] = if entry.ra_off == RA_IN_REGISTER {
+            self.ra.unwrap_or(0)
+        } else {
+            load(cfa.wrapping_add(entry.ra_off as usize))
+        };
+        if entry.fp_off != FP_UNCHANGED {
+            new_ctx[Arch::FP] = load(cfa.wrapping_add(entry.fp_off as usize));
+        }
+        new_ctx
+    }
+}
@@ -0,0 +1,137 @@
+use core::cell::UnsafeCell;
+#[cfg(any(
+    feature = "frame-stepper",
+    feature = "stack-switch",
+    feature = "trap-frame"
+))]
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+// Writers serialize on `locked`, and publish each update by switching `generation` over to the
+// other of two copies of the value. Readers never wait for writers: they register with the copy
+// they are about to read in `readers`, and a writer waits for the readers of the copy it is going
+// to overwrite to be done before touching it. This keeps lookups working from a signal handler
+// which interrupted a writer, as long as the handler doesn't update the value itself.
+pub struct DoubleBuffer<T: Copy> {
+    locked: AtomicBool,
+    generation: AtomicUsize,
+    readers: [AtomicUsize; 2],
+    values: [UnsafeCell<T>; 2],
+}
+
+unsafe impl<T: Copy + Send> Sync for DoubleBuffer<T> {}
+
+impl<T: Copy> DoubleBuffer<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            generation: AtomicUsize::new(0),
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            values: [UnsafeCell::new(value), UnsafeCell::new(value)],
+        }
+    }
+
+    pub fn read(&self) -> T {
+        loop {
+            let generation = self.generation.load(Ordering::SeqCst);
+            let readers = &self.readers[generation % 2];
+            readers.fetch_add(1, Ordering::SeqCst);
+            // If the value hasn't been republished since, no writer can have started overwriting
+            // this copy without seeing us first.
+            if self.generation.load(Ordering::SeqCst) == generation {
+                let value = unsafe { *self.values[generation % 2].get() };
+                readers.fetch_sub(1, Ordering::Release);
+                return value;
+            }
+            readers.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn update<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+        let generation = self.generation.load(Ordering::Relaxed);
+        let next = (generation + 1) % 2;
+        // Readers that got hold of this copy before the previous update was published.
+        while self.readers[next].load(Ordering::SeqCst) != 0 {
+            core::hint::spin_loop();
+        }
+        let value = unsafe {
+            let value = &mut *self.values[next].get();
+            *value = *self.values[generation % 2].get();
+            value
+        };
+        let result = f(value);
+        self.generation.store(generation + 1, Ordering::SeqCst);
+        self.locked.store(false, Ordering::Release);
+        result
+    }
+}
+
+/// A fixed-size table of registered values, which can be read from a signal handler.
+#[cfg(any(
+    feature = "frame-stepper",
+    feature = "stack-switch",
+    feature = "trap-frame"
+))]
+pub struct Registry<T: Copy, const N: usize> {
+    entries: DoubleBuffer<[Option<(u32, T)>; N]>,
+    next_id: AtomicU32,
+}
+
+#[cfg(any(
+    feature = "frame-stepper",
+    feature = "stack-switch",
+    feature = "trap-frame"
+))]
+impl<T: Copy + Send, const N: usize> Registry<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            entries: DoubleBuffer::new([None; N]),
+            next_id: AtomicU32::new(0),
+        }
+    }
+
+    /// The values registered, in no particular order.
+    pub fn values(&self) -> impl Iterator<Item = T> {
+        self.entries
+            .read()
+            .into_iter()
+            .flatten()
+            .map(|(_, value)| value)
+    }
+
+    /// Registers `value`, and returns the ID to unregister it with, or `None` if the table is full.
+    pub fn register(&self, value: T) -> Option<u32> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.entries
+            .update(|entries| match entries.iter_mut().find(|x| x.is_none()) {
+                Some(slot) => {
+                    *slot = Some((id, value));
+                    Some(id)
+                }
+                None => None,
+            })
+    }
+
+    /// Unregisters the value registered as `id`. Returns `false` if it is not registered.
+    pub fn unregister(&self, id: u32) -> bool {
+        self.entries.update(|entries| {
+            match entries
+                .iter_mut()
+                .find(|x| x.is_some_and(|(entry_id, _)| entry_id == id))
+            {
+                Some(slot) => {
+                    *slot = None;
+                    true
+                }
+                None => false,
+            }
+        })
+    }
+}
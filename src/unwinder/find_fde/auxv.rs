@@ -0,0 +1,503 @@
+use core::mem;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+const AT_NULL: usize = 0;
+const AT_PHDR: usize = 3;
+const AT_PHNUM: usize = 5;
+const AT_SYSINFO_EHDR: usize = 33;
+
+/// The entries of the auxiliary vector that are needed to find the loaded modules.
+pub struct Auxv {
+    pub phdr: usize,
+    pub phnum: usize,
+    pub sysinfo_ehdr: usize,
+}
+
+static mut AUXV: Auxv = Auxv {
+    phdr: 0,
+    phnum: 0,
+    sysinfo_ehdr: 0,
+};
+
+static AUXV_STATE: AtomicU32 = AtomicU32::new(UNINITIALIZED);
+
+const UNINITIALIZED: u32 = 0;
+const INITIALIZING: u32 = 1;
+const INITIALIZED: u32 = 2;
+const FAILED: u32 = 3;
+
+/// Get the auxiliary vector, reading it from `/proc/self/auxv` on first use.
+///
+/// Returns `None` if it couldn't be read, or if another thread (or an interrupted unwind on this
+/// thread) is reading it right now.
+pub fn get() -> Option<&'static Auxv> {
+    match AUXV_STATE.compare_exchange(
+        UNINITIALIZED,
+        INITIALIZING,
+        Ordering::Acquire,
+        Ordering::Acquire,
+    ) {
+        Ok(_) => match read_auxv() {
+            Some(auxv) => {
+                unsafe { AUXV = auxv };
+                AUXV_STATE.store(INITIALIZED, Ordering::Release);
+            }
+            None => AUXV_STATE.store(FAILED, Ordering::Release),
+        },
+        Err(INITIALIZED) => (),
+        Err(_) => return None,
+    }
+    if AUXV_STATE.load(Ordering::Acquire) == INITIALIZED {
+        Some(unsafe { &*core::ptr::addr_of!(AUXV) })
+    } else {
+        None
+    }
+}
+
+fn read_auxv() -> Option<Auxv> {
+    let fd = unsafe { sys::open(c"/proc/self/auxv".as_ptr() as _) };
+    if fd < 0 {
+        return None;
+    }
+
+    let mut auxv = Auxv {
+        phdr: 0,
+        phnum: 0,
+        sysinfo_ehdr: 0,
+    };
+    let mut buf = [0usize; 64];
+    let mut filled = 0;
+    let done = 'read: loop {
+        let bytes = unsafe {
+            sys::read(
+                fd,
+                (buf.as_mut_ptr() as *mut u8).add(filled),
+                mem::size_of_val(&buf) - filled,
+            )
+        };
+        if bytes <= 0 {
+            break false;
+        }
+        filled += bytes as usize;
+
+        // Consume the complete entries, and keep a partially read one for the next round.
+        let entries = filled / (2 * mem::size_of::<usize>());
+        for entry in buf[..entries * 2].chunks_exact(2) {
+            match entry[0] {
+                AT_NULL => break 'read true,
+                AT_PHDR => auxv.phdr = entry[1],
+                AT_PHNUM => auxv.phnum = entry[1],
+                AT_SYSINFO_EHDR => auxv.sysinfo_ehdr = entry[1],
+                _ => (),
+            }
+        }
+        let consumed = entries * 2 * mem::size_of::<usize>();
+        unsafe {
+            core::ptr::copy(
+                (buf.as_ptr() as *const u8).add(consumed),
+                buf.as_mut_ptr() as *mut u8,
+                filled - consumed,
+            )
+        };
+        filled -= consumed;
+    };
+    unsafe { sys::close(fd) };
+
+    if done && auxv.phdr != 0 {
+        Some(auxv)
+    } else {
+        None
+    }
+}
+
+/// Raw system calls, so no libc is needed.
+#[cfg(target_arch = "x86_64")]
+mod sys {
+    use core::arch::asm;
+
+    // x32 has system calls of its own, numbered from `__X32_SYSCALL_BIT`.
+    #[cfg(target_pointer_width = "64")]
+    const SYSCALL_BASE: i64 = 0;
+    #[cfg(target_pointer_width = "32")]
+    const SYSCALL_BASE: i64 = 0x40000000;
+
+    // Arguments are passed as 64-bit values, so they are extended on x32.
+    pub unsafe fn open(path: *const u8) -> isize {
+        const SYS_OPENAT: i64 = SYSCALL_BASE + 257;
+        const AT_FDCWD: i64 = -100;
+        const O_CLOEXEC: u64 = 0o2000000;
+        let ret: i64;
+        unsafe {
+            asm!(
+                "syscall",
+                inlateout("rax") SYS_OPENAT => ret,
+                in("rdi") AT_FDCWD,
+                in("rsi") path as usize as u64,
+                in("rdx") O_CLOEXEC,
+                in("r10") 0u64,
+                lateout("rcx") _,
+                lateout("r11") _,
+                options(nostack),
+            )
+        };
+        ret as isize
+    }
+
+    pub unsafe fn read(fd: isize, buf: *mut u8, len: usize) -> isize {
+        const SYS_READ: i64 = SYSCALL_BASE;
+        let ret: i64;
+        unsafe {
+            asm!(
+                "syscall",
+                inlateout("rax") SYS_READ => ret,
+                in("rdi") fd as i64,
+                in("rsi") buf as usize as u64,
+                in("rdx") len as u64,
+                lateout("rcx") _,
+                lateout("r11") _,
+                options(nostack),
+            )
+        };
+        ret as isize
+    }
+
+    pub unsafe fn close(fd: isize) {
+        const SYS_CLOSE: i64 = SYSCALL_BASE + 3;
+        unsafe {
+            asm!(
+                "syscall",
+                inlateout("rax") SYS_CLOSE => _,
+                in("rdi") fd as i64,
+                lateout("rcx") _,
+                lateout("r11") _,
+                options(nostack),
+            )
+        };
+    }
+}
+
+#[cfg(target_arch = "x86")]
+mod sys {
+    use core::arch::asm;
+
+    pub unsafe fn open(path: *const u8) -> isize {
+        const SYS_OPEN: isize = 5;
+        const O_CLOEXEC: usize = 0o2000000;
+        let ret;
+        unsafe {
+            asm!(
+                "int 0x80",
+                inlateout("eax") SYS_OPEN => ret,
+                in("ebx") path,
+                in("ecx") O_CLOEXEC,
+                in("edx") 0,
+                options(nostack),
+            )
+        };
+        ret
+    }
+
+    pub unsafe fn read(fd: isize, buf: *mut u8, len: usize) -> isize {
+        const SYS_READ: isize = 3;
+        let ret;
+        unsafe {
+            asm!(
+                "int 0x80",
+                inlateout("eax") SYS_READ => ret,
+                in("ebx") fd,
+                in("ecx") buf,
+                in("edx") len,
+                options(nostack),
+            )
+        };
+        ret
+    }
+
+    pub unsafe fn close(fd: isize) {
+        const SYS_CLOSE: isize = 6;
+        unsafe {
+            asm!(
+                "int 0x80",
+                inlateout("eax") SYS_CLOSE => _,
+                in("ebx") fd,
+                options(nostack),
+            )
+        };
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod sys {
+    use core::arch::asm;
+
+    pub unsafe fn open(path: *const u8) -> isize {
+        const SYS_OPENAT: usize = 56;
+        const AT_FDCWD: isize = -100;
+        const O_CLOEXEC: usize = 0o2000000;
+        let ret;
+        unsafe {
+            asm!(
+                "svc 0",
+                in("x8") SYS_OPENAT,
+                inlateout("x0") AT_FDCWD => ret,
+                in("x1") path,
+                in("x2") O_CLOEXEC,
+                in("x3") 0,
+                options(nostack),
+            )
+        };
+        ret
+    }
+
+    pub unsafe fn read(fd: isize, buf: *mut u8, len: usize) -> isize {
+        const SYS_READ: usize = 63;
+        let ret;
+        unsafe {
+            asm!(
+                "svc 0",
+                in("x8") SYS_READ,
+                inlateout("x0") fd => ret,
+                in("x1") buf,
+                in("x2") len,
+                options(nostack),
+            )
+        };
+        ret
+    }
+
+    pub unsafe fn close(fd: isize) {
+        const SYS_CLOSE: usize = 57;
+        unsafe {
+            asm!(
+                "svc 0",
+                in("x8") SYS_CLOSE,
+                inlateout("x0") fd => _,
+                options(nostack),
+            )
+        };
+    }
+}
+
+#[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+mod sys {
+    use core::arch::asm;
+
+    pub unsafe fn open(path: *const u8) -> isize {
+        const SYS_OPENAT: usize = 56;
+        const AT_FDCWD: isize = -100;
+        const O_CLOEXEC: usize = 0o2000000;
+        let ret;
+        unsafe {
+            asm!(
+                "ecall",
+                in("a7") SYS_OPENAT,
+                inlateout("a0") AT_FDCWD => ret,
+                in("a1") path,
+                in("a2") O_CLOEXEC,
+                in("a3") 0,
+                options(nostack),
+            )
+        };
+        ret
+    }
+
+    pub unsafe fn read(fd: isize, buf: *mut u8, len: usize) -> isize {
+        const SYS_READ: usize = 63;
+        let ret;
+        unsafe {
+            asm!(
+                "ecall",
+                in("a7") SYS_READ,
+                inlateout("a0") fd => ret,
+                in("a1") buf,
+                in("a2") len,
+                options(nostack),
+            )
+        };
+        ret
+    }
+
+    pub unsafe fn close(fd: isize) {
+        const SYS_CLOSE: usize = 57;
+        unsafe {
+            asm!(
+                "ecall",
+                in("a7") SYS_CLOSE,
+                inlateout("a0") fd => _,
+                options(nostack),
+            )
+        };
+    }
+}
+
+#[cfg(target_arch = "loongarch64")]
+mod sys {
+    use core::arch::asm;
+
+    pub unsafe fn open(path: *const u8) -> isize {
+        const SYS_OPENAT: usize = 56;
+        const AT_FDCWD: isize = -100;
+        const O_CLOEXEC: usize = 0o2000000;
+        let ret;
+        unsafe {
+            asm!(
+                "syscall 0",
+                in("$a7") SYS_OPENAT,
+                inlateout("$a0") AT_FDCWD => ret,
+                in("$a1") path,
+                in("$a2") O_CLOEXEC,
+                in("$a3") 0,
+                options(nostack),
+            )
+        };
+        ret
+    }
+
+    pub unsafe fn read(fd: isize, buf: *mut u8, len: usize) -> isize {
+        const SYS_READ: usize = 63;
+        let ret;
+        unsafe {
+            asm!(
+                "syscall 0",
+                in("$a7") SYS_READ,
+                inlateout("$a0") fd => ret,
+                in("$a1") buf,
+                in("$a2") len,
+                options(nostack),
+            )
+        };
+        ret
+    }
+
+    pub unsafe fn close(fd: isize) {
+        const SYS_CLOSE: usize = 57;
+        unsafe {
+            asm!(
+                "syscall 0",
+                in("$a7") SYS_CLOSE,
+                inlateout("$a0") fd => _,
+                options(nostack),
+            )
+        };
+    }
+}
+
+#[cfg(target_arch = "powerpc64")]
+mod sys {
+    use core::arch::asm;
+
+    // Errors are flagged in the summary overflow bit of cr0 with a positive errno, so they are
+    // negated to match the other architectures.
+    macro_rules! syscall {
+        ($nr:expr, $a0:expr => $ret:tt, $a1:expr, $a2:expr, $a3:expr) => {
+            asm!(
+                "
+                sc
+                bns+ 2f
+                neg 3, 3
+                2:
+                ",
+                inlateout("r0") $nr => _,
+                inlateout("r3") $a0 => $ret,
+                inlateout("r4") $a1 => _,
+                inlateout("r5") $a2 => _,
+                inlateout("r6") $a3 => _,
+                lateout("r7") _,
+                lateout("r8") _,
+                lateout("r9") _,
+                lateout("r10") _,
+                lateout("r11") _,
+                lateout("r12") _,
+                lateout("cr0") _,
+                lateout("ctr") _,
+                lateout("xer") _,
+                options(nostack),
+            )
+        };
+    }
+
+    pub unsafe fn open(path: *const u8) -> isize {
+        const SYS_OPENAT: usize = 286;
+        const AT_FDCWD: isize = -100;
+        const O_CLOEXEC: usize = 0o2000000;
+        let ret;
+        unsafe { syscall!(SYS_OPENAT, AT_FDCWD => ret, path, O_CLOEXEC, 0) };
+        ret
+    }
+
+    pub unsafe fn read(fd: isize, buf: *mut u8, len: usize) -> isize {
+        const SYS_READ: usize = 3;
+        let ret;
+        unsafe { syscall!(SYS_READ, fd => ret, buf, len, 0) };
+        ret
+    }
+
+    pub unsafe fn close(fd: isize) {
+        const SYS_CLOSE: usize = 6;
+        unsafe { syscall!(SYS_CLOSE, fd => _, 0, 0, 0) };
+    }
+}
+
+#[cfg(target_arch = "s390x")]
+mod sys {
+    use core::arch::asm;
+
+    pub unsafe fn open(path: *const u8) -> isize {
+        const SYS_OPENAT: usize = 288;
+        const AT_FDCWD: isize = -100;
+        const O_CLOEXEC: usize = 0o2000000;
+        let ret;
+        unsafe {
+            asm!(
+                "svc 0",
+                in("r1") SYS_OPENAT,
+                inlateout("r2") AT_FDCWD => ret,
+                in("r3") path,
+                in("r4") O_CLOEXEC,
+                in("r5") 0,
+                options(nostack),
+            )
+        };
+        ret
+    }
+
+    pub unsafe fn read(fd: isize, buf: *mut u8, len: usize) -> isize {
+        const SYS_READ: usize = 3;
+        let ret;
+        unsafe {
+            asm!(
+                "svc 0",
+                in("r1") SYS_READ,
+                inlateout("r2") fd => ret,
+                in("r3") buf,
+                in("r4") len,
+                options(nostack),
+            )
+        };
+        ret
+    }
+
+    pub unsafe fn close(fd: isize) {
+        const SYS_CLOSE: usize = 6;
+        unsafe {
+            asm!(
+                "svc 0",
+                in("r1") SYS_CLOSE,
+                inlateout("r2") fd => _,
+                options(nostack),
+            )
+        };
+    }
+}
+
+#[cfg(not(any(
+    target_arch = "x86_64",
+    target_arch = "x86",
+    target_arch = "aarch64",
+    target_arch = "riscv64",
+    target_arch = "riscv32",
+    target_arch = "loongarch64",
+    target_arch = "powerpc64",
+    target_arch = "s390x"
+)))]
+compile_error!("\"fde-phdr-raw\" is not supported on this architecture.");
@@ -0,0 +1,153 @@
+//! CIEs with the `B` augmentation, which marks return addresses signed with the B key.
+//!
+//! gimli rejects the augmentation. It carries no data, and return addresses signed with either key
+//! are stripped the same way, so such CIEs are parsed from a copy without the `B`. The parsed CIE
+//! refers to the copy, so copies are kept for the rest of the program, and shared between CIEs
+//! with the same contents.
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicU8, Ordering};
+use gimli::{
+    BaseAddresses, CallFrameInstruction, CommonInformationEntry, EhFrame, EhFrameOffset,
+    NativeEndian, Section, UnwindSection,
+};
+
+use crate::util::*;
+
+/// Maximum number of distinct CIEs with the `B` augmentation.
+const SLOTS: usize = 32;
+/// Maximum size of such a CIE, including its length field.
+const SLOT_SIZE: usize = 64;
+
+const FREE: u8 = 0;
+const WRITING: u8 = 1;
+const READY: u8 = 2;
+
+struct Slot {
+    state: AtomicU8,
+    // Only written while `state` is `WRITING`, and never changed once it is `READY`.
+    len: UnsafeCell<usize>,
+    data: UnsafeCell<[u8; SLOT_SIZE]>,
+}
+
+unsafe impl Sync for Slot {}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_SLOT: Slot = Slot {
+    state: AtomicU8::new(FREE),
+    len: UnsafeCell::new(0),
+    data: UnsafeCell::new([0; SLOT_SIZE]),
+};
+
+static COPIES: [Slot; SLOTS] = [EMPTY_SLOT; SLOTS];
+
+// Find a copy of `cie`, or make one. Returns `None` if all slots are used.
+fn intern(cie: &[u8]) -> Option<&'static [u8]> {
+    for slot in COPIES.iter() {
+        match slot.state.load(Ordering::Acquire) {
+            READY => {
+                let copy = unsafe { &(&*slot.data.get())[..*slot.len.get()] };
+                if copy == cie {
+                    return Some(copy);
+                }
+            }
+            FREE => {
+                if slot
+                    .state
+                    .compare_exchange(FREE, WRITING, Ordering::Acquire, Ordering::Relaxed)
+                    .is_err()
+                {
+                    continue;
+                }
+                let copy = unsafe {
+                    let data = &mut *slot.data.get();
+                    data[..cie.len()].copy_from_slice(cie);
+                    *slot.len.get() = cie.len();
+                    &data[..cie.len()]
+                };
+                slot.state.store(READY, Ordering::Release);
+                return Some(copy);
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Parses the CIE at `offset` in `eh_frame`, which gimli failed to parse, if the `B` augmentation
+/// is what it didn't understand.
+pub fn cie_from_offset(
+    eh_frame: &EhFrame<StaticSlice>,
+    bases: &BaseAddresses,
+    offset: EhFrameOffset,
+) -> gimli::Result<CommonInformationEntry<StaticSlice>> {
+    const UNKNOWN: gimli::Error = gimli::Error::UnknownAugmentation;
+
+    let cie = eh_frame.reader().slice().get(offset.0..).ok_or(UNKNOWN)?;
+    let len = u32::from_ne_bytes(cie.get(..4).ok_or(UNKNOWN)?.try_into().unwrap()) as usize;
+    // The copy is a byte shorter. A CIE with a 64-bit length would be too large anyway.
+    if len > SLOT_SIZE - 3 {
+        return Err(UNKNOWN);
+    }
+    let cie = cie.get(..len + 4).ok_or(UNKNOWN)?;
+
+    // The augmentation string follows the length, the CIE ID and the version.
+    let augmentation = cie.get(9..).ok_or(UNKNOWN)?;
+    let augmentation = &augmentation[..augmentation.iter().position(|&c| c == 0).ok_or(UNKNOWN)?];
+    let b = 9 + augmentation
+        .iter()
+        .position(|&c| c == b'B')
+        .ok_or(UNKNOWN)?;
+
+    let mut copy = [0; SLOT_SIZE];
+    copy[..4].copy_from_slice(&(len as u32 - 1).to_ne_bytes());
+    copy[4..b].copy_from_slice(&cie[4..b]);
+    copy[b..len + 3].copy_from_slice(&cie[b + 1..]);
+    let copy = intern(&copy[..len + 3]).ok_or(UNKNOWN)?;
+
+    // Everything after the augmentation string is one byte earlier in the copy. Move the base of
+    // the section by as much, so PC-relative pointers resolve to the same addresses.
+    let bases = bases.clone().set_eh_frame((cie.as_ptr() as usize + 1) as _);
+    let copy = EhFrame::new(copy, NativeEndian);
+    let cie = copy.cie_from_offset(&bases, EhFrameOffset(0))?;
+
+    // Expressions are located by their offset in the section, which the copy doesn't preserve.
+    let mut instructions = cie.instructions(&copy, &bases);
+    while let Some(instruction) = instructions.next()? {
+        if matches!(
+            instruction,
+            CallFrameInstruction::DefCfaExpression { .. }
+                | CallFrameInstruction::Expression { .. }
+                | CallFrameInstruction::ValExpression { .. }
+        ) {
+            return Err(UNKNOWN);
+        }
+    }
+    Ok(cie)
+}
+
+/// Iterates over the offsets of the FDEs in `eh_frame`, skipping CIEs without parsing them.
+pub fn fde_offsets(eh_frame: &EhFrame<StaticSlice>) -> impl Iterator<Item = EhFrameOffset> {
+    let section: &'static [u8] = eh_frame.reader().slice();
+    let mut offset = 0;
+    core::iter::from_fn(move || loop {
+        let entry = section.get(offset..)?;
+        let (len, header, id_len) = match u32::from_ne_bytes(entry.get(..4)?.try_into().unwrap()) {
+            // A zero length terminates the section.
+            0 => return None,
+            0xffff_ffff => (
+                u64::from_ne_bytes(entry.get(4..12)?.try_into().unwrap()) as usize,
+                12,
+                8,
+            ),
+            len => (len as usize, 4, 4),
+        };
+        // The CIE ID is as wide as the length field, and zero.
+        let id = entry.get(header..header + id_len)?;
+        let this = offset;
+        offset = offset.checked_add(header)?.checked_add(len)?;
+        if id.iter().any(|&b| b != 0) {
+            return Some(EhFrameOffset(this));
+        }
+    })
+}
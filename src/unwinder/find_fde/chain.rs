@@ -0,0 +1,194 @@
+use super::{FDEFinder, FDESearchResult};
+use crate::unwinder::double_buffer::DoubleBuffer;
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// Maximum number of finders in the chain, including the built-in ones.
+pub const MAX_FINDERS: usize = 16;
+
+/// Identifies a finder in the FDE finder chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FinderId(u32);
+
+impl FinderId {
+    /// The finder that consults [`set_custom_eh_frame_finder`](super::custom_eh_frame_finder::set_custom_eh_frame_finder).
+    pub const CUSTOM: Self = Self(0);
+    /// Frames registered with `__register_frame` and friends.
+    pub const REGISTRY: Self = Self(1);
+    /// The `__GNU_EH_FRAME_HDR` linker symbol.
+    pub const GNU_EH_FRAME_HDR: Self = Self(2);
+    /// Modules found via program headers.
+    pub const PHDR: Self = Self(3);
+    /// `.debug_frame` sections read from module files.
+    pub const DEBUG_FRAME: Self = Self(4);
+    /// The `__eh_frame` linker symbol.
+    pub const STATIC: Self = Self(5);
+    /// Regions listed in the `unwinding_regions` linker section.
+    pub const STATIC_REGIONS: Self = Self(6);
+
+    const FIRST_DYNAMIC: u32 = 7;
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    id: FinderId,
+    priority: i32,
+    finder: &'static (dyn FDEFinder + Sync),
+}
+
+type Chain = [Option<Entry>; MAX_FINDERS];
+
+// Built-in finders, in the order they have been consulted historically.
+#[allow(unused_mut)]
+const fn default_chain() -> Chain {
+    let mut chain: Chain = [None; MAX_FINDERS];
+    let mut i = 0;
+    macro_rules! builtin {
+        ($feature: literal, $id: ident, $priority: literal, $finder: expr) => {
+            #[cfg(feature = $feature)]
+            {
+                chain[i] = Some(Entry {
+                    id: FinderId::$id,
+                    priority: $priority,
+                    finder: $finder,
+                });
+                i += 1;
+            }
+        };
+    }
+    builtin!("fde-custom", CUSTOM, 600, super::custom::get_finder());
+    builtin!("fde-registry", REGISTRY, 500, super::registry::get_finder());
+    builtin!(
+        "fde-gnu-eh-frame-hdr",
+        GNU_EH_FRAME_HDR,
+        400,
+        super::gnu_eh_frame_hdr::get_finder()
+    );
+    builtin!("fde-phdr", PHDR, 300, super::phdr::get_finder());
+    builtin!(
+        "fde-debug-frame",
+        DEBUG_FRAME,
+        200,
+        super::debug_frame::get_finder()
+    );
+    builtin!("fde-static", STATIC, 100, super::fixed::get_finder());
+    builtin!(
+        "fde-static-regions",
+        STATIC_REGIONS,
+        100,
+        super::regions::get_finder()
+    );
+    let _ = i;
+    chain
+}
+
+// Finders run on a copy of the chain, so they can themselves unwind or modify the chain.
+static CHAIN: DoubleBuffer<Chain> = DoubleBuffer::new(default_chain());
+
+static NEXT_ID: AtomicU32 = AtomicU32::new(FinderId::FIRST_DYNAMIC);
+
+pub fn find_fde(pc: usize) -> Option<FDESearchResult> {
+    let chain = CHAIN.read();
+    chain
+        .iter()
+        .map_while(|x| x.as_ref())
+        .find_map(|entry| entry.finder.find_fde(pc))
+}
+
+// Keep the chain sorted by descending priority; finders with equal priority stay in the order
+// they were inserted.
+fn insert(chain: &mut Chain, entry: Entry) -> bool {
+    let len = chain.iter().take_while(|x| x.is_some()).count();
+    if len == MAX_FINDERS {
+        return false;
+    }
+    let pos = chain[..len]
+        .iter()
+        .position(|x| x.unwrap().priority < entry.priority)
+        .unwrap_or(len);
+    chain[pos..=len].rotate_right(1);
+    chain[pos] = Some(entry);
+    true
+}
+
+fn remove(chain: &mut Chain, id: FinderId) -> Option<Entry> {
+    let pos = chain
+        .iter()
+        .map_while(|x| x.as_ref())
+        .position(|x| x.id == id)?;
+    let entry = chain[pos].take();
+    chain[pos..].rotate_left(1);
+    entry
+}
+
+/// The type returned by [`add_finder`] if the finder chain is full.
+#[derive(Debug)]
+pub struct AddFinderError(());
+
+/// Adds a finder to the chain.
+///
+/// Finders are consulted in order of descending priority, and the first one that returns a result
+/// wins. Finders with the same priority are consulted in the order they are added. The built-in
+/// finders have the following priorities:
+///
+/// | Finder                         | Priority |
+/// |--------------------------------|----------|
+/// | [`FinderId::CUSTOM`]           | 600      |
+/// | [`FinderId::REGISTRY`]         | 500      |
+/// | [`FinderId::GNU_EH_FRAME_HDR`] | 400      |
+/// | [`FinderId::PHDR`]             | 300      |
+/// | [`FinderId::DEBUG_FRAME`]      | 200      |
+/// | [`FinderId::STATIC`]           | 100      |
+/// | [`FinderId::STATIC_REGIONS`]   | 100      |
+///
+/// # Errors
+///
+/// An error is returned if the chain already holds [`MAX_FINDERS`] finders.
+pub fn add_finder(
+    finder: &'static (dyn FDEFinder + Sync),
+    priority: i32,
+) -> Result<FinderId, AddFinderError> {
+    let id = FinderId(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    let entry = Entry {
+        id,
+        priority,
+        finder,
+    };
+    if CHAIN.update(|chain| insert(chain, entry)) {
+        Ok(id)
+    } else {
+        Err(AddFinderError(()))
+    }
+}
+
+/// Removes a finder from the chain. Built-in finders can be removed as well.
+///
+/// Returns `false` if the finder is not in the chain.
+///
+/// An unwind that is already in progress may still consult the finder.
+pub fn remove_finder(id: FinderId) -> bool {
+    CHAIN.update(|chain| remove(chain, id)).is_some()
+}
+
+/// Changes the priority of a finder in the chain.
+///
+/// Returns `false` if the finder is not in the chain.
+pub fn set_finder_priority(id: FinderId, priority: i32) -> bool {
+    CHAIN.update(|chain| match remove(chain, id) {
+        Some(mut entry) => {
+            entry.priority = priority;
+            insert(chain, entry)
+        }
+        None => false,
+    })
+}
+
+/// Returns the priority of a finder, or `None` if it is not in the chain.
+pub fn finder_priority(id: FinderId) -> Option<i32> {
+    CHAIN
+        .read()
+        .iter()
+        .map_while(|x| x.as_ref())
+        .find(|x| x.id == id)
+        .map(|x| x.priority)
+}
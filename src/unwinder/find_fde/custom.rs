@@ -1,18 +1,23 @@
-use super::{FDEFinder, FDESearchResult};
-use crate::util::{deref_pointer, get_unlimited_slice};
+#[cfg(target_has_atomic = "ptr")]
+use super::table;
+use super::{fde_for_address, fde_from_offset, hdr_table_fde, FDEFinder, FDESearchResult};
+use crate::util::{self, deref_pointer, get_slice, EhFrameEnd};
 
 use core::sync::atomic::{AtomicU32, Ordering};
-use gimli::{BaseAddresses, EhFrame, EhFrameHdr, NativeEndian, UnwindSection};
+use gimli::{BaseAddresses, EhFrame, EhFrameHdr, EhFrameOffset, NativeEndian};
 
 pub(crate) struct CustomFinder(());
 
-pub(crate) fn get_finder() -> &'static CustomFinder {
+pub(crate) const fn get_finder() -> &'static CustomFinder {
     &CustomFinder(())
 }
 
 impl FDEFinder for CustomFinder {
     fn find_fde(&self, pc: usize) -> Option<FDESearchResult> {
-        get_custom_eh_frame_finder().and_then(|eh_frame_finder| find_fde(eh_frame_finder, pc))
+        get_custom_eh_frame_finder().and_then(|eh_frame_finder| {
+            let info = eh_frame_finder.find(pc)?;
+            unsafe { FDESearchResult::from_frame_info(pc, info) }
+        })
     }
 }
 
@@ -27,8 +32,18 @@ pub struct FrameInfo {
 }
 
 pub enum FrameInfoKind {
+    /// The address of an `.eh_frame_hdr` section. Unless the header has a search table, the
+    /// `.eh_frame` section it refers to must end with a zero terminator.
     EhFrameHdr(usize),
+    /// The address of an `.eh_frame` section, which must end with a zero terminator.
     EhFrame(usize),
+    /// An `.eh_frame_hdr` section, along with the length of the `.eh_frame` section it refers to.
+    EhFrameHdrSlice {
+        eh_frame_hdr: &'static [u8],
+        eh_frame_len: usize,
+    },
+    /// An `.eh_frame` section.
+    EhFrameSlice(&'static [u8]),
 }
 
 static mut CUSTOM_EH_FRAME_FINDER: Option<&(dyn EhFrameFinder + Sync)> = None;
@@ -89,83 +104,200 @@ fn get_custom_eh_frame_finder() -> Option<&'static dyn EhFrameFinder> {
     }
 }
 
-fn find_fde<T: EhFrameFinder + ?Sized>(eh_frame_finder: &T, pc: usize) -> Option<FDESearchResult> {
-    let info = eh_frame_finder.find(pc)?;
-    let text_base = info.text_base;
-    match info.kind {
-        FrameInfoKind::EhFrameHdr(eh_frame_hdr) => {
-            find_fde_with_eh_frame_hdr(pc, text_base, eh_frame_hdr)
-        }
-        FrameInfoKind::EhFrame(eh_frame) => find_fde_with_eh_frame(pc, text_base, eh_frame),
-    }
+/// Identifies an `.eh_frame` section indexed with [`index_eh_frame`].
+#[cfg(target_has_atomic = "ptr")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EhFrameIndexId {
+    slot: usize,
+    generation: usize,
 }
 
-fn find_fde_with_eh_frame_hdr(
-    pc: usize,
+/// The type returned by [`index_eh_frame`] if the section can't be indexed.
+#[cfg(target_has_atomic = "ptr")]
+#[derive(Debug)]
+pub struct IndexEhFrameError(());
+
+/// Builds a binary search table for an `.eh_frame` section returned by the custom finder, so that
+/// lookups in it don't search it linearly.
+///
+/// Sections returned by the custom finder may be unloaded and their address reused, so they are
+/// only indexed while registered with this function. The table is used until it is removed with
+/// [`remove_eh_frame_index`].
+///
+/// # Errors
+///
+/// An error is returned if the section is malformed or already indexed, or there is no room left
+/// for the table.
+///
+/// # Safety
+///
+/// `eh_frame` must be a valid `.eh_frame` section which stays loaded until the table is removed.
+#[cfg(target_has_atomic = "ptr")]
+pub unsafe fn index_eh_frame(
+    eh_frame: &'static [u8],
     text_base: Option<usize>,
-    eh_frame_hdr: usize,
-) -> Option<FDESearchResult> {
-    unsafe {
-        let mut bases = BaseAddresses::default().set_eh_frame_hdr(eh_frame_hdr as _);
-        if let Some(text_base) = text_base {
-            bases = bases.set_text(text_base as _);
-        }
-        let eh_frame_hdr = EhFrameHdr::new(
-            get_unlimited_slice(eh_frame_hdr as usize as _),
-            NativeEndian,
-        )
-        .parse(&bases, core::mem::size_of::<usize>() as _)
-        .ok()?;
-        let eh_frame = deref_pointer(eh_frame_hdr.eh_frame_ptr());
-        let bases = bases.set_eh_frame(eh_frame as _);
-        let eh_frame = EhFrame::new(get_unlimited_slice(eh_frame as _), NativeEndian);
+) -> Result<EhFrameIndexId, IndexEhFrameError> {
+    let bases = bases(eh_frame.as_ptr() as usize, text_base);
+    match unsafe { table::insert(eh_frame, &bases) } {
+        Some((slot, generation)) => Ok(EhFrameIndexId { slot, generation }),
+        None => Err(IndexEhFrameError(())),
+    }
+}
 
-        // Use binary search table for address if available.
-        if let Some(table) = eh_frame_hdr.table() {
-            if let Ok(fde) =
-                table.fde_for_address(&eh_frame, &bases, pc as _, EhFrame::cie_from_offset)
-            {
-                return Some(FDESearchResult {
-                    fde,
-                    bases,
-                    eh_frame,
-                });
+/// Removes a table built by [`index_eh_frame`], waiting for lookups using it to finish.
+///
+/// Returns `false` if the table has already been removed.
+#[cfg(target_has_atomic = "ptr")]
+pub fn remove_eh_frame_index(id: EhFrameIndexId) -> bool {
+    table::remove(id.slot, id.generation)
+}
+
+/// The location of a single FDE within an `.eh_frame` section.
+pub struct FdeLocation {
+    pub text_base: Option<usize>,
+    pub eh_frame: &'static [u8],
+    /// The address of the FDE.
+    pub fde: usize,
+}
+
+impl FDESearchResult {
+    /// Finds the FDE covering `pc` in the frame information described by `info`.
+    ///
+    /// # Safety
+    ///
+    /// `info` must describe a valid `.eh_frame_hdr` or `.eh_frame` section which stays loaded
+    /// while the result is in use.
+    pub unsafe fn from_frame_info(pc: usize, info: FrameInfo) -> Option<Self> {
+        let text_base = info.text_base;
+        match info.kind {
+            FrameInfoKind::EhFrameHdr(eh_frame_hdr) => {
+                let eh_frame_hdr =
+                    unsafe { get_slice(eh_frame_hdr, util::eh_frame_hdr_len(eh_frame_hdr)?) };
+                find_fde_with_eh_frame_hdr(pc, text_base, eh_frame_hdr, None)
+            }
+            FrameInfoKind::EhFrame(eh_frame) => {
+                let bases = bases(eh_frame, text_base);
+                unsafe {
+                    find_fde_with_eh_frame(pc, bases, eh_frame, EhFrameEnd::Terminator(usize::MAX))
+                }
+            }
+            FrameInfoKind::EhFrameHdrSlice {
+                eh_frame_hdr,
+                eh_frame_len,
+            } => find_fde_with_eh_frame_hdr(pc, text_base, eh_frame_hdr, Some(eh_frame_len)),
+            FrameInfoKind::EhFrameSlice(eh_frame) => {
+                let eh_frame_addr = eh_frame.as_ptr() as usize;
+                let bases = bases(eh_frame_addr, text_base);
+                let end = EhFrameEnd::At(eh_frame_addr + eh_frame.len());
+                unsafe { find_fde_with_eh_frame(pc, bases, eh_frame_addr, end) }
             }
         }
+    }
 
-        // Otherwise do the linear search.
-        if let Ok(fde) = eh_frame.fde_for_address(&bases, pc as _, EhFrame::cie_from_offset) {
-            return Some(FDESearchResult {
-                fde,
-                bases,
-                eh_frame,
-            });
+    /// Uses the FDE at `location`, returning `None` if it doesn't cover `pc`.
+    ///
+    /// # Safety
+    ///
+    /// `location.eh_frame` must be a valid `.eh_frame` section which stays loaded while the result
+    /// is in use.
+    pub unsafe fn from_fde(pc: usize, location: FdeLocation) -> Option<Self> {
+        let eh_frame_addr = location.eh_frame.as_ptr() as usize;
+        let mut bases = BaseAddresses::default().set_eh_frame(eh_frame_addr as _);
+        if let Some(text_base) = location.text_base {
+            bases = bases.set_text(text_base as _);
         }
+        let eh_frame = EhFrame::new(location.eh_frame, NativeEndian);
+        let offset = EhFrameOffset(location.fde.checked_sub(eh_frame_addr)?);
+        let fde = fde_from_offset(&eh_frame, &bases, offset).ok()?;
+        if !fde.contains(pc as _) {
+            return None;
+        }
+        Some(FDESearchResult {
+            fde,
+            bases,
+            section: eh_frame.into(),
+            image_end: None,
+        })
+    }
+}
 
-        None
+fn bases(eh_frame_addr: usize, text_base: Option<usize>) -> BaseAddresses {
+    let bases = BaseAddresses::default().set_eh_frame(eh_frame_addr as _);
+    match text_base {
+        Some(text_base) => bases.set_text(text_base as _),
+        None => bases,
     }
 }
 
-fn find_fde_with_eh_frame(
+fn find_fde_with_eh_frame_hdr(
     pc: usize,
     text_base: Option<usize>,
-    eh_frame: usize,
+    eh_frame_hdr: &'static [u8],
+    eh_frame_len: Option<usize>,
 ) -> Option<FDESearchResult> {
     unsafe {
-        let mut bases = BaseAddresses::default().set_eh_frame(eh_frame as _);
+        let mut bases = BaseAddresses::default().set_eh_frame_hdr(eh_frame_hdr.as_ptr() as _);
         if let Some(text_base) = text_base {
             bases = bases.set_text(text_base as _);
         }
-        let eh_frame = EhFrame::new(get_unlimited_slice(eh_frame as _), NativeEndian);
+        let eh_frame_hdr = EhFrameHdr::new(eh_frame_hdr, NativeEndian)
+            .parse(&bases, core::mem::size_of::<usize>() as _)
+            .ok()?;
+        let eh_frame_addr = deref_pointer(eh_frame_hdr.eh_frame_ptr());
+        let bases = bases.set_eh_frame(eh_frame_addr as _);
+        let end = match eh_frame_len {
+            Some(eh_frame_len) => EhFrameEnd::At(eh_frame_addr.checked_add(eh_frame_len)?),
+            None => EhFrameEnd::Terminator(usize::MAX),
+        };
 
-        if let Ok(fde) = eh_frame.fde_for_address(&bases, pc as _, EhFrame::cie_from_offset) {
+        // Use binary search table for address if available.
+        if let Some(table) = eh_frame_hdr.table() {
+            let (eh_frame, fde) = hdr_table_fde(&table, eh_frame_addr, end.bound(), &bases, pc)?;
             return Some(FDESearchResult {
                 fde,
                 bases,
-                eh_frame,
+                section: eh_frame.into(),
+                image_end: None,
             });
         }
 
-        None
+        find_fde_with_eh_frame(pc, bases, eh_frame_addr, end)
+    }
+}
+
+/// Searches the `.eh_frame` section at `eh_frame_addr` linearly.
+///
+/// # Safety
+///
+/// The section must be a valid `.eh_frame` section which ends at `end`.
+unsafe fn find_fde_with_eh_frame(
+    pc: usize,
+    bases: BaseAddresses,
+    eh_frame_addr: usize,
+    end: EhFrameEnd,
+) -> Option<FDESearchResult> {
+    // The section may be freed and its address reused later, so it is only indexed while
+    // registered with `index_eh_frame`.
+    #[cfg(target_has_atomic = "ptr")]
+    if let Some(entry) = table::get(eh_frame_addr) {
+        let (eh_frame, fde) = entry.find_fde(&bases, pc)?;
+        return Some(FDESearchResult {
+            fde,
+            bases,
+            section: eh_frame.into(),
+            image_end: None,
+        });
     }
+    let eh_frame_len = unsafe { end.len(eh_frame_addr)? };
+    let eh_frame = EhFrame::new(
+        unsafe { get_slice(eh_frame_addr, eh_frame_len) },
+        NativeEndian,
+    );
+    let fde = fde_for_address(&eh_frame, &bases, pc).ok()?;
+    Some(FDESearchResult {
+        fde,
+        bases,
+        section: eh_frame.into(),
+        image_end: None,
+    })
 }
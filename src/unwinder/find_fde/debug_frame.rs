@@ -0,0 +1,464 @@
+#[cfg(feature = "cfi-minimal")]
+use super::cfi;
+use super::{FDESearchResult, FrameSection};
+use crate::util::*;
+
+use alloc::vec::Vec;
+use core::ffi::{c_void, CStr};
+use core::mem;
+use core::ops;
+use core::ptr;
+use core::slice;
+#[cfg(not(feature = "cfi-minimal"))]
+use gimli::UnwindSection;
+use gimli::{BaseAddresses, DebugFrame, NativeEndian};
+use libc::{PT_LOAD, PT_NOTE};
+
+#[cfg(target_pointer_width = "32")]
+use libc::{Elf32_Ehdr as Elf_Ehdr, Elf32_Shdr as Elf_Shdr};
+#[cfg(target_pointer_width = "64")]
+use libc::{Elf64_Ehdr as Elf_Ehdr, Elf64_Shdr as Elf_Shdr};
+
+#[cfg(target_pointer_width = "32")]
+const ELF_CLASS: u8 = libc::ELFCLASS32;
+#[cfg(target_pointer_width = "64")]
+const ELF_CLASS: u8 = libc::ELFCLASS64;
+
+#[cfg(target_endian = "little")]
+const ELF_DATA: u8 = libc::ELFDATA2LSB;
+#[cfg(target_endian = "big")]
+const ELF_DATA: u8 = libc::ELFDATA2MSB;
+
+const SHT_NOBITS: u32 = 8;
+const SHF_COMPRESSED: usize = 0x800;
+const NT_GNU_BUILD_ID: u32 = 3;
+
+const DEBUG_DIR: &[u8] = b"/usr/lib/debug";
+
+pub struct DebugFrameFinder(());
+
+pub const fn get_finder() -> &'static DebugFrameFinder {
+    &DebugFrameFinder(())
+}
+
+/// A loaded module, along with its `.debug_frame` section if one could be found.
+///
+/// Modules are told apart by their base address, name and build ID.
+struct Module {
+    base: usize,
+    name: Vec<u8>,
+    build_id: Vec<u8>,
+    // The file mapped to read `.debug_frame` from, and the section in it.
+    file: Option<&'static [u8]>,
+    debug_frame: Option<&'static [u8]>,
+}
+
+struct GlobalState {
+    // The number of modules ever loaded and unloaded when `modules` was last pruned, if known.
+    counters: Option<(u64, u64)>,
+    modules: Vec<Module>,
+}
+
+fn lock_global_state() -> impl ops::DerefMut<Target = GlobalState> {
+    static mut MUTEX: libc::pthread_mutex_t = libc::PTHREAD_MUTEX_INITIALIZER;
+    unsafe { libc::pthread_mutex_lock(ptr::addr_of_mut!(MUTEX)) };
+
+    static mut STATE: GlobalState = GlobalState {
+        counters: None,
+        modules: Vec::new(),
+    };
+
+    struct LockGuard;
+    impl Drop for LockGuard {
+        fn drop(&mut self) {
+            unsafe { libc::pthread_mutex_unlock(ptr::addr_of_mut!(MUTEX)) };
+        }
+    }
+
+    impl ops::Deref for LockGuard {
+        type Target = GlobalState;
+
+        fn deref(&self) -> &GlobalState {
+            unsafe { &*ptr::addr_of!(STATE) }
+        }
+    }
+
+    impl ops::DerefMut for LockGuard {
+        fn deref_mut(&mut self) -> &mut GlobalState {
+            unsafe { &mut *ptr::addr_of_mut!(STATE) }
+        }
+    }
+
+    LockGuard
+}
+
+impl super::FDEFinder for DebugFrameFinder {
+    fn find_fde(&self, pc: usize) -> Option<FDESearchResult> {
+        // Reading module files allocates and takes locks, so this can't be done from a signal
+        // handler.
+        if cfg!(feature = "signal-safe") {
+            return None;
+        }
+
+        let info = find_loaded_module(pc)?;
+
+        let debug_frame = {
+            let mut guard = lock_global_state();
+            // Modules unloaded since are dropped, so that a module loaded in their place isn't
+            // mistaken for them. Without the counters this has to be checked every time.
+            if info.counters.is_none() || info.counters != guard.counters {
+                prune_unloaded(&mut guard.modules);
+                guard.counters = info.counters;
+            }
+            match guard
+                .modules
+                .iter()
+                .find(|x| x.base == info.base && x.name == info.name && x.build_id == info.build_id)
+            {
+                Some(module) => module.debug_frame,
+                None => {
+                    let (file, debug_frame) = load_debug_frame(&info.name, &info.build_id).unzip();
+                    guard.modules.push(Module {
+                        base: info.base,
+                        name: info.name,
+                        build_id: info.build_id,
+                        file,
+                        debug_frame,
+                    });
+                    debug_frame
+                }
+            }
+        }?;
+
+        let mut debug_frame = DebugFrame::new(debug_frame, NativeEndian);
+        debug_frame.set_address_size(mem::size_of::<usize>() as _);
+        let bases = BaseAddresses::default();
+        let pc = pc.wrapping_sub(info.base) as u64;
+        #[cfg(not(feature = "cfi-minimal"))]
+        let fde = debug_frame
+            .fde_for_address(&bases, pc, DebugFrame::cie_from_offset)
+            .ok()?;
+        #[cfg(feature = "cfi-minimal")]
+        let fde = cfi::fdes(
+            gimli::Section::reader(&debug_frame),
+            cfi::Kind::DebugFrame,
+            &bases,
+        )
+        .map_while(Result::ok)
+        .find(|fde| fde.contains(pc))?;
+        Some(FDESearchResult {
+            fde,
+            bases,
+            section: FrameSection::DebugFrame {
+                debug_frame,
+                bias: info.base,
+            },
+            image_end: Some(info.end),
+        })
+    }
+}
+
+struct ModuleInfo {
+    base: usize,
+    // The end of the last loaded segment.
+    end: usize,
+    name: Vec<u8>,
+    build_id: Vec<u8>,
+    counters: Option<(u64, u64)>,
+}
+
+// Find the module containing `pc`. The information is copied out, as the module may be unloaded
+// once `dl_iterate_phdr` returns.
+fn find_loaded_module(pc: usize) -> Option<ModuleInfo> {
+    use libc::{dl_iterate_phdr, dl_phdr_info};
+
+    struct CallbackData {
+        pc: usize,
+        result: Option<ModuleInfo>,
+    }
+
+    unsafe extern "C" fn phdr_callback(
+        info: *mut dl_phdr_info,
+        size: usize,
+        data: *mut c_void,
+    ) -> c_int {
+        unsafe {
+            let data = &mut *(data as *mut CallbackData);
+            let base = (*info).dlpi_addr as usize;
+            let phdrs = slice::from_raw_parts((*info).dlpi_phdr, (*info).dlpi_phnum as usize);
+            let mut contains_pc = false;
+            let mut end = 0;
+            for phdr in phdrs.iter().filter(|x| x.p_type == PT_LOAD) {
+                let start = base + phdr.p_vaddr as usize;
+                let range = start..start + phdr.p_memsz as usize;
+                contains_pc |= range.contains(&data.pc);
+                end = end.max(range.end);
+            }
+            if !contains_pc {
+                return 0;
+            }
+
+            let name = module_name(&*info);
+            let mut build_id = Vec::new();
+            for phdr in phdrs.iter().filter(|x| x.p_type == PT_NOTE) {
+                let notes = slice::from_raw_parts(
+                    (base + phdr.p_vaddr as usize) as *const u8,
+                    phdr.p_memsz as usize,
+                );
+                if let Some(id) = find_build_id(notes) {
+                    build_id.extend_from_slice(id);
+                    break;
+                }
+            }
+            data.result = Some(ModuleInfo {
+                base,
+                end,
+                name: name.to_vec(),
+                build_id,
+                counters: phdr_counters(&*info, size),
+            });
+            1
+        }
+    }
+
+    let mut data = CallbackData { pc, result: None };
+    unsafe { dl_iterate_phdr(Some(phdr_callback), &mut data as *mut CallbackData as _) };
+    data.result
+}
+
+unsafe fn module_name(info: &libc::dl_phdr_info) -> &[u8] {
+    if info.dlpi_name.is_null() {
+        &[]
+    } else {
+        unsafe { CStr::from_ptr(info.dlpi_name).to_bytes() }
+    }
+}
+
+// Drop the modules that are no longer loaded, and unmap their files. Nothing can be unwinding
+// through their code anymore, so their `.debug_frame` sections are no longer in use.
+fn prune_unloaded(modules: &mut Vec<Module>) {
+    use libc::{dl_iterate_phdr, dl_phdr_info};
+
+    struct CallbackData<'a> {
+        modules: &'a [Module],
+        loaded: Vec<bool>,
+    }
+
+    unsafe extern "C" fn phdr_callback(
+        info: *mut dl_phdr_info,
+        _size: usize,
+        data: *mut c_void,
+    ) -> c_int {
+        unsafe {
+            let data = &mut *(data as *mut CallbackData<'_>);
+            let base = (*info).dlpi_addr as usize;
+            let name = module_name(&*info);
+            for (module, loaded) in data.modules.iter().zip(&mut data.loaded) {
+                *loaded |= module.base == base && module.name == name;
+            }
+            0
+        }
+    }
+
+    if modules.is_empty() {
+        return;
+    }
+    let mut data = CallbackData {
+        modules,
+        loaded: alloc::vec![false; modules.len()],
+    };
+    unsafe { dl_iterate_phdr(Some(phdr_callback), &mut data as *mut CallbackData<'_> as _) };
+    let mut loaded = data.loaded.into_iter();
+    modules.retain(|module| {
+        let loaded = loaded.next().unwrap();
+        if !loaded {
+            if let Some(file) = module.file {
+                unsafe { unmap_file(file) };
+            }
+        }
+        loaded
+    });
+}
+
+fn find_build_id(mut notes: &[u8]) -> Option<&[u8]> {
+    const fn align4(x: usize) -> usize {
+        (x + 3) & !3
+    }
+
+    while notes.len() >= 12 {
+        let namesz = read::<u32>(notes, 0)? as usize;
+        let descsz = read::<u32>(notes, 4)? as usize;
+        let ty = read::<u32>(notes, 8)?;
+        let name = notes.get(12..12 + namesz)?;
+        let desc_start = 12 + align4(namesz);
+        let desc = notes.get(desc_start..desc_start + descsz)?;
+        if ty == NT_GNU_BUILD_ID && name == b"GNU\0" {
+            return Some(desc);
+        }
+        notes = notes.get(desc_start + align4(descsz)..)?;
+    }
+    None
+}
+
+// Read the `.debug_frame` section for a module, either from the module itself or from its
+// separate debug file. The file it is read from is returned along with it.
+fn load_debug_frame(name: &[u8], build_id: &[u8]) -> Option<(&'static [u8], &'static [u8])> {
+    // The main executable has an empty name.
+    let path = if name.is_empty() {
+        current_exe()?
+    } else {
+        name.to_vec()
+    };
+    let file = map_file(&path)?;
+
+    if let Some(debug_frame) = find_section(file, b".debug_frame") {
+        return Some((file, debug_frame));
+    }
+
+    let mut debug_frame = None;
+    if !build_id.is_empty() {
+        // /usr/lib/debug/.build-id/xx/xxxxxx.debug
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+        let mut path = DEBUG_DIR.to_vec();
+        path.extend_from_slice(b"/.build-id/");
+        for (i, byte) in build_id.iter().enumerate() {
+            if i == 1 {
+                path.push(b'/');
+            }
+            path.push(HEX[(byte >> 4) as usize]);
+            path.push(HEX[(byte & 0xf) as usize]);
+        }
+        path.extend_from_slice(b".debug");
+        debug_frame = load_separate_debug_frame(&path);
+    }
+
+    if debug_frame.is_none() {
+        if let Some(link) = find_section(file, b".gnu_debuglink") {
+            let link = &link[..link.iter().position(|&x| x == 0)?];
+            let dir = match path.iter().rposition(|&x| x == b'/') {
+                Some(pos) => &path[..pos],
+                None => b".",
+            };
+
+            // Same search order as GDB: next to the file, in `.debug`, and in the global debug
+            // directory.
+            let candidates: [&[&[u8]]; 3] = [
+                &[dir, b"/", link],
+                &[dir, b"/.debug/", link],
+                &[DEBUG_DIR, dir, b"/", link],
+            ];
+            for parts in candidates {
+                let path = parts.concat();
+                debug_frame = load_separate_debug_frame(&path);
+                if debug_frame.is_some() {
+                    break;
+                }
+            }
+        }
+    }
+
+    unsafe { unmap_file(file) };
+    debug_frame
+}
+
+fn current_exe() -> Option<Vec<u8>> {
+    let mut buf = alloc::vec![0u8; libc::PATH_MAX as usize];
+    let len =
+        unsafe { libc::readlink(c"/proc/self/exe".as_ptr(), buf.as_mut_ptr() as _, buf.len()) };
+    if len <= 0 || len as usize == buf.len() {
+        return None;
+    }
+    buf.truncate(len as usize);
+    Some(buf)
+}
+
+fn load_separate_debug_frame(path: &[u8]) -> Option<(&'static [u8], &'static [u8])> {
+    let file = map_file(path)?;
+    match find_section(file, b".debug_frame") {
+        Some(debug_frame) => Some((file, debug_frame)),
+        None => {
+            unsafe { unmap_file(file) };
+            None
+        }
+    }
+}
+
+fn map_file(path: &[u8]) -> Option<&'static [u8]> {
+    let mut path = path.to_vec();
+    path.push(0);
+
+    unsafe {
+        let fd = libc::open(path.as_ptr() as _, libc::O_RDONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return None;
+        }
+        let mut stat: libc::stat = mem::zeroed();
+        let len = if libc::fstat(fd, &mut stat) == 0 {
+            stat.st_size as usize
+        } else {
+            0
+        };
+        let addr = if len == 0 {
+            libc::MAP_FAILED
+        } else {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                fd,
+                0,
+            )
+        };
+        libc::close(fd);
+        if addr == libc::MAP_FAILED {
+            return None;
+        }
+        Some(slice::from_raw_parts(addr as *const u8, len))
+    }
+}
+
+unsafe fn unmap_file(file: &'static [u8]) {
+    unsafe { libc::munmap(file.as_ptr() as *mut c_void, file.len()) };
+}
+
+fn read<T: Copy>(data: &[u8], offset: usize) -> Option<T> {
+    let bytes = data.get(offset..offset.checked_add(mem::size_of::<T>())?)?;
+    Some(unsafe { (bytes.as_ptr() as *const T).read_unaligned() })
+}
+
+// Compressed sections (`SHF_COMPRESSED`, as produced by `--compress-debug-sections`) are not
+// supported, as that would need a zlib or zstd decoder; they are treated as missing.
+fn find_section(file: &'static [u8], name: &[u8]) -> Option<&'static [u8]> {
+    let ehdr = read::<Elf_Ehdr>(file, 0)?;
+    if ehdr.e_ident[..4] != *b"\x7fELF"
+        || ehdr.e_ident[libc::EI_CLASS] != ELF_CLASS
+        || ehdr.e_ident[libc::EI_DATA] != ELF_DATA
+        || ehdr.e_shentsize as usize != mem::size_of::<Elf_Shdr>()
+    {
+        return None;
+    }
+
+    let shdr = |index: usize| {
+        read::<Elf_Shdr>(
+            file,
+            (ehdr.e_shoff as usize).checked_add(index.checked_mul(mem::size_of::<Elf_Shdr>())?)?,
+        )
+    };
+    let shstrtab = shdr(ehdr.e_shstrndx as usize)?;
+    let shstrtab = file.get(shstrtab.sh_offset as usize..)?;
+
+    for i in 0..ehdr.e_shnum as usize {
+        let section = shdr(i)?;
+        let section_name = shstrtab.get(section.sh_name as usize..)?;
+        if !section_name.starts_with(name) || section_name.get(name.len()) != Some(&0) {
+            continue;
+        }
+        if section.sh_type == SHT_NOBITS || section.sh_flags as usize & SHF_COMPRESSED != 0 {
+            return None;
+        }
+        let start = section.sh_offset as usize;
+        return file.get(start..start.checked_add(section.sh_size as usize)?);
+    }
+    None
+}
@@ -0,0 +1,85 @@
+//! Parsing of the FDEs in an `.eh_frame` section, for the finders that look up `.eh_frame`.
+
+#[cfg(all(target_arch = "aarch64", not(feature = "cfi-minimal")))]
+use super::b_key;
+#[cfg(feature = "cfi-minimal")]
+use super::cfi;
+use super::Fde;
+use crate::util::*;
+use gimli::{BaseAddresses, EhFrame, EhFrameOffset};
+#[cfg(not(feature = "cfi-minimal"))]
+use gimli::{CommonInformationEntry, UnwindSection};
+
+/// Parses the CIE at `offset` in `eh_frame`, for the finders to pass when parsing FDEs.
+#[cfg(not(feature = "cfi-minimal"))]
+fn cie_from_offset(
+    eh_frame: &EhFrame<StaticSlice>,
+    bases: &BaseAddresses,
+    offset: EhFrameOffset,
+) -> gimli::Result<CommonInformationEntry<StaticSlice>> {
+    let cie = eh_frame.cie_from_offset(bases, offset);
+    #[cfg(target_arch = "aarch64")]
+    if let Err(gimli::Error::UnknownAugmentation) = cie {
+        return b_key::cie_from_offset(eh_frame, bases, offset);
+    }
+    cie
+}
+
+/// Parses the FDE at `offset` in `eh_frame`.
+pub(super) fn fde_from_offset(
+    eh_frame: &EhFrame<StaticSlice>,
+    bases: &BaseAddresses,
+    offset: EhFrameOffset,
+) -> gimli::Result<Fde> {
+    #[cfg(not(feature = "cfi-minimal"))]
+    return eh_frame.fde_from_offset(bases, offset, cie_from_offset);
+    #[cfg(feature = "cfi-minimal")]
+    return cfi::fde_from_offset(
+        gimli::Section::reader(eh_frame),
+        offset.0,
+        cfi::Kind::EhFrame,
+        bases,
+    );
+}
+
+/// Iterates over the FDEs in `eh_frame`.
+pub(super) fn fdes<'a>(
+    eh_frame: &'a EhFrame<StaticSlice>,
+    bases: &'a BaseAddresses,
+) -> impl Iterator<Item = gimli::Result<Fde>> + 'a {
+    #[cfg(feature = "cfi-minimal")]
+    return cfi::fdes(gimli::Section::reader(eh_frame), cfi::Kind::EhFrame, bases);
+    // gimli's `entries` parses every CIE it passes, and stops at the first one it can't parse.
+    #[cfg(all(target_arch = "aarch64", not(feature = "cfi-minimal")))]
+    return b_key::fde_offsets(eh_frame)
+        .map(|offset| eh_frame.fde_from_offset(bases, offset, cie_from_offset));
+    #[cfg(not(any(target_arch = "aarch64", feature = "cfi-minimal")))]
+    {
+        let mut entries = eh_frame.entries(bases);
+        core::iter::from_fn(move || loop {
+            match entries.next() {
+                Ok(Some(gimli::CieOrFde::Fde(partial))) => {
+                    return Some(partial.parse(cie_from_offset))
+                }
+                Ok(Some(gimli::CieOrFde::Cie(_))) => (),
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        })
+    }
+}
+
+/// Searches `eh_frame` linearly for the FDE covering `pc`.
+pub(super) fn fde_for_address(
+    eh_frame: &EhFrame<StaticSlice>,
+    bases: &BaseAddresses,
+    pc: usize,
+) -> gimli::Result<Fde> {
+    for fde in fdes(eh_frame, bases) {
+        let fde = fde?;
+        if fde.contains(pc as _) {
+            return Ok(fde);
+        }
+    }
+    Err(gimli::Error::NoUnwindInfoForAddress)
+}
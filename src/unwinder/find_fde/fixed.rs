@@ -1,11 +1,11 @@
-use super::FDESearchResult;
+use super::{table, FDESearchResult};
 use crate::util::*;
 
-use gimli::{BaseAddresses, EhFrame, NativeEndian, UnwindSection};
+use gimli::BaseAddresses;
 
 pub struct StaticFinder(());
 
-pub fn get_finder() -> &'static StaticFinder {
+pub const fn get_finder() -> &'static StaticFinder {
     &StaticFinder(())
 }
 
@@ -13,6 +13,9 @@ extern "C" {
     static __executable_start: u8;
     static __etext: u8;
     static __eh_frame: u8;
+    // Optional. Without it the section must end with a zero terminator.
+    #[linkage = "extern_weak"]
+    static __eh_frame_end: *const u8;
 }
 
 impl super::FDEFinder for StaticFinder {
@@ -24,17 +27,23 @@ impl super::FDEFinder for StaticFinder {
                 return None;
             }
 
-            let eh_frame = &__eh_frame as *const u8 as usize;
+            let eh_frame_addr = &__eh_frame as *const u8 as usize;
             let bases = BaseAddresses::default()
-                .set_eh_frame(eh_frame as _)
+                .set_eh_frame(eh_frame_addr as _)
                 .set_text(text_start as _);
-            let eh_frame = EhFrame::new(get_unlimited_slice(eh_frame as _), NativeEndian);
-
-            if let Ok(fde) = eh_frame.fde_for_address(&bases, pc as _, EhFrame::cie_from_offset) {
+            // The terminator is searched for no further than the end of the image.
+            let end = if __eh_frame_end.is_null() {
+                EhFrameEnd::Terminator(image_end().unwrap_or(usize::MAX))
+            } else {
+                EhFrameEnd::At(__eh_frame_end as usize)
+            };
+
+            if let Some((eh_frame, fde)) = table::find_fde(eh_frame_addr, end, &bases, pc) {
                 return Some(FDESearchResult {
                     fde,
                     bases,
-                    eh_frame,
+                    section: eh_frame.into(),
+                    image_end: image_end(),
                 });
             }
 
@@ -1,11 +1,11 @@
-use super::FDESearchResult;
+use super::{hdr_table_fde, table, FDESearchResult};
 use crate::util::*;
 
-use gimli::{BaseAddresses, EhFrame, EhFrameHdr, NativeEndian, UnwindSection};
+use gimli::{BaseAddresses, EhFrameHdr, NativeEndian};
 
 pub struct StaticFinder(());
 
-pub fn get_finder() -> &'static StaticFinder {
+pub const fn get_finder() -> &'static StaticFinder {
     &StaticFinder(())
 }
 
@@ -13,6 +13,10 @@ extern "C" {
     static __executable_start: u8;
     static __etext: u8;
     static __GNU_EH_FRAME_HDR: u8;
+    // Optional, as with `fde-static`. Without it the `.eh_frame` section must end with a zero
+    // terminator.
+    #[linkage = "extern_weak"]
+    static __eh_frame_end: *const u8;
 }
 
 impl super::FDEFinder for StaticFinder {
@@ -29,38 +33,33 @@ impl super::FDEFinder for StaticFinder {
                 .set_text(text_start as _)
                 .set_eh_frame_hdr(eh_frame_hdr as _);
             let eh_frame_hdr = EhFrameHdr::new(
-                get_unlimited_slice(eh_frame_hdr as usize as _),
+                get_slice(eh_frame_hdr, eh_frame_hdr_len(eh_frame_hdr)?),
                 NativeEndian,
             )
             .parse(&bases, core::mem::size_of::<usize>() as _)
             .ok()?;
-            let eh_frame = deref_pointer(eh_frame_hdr.eh_frame_ptr());
-            let bases = bases.set_eh_frame(eh_frame as _);
-            let eh_frame = EhFrame::new(get_unlimited_slice(eh_frame as _), NativeEndian);
+            let eh_frame_addr = deref_pointer(eh_frame_hdr.eh_frame_ptr());
+            let bases = bases.set_eh_frame(eh_frame_addr as _);
+            // The terminator is searched for no further than the end of the image.
+            let end = if __eh_frame_end.is_null() {
+                EhFrameEnd::Terminator(image_end().unwrap_or(usize::MAX))
+            } else {
+                EhFrameEnd::At(__eh_frame_end as usize)
+            };
 
-            // Use binary search table for address if available.
-            if let Some(table) = eh_frame_hdr.table() {
-                if let Ok(fde) =
-                    table.fde_for_address(&eh_frame, &bases, pc as _, EhFrame::cie_from_offset)
-                {
-                    return Some(FDESearchResult {
-                        fde,
-                        bases,
-                        eh_frame,
-                    });
-                }
-            }
-
-            // Otherwise do the linear search.
-            if let Ok(fde) = eh_frame.fde_for_address(&bases, pc as _, EhFrame::cie_from_offset) {
-                return Some(FDESearchResult {
-                    fde,
-                    bases,
-                    eh_frame,
-                });
-            }
-
-            None
+            let (eh_frame, fde) = match eh_frame_hdr.table() {
+                // Use binary search table for address if available. GNU ld doesn't define
+                // `__eh_frame_end`, so the section is only read up to the FDE found.
+                Some(table) => hdr_table_fde(&table, eh_frame_addr, end.bound(), &bases, pc)?,
+                // Otherwise build a search table on first use.
+                None => table::find_fde(eh_frame_addr, end, &bases, pc)?,
+            };
+            Some(FDESearchResult {
+                fde,
+                bases,
+                section: eh_frame.into(),
+                image_end: image_end(),
+            })
         }
     }
 }
@@ -1,39 +1,246 @@
+#[cfg(feature = "fde-phdr-raw")]
+mod auxv;
+#[cfg(all(
+    target_arch = "aarch64",
+    not(feature = "cfi-minimal"),
+    any(
+        feature = "fde-phdr",
+        feature = "fde-registry",
+        feature = "fde-static",
+        feature = "fde-static-regions",
+        feature = "fde-gnu-eh-frame-hdr",
+        feature = "fde-custom"
+    )
+))]
+mod b_key;
+#[cfg(all(feature = "fde-custom", target_has_atomic = "ptr"))]
+mod chain;
 #[cfg(feature = "fde-custom")]
 mod custom;
+#[cfg(feature = "fde-debug-frame")]
+mod debug_frame;
+#[cfg(any(
+    feature = "fde-phdr",
+    feature = "fde-registry",
+    feature = "fde-static",
+    feature = "fde-static-regions",
+    feature = "fde-gnu-eh-frame-hdr",
+    feature = "fde-custom"
+))]
+mod eh_frame;
 #[cfg(feature = "fde-static")]
 mod fixed;
 #[cfg(feature = "fde-gnu-eh-frame-hdr")]
 mod gnu_eh_frame_hdr;
 #[cfg(feature = "fde-phdr")]
 mod phdr;
+#[cfg(feature = "fde-static-regions")]
+mod regions;
 #[cfg(feature = "fde-registry")]
 mod registry;
+#[cfg(any(
+    feature = "fde-static",
+    feature = "fde-static-regions",
+    feature = "fde-gnu-eh-frame-hdr",
+    feature = "fde-registry",
+    all(feature = "fde-custom", target_has_atomic = "ptr")
+))]
+#[cfg_attr(
+    not(all(
+        target_has_atomic = "ptr",
+        any(
+            feature = "fde-static",
+            feature = "fde-static-regions",
+            feature = "fde-gnu-eh-frame-hdr"
+        )
+    )),
+    allow(dead_code, unused_imports)
+)]
+mod table;
 
+#[cfg(feature = "cfi-minimal")]
+use super::cfi;
 use crate::util::*;
-use gimli::{BaseAddresses, EhFrame, FrameDescriptionEntry};
+#[cfg(any(
+    feature = "fde-phdr",
+    feature = "fde-registry",
+    feature = "fde-static",
+    feature = "fde-static-regions",
+    feature = "fde-gnu-eh-frame-hdr",
+    feature = "fde-custom"
+))]
+use eh_frame::{fde_for_address, fde_from_offset};
+#[cfg(feature = "fde-debug-frame")]
+use gimli::DebugFrame;
+#[cfg(any(
+    feature = "fde-gnu-eh-frame-hdr",
+    feature = "fde-static-regions",
+    feature = "fde-custom"
+))]
+use gimli::EhFrameOffset;
+#[cfg(not(feature = "cfi-minimal"))]
+use gimli::FrameDescriptionEntry;
+use gimli::{BaseAddresses, EhFrame};
+#[cfg(any(
+    feature = "fde-gnu-eh-frame-hdr",
+    feature = "fde-static-regions",
+    feature = "fde-custom"
+))]
+use gimli::{EhHdrTable, NativeEndian};
 
 #[cfg(feature = "fde-custom")]
 pub mod custom_eh_frame_finder {
+    #[cfg(target_has_atomic = "ptr")]
+    pub use super::custom::{
+        index_eh_frame, remove_eh_frame_index, EhFrameIndexId, IndexEhFrameError,
+    };
     pub use super::custom::{
         set_custom_eh_frame_finder, EhFrameFinder, FrameInfo, FrameInfoKind,
         SetCustomEhFrameFinderError,
     };
 }
 
+#[cfg(all(feature = "fde-custom", target_has_atomic = "ptr"))]
+pub mod fde_finder {
+    pub use super::chain::{
+        add_finder, finder_priority, remove_finder, set_finder_priority, AddFinderError, FinderId,
+        MAX_FINDERS,
+    };
+    pub use super::custom::FdeLocation;
+    pub use super::{FDEFinder, FDESearchResult};
+}
+
+#[cfg(all(feature = "fde-phdr-dl", feature = "signal-safe"))]
+pub mod phdr_snapshot {
+    pub use super::phdr::snapshot::{update, MAX_MODULES};
+}
+
+#[cfg(feature = "fde-static-regions")]
+pub mod static_regions {
+    pub use super::regions::{StaticRegion, StaticRegionInfo};
+}
+
+#[cfg(all(
+    target_has_atomic = "ptr",
+    any(
+        feature = "fde-static",
+        feature = "fde-static-regions",
+        feature = "fde-gnu-eh-frame-hdr"
+    )
+))]
+pub mod fde_table {
+    pub use super::table::{set_fde_table_buffer, FdeTableEntry, SetFdeTableBufferError};
+}
+
+/// A parsed FDE. With `cfi-minimal`, it is parsed by the built-in CFI parser instead of gimli.
+#[cfg(not(feature = "cfi-minimal"))]
+pub(crate) type Fde = FrameDescriptionEntry<StaticSlice>;
+#[cfg(feature = "cfi-minimal")]
+pub(crate) use cfi::Fde;
+
+/// The FDE covering a PC, as returned by an [`FDEFinder`].
 #[derive(Debug)]
 pub struct FDESearchResult {
-    pub fde: FrameDescriptionEntry<StaticSlice>,
-    pub bases: BaseAddresses,
-    pub eh_frame: EhFrame<StaticSlice>,
+    pub(crate) fde: Fde,
+    pub(crate) bases: BaseAddresses,
+    pub(crate) section: FrameSection,
+    // The end of the loaded image the FDE belongs to, if known. The LSDA is bounded by it.
+    #[cfg_attr(
+        not(any(feature = "personality", feature = "personality-gcc")),
+        allow(dead_code)
+    )]
+    pub(crate) image_end: Option<usize>,
 }
 
+/// The section an FDE is found in.
+#[derive(Debug)]
+pub enum FrameSection {
+    EhFrame(EhFrame<StaticSlice>),
+    /// A `.debug_frame` section read from file. Addresses in it are not relocated, so `bias`
+    /// needs to be added to get the runtime addresses.
+    #[cfg(feature = "fde-debug-frame")]
+    DebugFrame {
+        debug_frame: DebugFrame<StaticSlice>,
+        bias: usize,
+    },
+}
+
+impl FrameSection {
+    pub fn bias(&self) -> usize {
+        match self {
+            FrameSection::EhFrame(_) => 0,
+            #[cfg(feature = "fde-debug-frame")]
+            FrameSection::DebugFrame { bias, .. } => *bias,
+        }
+    }
+}
+
+impl From<EhFrame<StaticSlice>> for FrameSection {
+    fn from(eh_frame: EhFrame<StaticSlice>) -> Self {
+        FrameSection::EhFrame(eh_frame)
+    }
+}
+
+/// A source of unwind information.
+///
+/// Finders are consulted in turn to find the FDE covering a PC. With `fde-custom`, additional
+/// finders can be added with `fde_finder::add_finder`.
 pub trait FDEFinder {
+    /// Finds the FDE covering `pc`, or returns `None` if `pc` is unknown to this finder.
     fn find_fde(&self, pc: usize) -> Option<FDESearchResult>;
 }
 
+/// Finds the FDE covering `pc` with the binary search table of an `.eh_frame_hdr` section, and
+/// returns it along with the `.eh_frame` section.
+///
+/// The section is only sliced up to the end of the FDE, so its full length isn't needed. The FDE
+/// must end before `end`.
+#[cfg(any(
+    feature = "fde-gnu-eh-frame-hdr",
+    feature = "fde-static-regions",
+    feature = "fde-custom"
+))]
+unsafe fn hdr_table_fde(
+    table: &EhHdrTable<'_, StaticSlice>,
+    eh_frame_addr: usize,
+    end: usize,
+    bases: &BaseAddresses,
+    pc: usize,
+) -> Option<(EhFrame<StaticSlice>, Fde)> {
+    let fde_addr = unsafe { deref_pointer(table.lookup(pc as _, bases).ok()?) };
+    let offset = fde_addr.checked_sub(eh_frame_addr)?;
+    let (fde_end, _) = unsafe { eh_frame_entry(fde_addr, end)? };
+    let eh_frame = EhFrame::new(
+        unsafe { get_slice(eh_frame_addr, fde_end - eh_frame_addr) },
+        NativeEndian,
+    );
+    let fde = fde_from_offset(&eh_frame, bases, EhFrameOffset(offset)).ok()?;
+    fde.contains(pc as _).then_some((eh_frame, fde))
+}
+
 pub struct GlobalFinder(());
 
 impl FDEFinder for GlobalFinder {
+    #[cfg(all(feature = "fde-custom", target_has_atomic = "ptr"))]
+    fn find_fde(&self, pc: usize) -> Option<FDESearchResult> {
+        chain::find_fde(pc)
+    }
+
+    // Without `fde-custom`, or without the pointer atomics the chain needs, the chain can't be
+    // changed, so the built-in finders are consulted in the same order directly.
+    #[cfg(not(all(feature = "fde-custom", target_has_atomic = "ptr")))]
+    #[cfg_attr(
+        not(any(
+            feature = "fde-phdr",
+            feature = "fde-registry",
+            feature = "fde-static",
+            feature = "fde-static-regions",
+            feature = "fde-gnu-eh-frame-hdr",
+            feature = "fde-custom",
+            feature = "fde-debug-frame"
+        )),
+        allow(unused_variables)
+    )]
     fn find_fde(&self, pc: usize) -> Option<FDESearchResult> {
         #[cfg(feature = "fde-custom")]
         if let Some(v) = custom::get_finder().find_fde(pc) {
@@ -51,10 +258,18 @@ impl FDEFinder for GlobalFinder {
         if let Some(v) = phdr::get_finder().find_fde(pc) {
             return Some(v);
         }
+        #[cfg(feature = "fde-debug-frame")]
+        if let Some(v) = debug_frame::get_finder().find_fde(pc) {
+            return Some(v);
+        }
         #[cfg(feature = "fde-static")]
         if let Some(v) = fixed::get_finder().find_fde(pc) {
             return Some(v);
         }
+        #[cfg(feature = "fde-static-regions")]
+        if let Some(v) = regions::get_finder().find_fde(pc) {
+            return Some(v);
+        }
         None
     }
 }
@@ -1,19 +1,49 @@
-use super::FDESearchResult;
+use super::{fde_for_address, fde_from_offset, FDESearchResult};
 use crate::util::*;
 
 use core::mem;
+use core::ops::Range;
 use core::slice;
-use gimli::{BaseAddresses, EhFrame, EhFrameHdr, NativeEndian, UnwindSection};
-use libc::{PT_DYNAMIC, PT_GNU_EH_FRAME, PT_LOAD};
+use gimli::{BaseAddresses, EhFrame, EhFrameHdr, EhFrameOffset, NativeEndian, ParsedEhFrameHdr};
+
+// ELF definitions are spelled out here rather than taken from libc, as `fde-phdr-raw` doesn't
+// depend on it.
+const PT_LOAD: u32 = 1;
+#[cfg(not(target_arch = "powerpc64"))]
+const PT_DYNAMIC: u32 = 2;
+const PT_GNU_EH_FRAME: u32 = 0x6474e550;
 
-#[cfg(target_pointer_width = "32")]
-use libc::Elf32_Phdr as Elf_Phdr;
 #[cfg(target_pointer_width = "64")]
-use libc::Elf64_Phdr as Elf_Phdr;
+#[allow(non_camel_case_types)]
+#[repr(C)]
+struct Elf_Phdr {
+    p_type: u32,
+    p_flags: u32,
+    p_offset: u64,
+    p_vaddr: u64,
+    p_paddr: u64,
+    p_filesz: u64,
+    p_memsz: u64,
+    p_align: u64,
+}
+
+#[cfg(target_pointer_width = "32")]
+#[allow(non_camel_case_types)]
+#[repr(C)]
+struct Elf_Phdr {
+    p_type: u32,
+    p_offset: u32,
+    p_vaddr: u32,
+    p_paddr: u32,
+    p_filesz: u32,
+    p_memsz: u32,
+    p_flags: u32,
+    p_align: u32,
+}
 
 pub struct PhdrFinder(());
 
-pub fn get_finder() -> &'static PhdrFinder {
+pub const fn get_finder() -> &'static PhdrFinder {
     &PhdrFinder(())
 }
 
@@ -23,81 +53,471 @@ impl super::FDEFinder for PhdrFinder {
         if let Some(v) = search_aux_phdr(pc) {
             return Some(v);
         }
-        #[cfg(feature = "fde-phdr-dl")]
+        #[cfg(feature = "fde-phdr-raw")]
+        if let Some(v) = search_raw_phdr(pc) {
+            return Some(v);
+        }
+        #[cfg(all(feature = "fde-phdr-dl", not(feature = "signal-safe")))]
         if let Some(v) = search_dl_phdr(pc) {
             return Some(v);
         }
+        #[cfg(all(feature = "fde-phdr-dl", feature = "signal-safe"))]
+        if let Some(v) = search_dl_find_object(pc) {
+            return Some(v);
+        }
         None
     }
 }
 
 #[cfg(feature = "fde-phdr-aux")]
 fn search_aux_phdr(pc: usize) -> Option<FDESearchResult> {
-    use libc::{getauxval, AT_PHDR, AT_PHNUM, PT_PHDR};
+    use libc::{getauxval, AT_PHDR, AT_PHNUM};
 
     unsafe {
         let phdr = getauxval(AT_PHDR) as *const Elf_Phdr;
         let phnum = getauxval(AT_PHNUM) as usize;
         let phdrs = slice::from_raw_parts(phdr, phnum);
-        // With known address of PHDR, we can calculate the base address in reverse.
-        let base =
-            phdrs.as_ptr() as usize - phdrs.iter().find(|x| x.p_type == PT_PHDR)?.p_vaddr as usize;
-        search_phdr(phdrs, base, pc)
+        let module = find_module(phdrs, exe_base(phdrs)?, pc)?;
+        search_module(&module, pc)
+    }
+}
+
+#[cfg(any(feature = "fde-phdr-aux", feature = "fde-phdr-raw"))]
+fn exe_base(phdrs: &[Elf_Phdr]) -> Option<usize> {
+    const PT_PHDR: u32 = 6;
+
+    // With known address of PHDR, we can calculate the base address in reverse.
+    if let Some(phdr) = phdrs.iter().find(|x| x.p_type == PT_PHDR) {
+        return Some(phdrs.as_ptr() as usize - phdr.p_vaddr as usize);
+    }
+
+    // Static PIE executables may come without PHDR. The program headers then usually directly
+    // follow the ELF header, which is at the start of the first segment.
+    const EHDR_SIZE: usize = if cfg!(target_pointer_width = "64") {
+        64
+    } else {
+        52
+    };
+    let ehdr = (phdrs.as_ptr() as usize).checked_sub(EHDR_SIZE)?;
+    if unsafe { *(ehdr as *const [u8; 4]) } != *b"\x7fELF" {
+        return None;
     }
+    let first = phdrs
+        .iter()
+        .find(|x| x.p_type == PT_LOAD && x.p_offset == 0)?;
+    Some(ehdr - first.p_vaddr as usize)
 }
 
-#[cfg(feature = "fde-phdr-dl")]
+#[cfg(feature = "fde-phdr-raw")]
+fn search_raw_phdr(pc: usize) -> Option<FDESearchResult> {
+    let auxv = super::auxv::get()?;
+
+    unsafe {
+        let phdrs = slice::from_raw_parts(auxv.phdr as *const Elf_Phdr, auxv.phnum);
+        if let Some(module) = find_module(phdrs, exe_base(phdrs)?, pc) {
+            return search_module(&module, pc);
+        }
+
+        // The vDSO isn't listed anywhere else without libc.
+        if auxv.sysinfo_ehdr != 0 {
+            let ehdr = auxv.sysinfo_ehdr;
+            let phdrs = ehdr_phdrs(ehdr);
+            // The ELF header is mapped at the start of the first segment.
+            let first = phdrs
+                .iter()
+                .find(|x| x.p_type == PT_LOAD && x.p_offset == 0)?;
+            let module = find_module(phdrs, ehdr - first.p_vaddr as usize, pc)?;
+            return search_module(&module, pc);
+        }
+    }
+    None
+}
+
+// Get the program headers of the module whose ELF header is mapped at `ehdr`.
+#[cfg(any(
+    feature = "fde-phdr-raw",
+    all(feature = "fde-phdr-dl", feature = "signal-safe")
+))]
+unsafe fn ehdr_phdrs(ehdr: usize) -> &'static [Elf_Phdr] {
+    unsafe {
+        // `e_phoff` and `e_phnum` of the ELF header.
+        let (phoff, phnum) = if cfg!(target_pointer_width = "64") {
+            (
+                *((ehdr + 32) as *const u64) as usize,
+                *((ehdr + 56) as *const u16),
+            )
+        } else {
+            (
+                *((ehdr + 28) as *const u32) as usize,
+                *((ehdr + 44) as *const u16),
+            )
+        };
+        slice::from_raw_parts((ehdr + phoff) as *const Elf_Phdr, phnum as usize)
+    }
+}
+
+#[cfg(all(feature = "fde-phdr-dl", feature = "signal-safe"))]
+#[repr(C)]
+struct DlFindObject {
+    dlfo_flags: u64,
+    dlfo_map_start: *mut core::ffi::c_void,
+    dlfo_map_end: *mut core::ffi::c_void,
+    dlfo_link_map: *mut core::ffi::c_void,
+    dlfo_eh_frame: *mut core::ffi::c_void,
+    __dlfo_reserved: [u64; 7],
+}
+
+#[cfg(all(feature = "fde-phdr-dl", feature = "signal-safe"))]
+type DlFindObjectFn = unsafe extern "C" fn(*mut core::ffi::c_void, *mut DlFindObject) -> c_int;
+
+// `_dl_find_object` was added in glibc 2.35. It is linked weakly so other C libraries still work.
+#[cfg(all(feature = "fde-phdr-dl", feature = "signal-safe"))]
+fn dl_find_object() -> Option<DlFindObjectFn> {
+    extern "C" {
+        #[linkage = "extern_weak"]
+        static _dl_find_object: Option<DlFindObjectFn>;
+    }
+
+    unsafe { _dl_find_object }
+}
+
+// `dl_iterate_phdr` takes the loader lock, so it can't be used from a signal handler.
+// `_dl_find_object` is meant for this purpose; without it the snapshot of the loaded modules is
+// searched instead.
+#[cfg(all(feature = "fde-phdr-dl", feature = "signal-safe"))]
+fn search_dl_find_object(pc: usize) -> Option<FDESearchResult> {
+    let Some(dl_find_object) = dl_find_object() else {
+        return snapshot::find_fde(pc);
+    };
+
+    unsafe {
+        let mut result = mem::zeroed::<DlFindObject>();
+        if dl_find_object(pc as _, &mut result) != 0 {
+            return None;
+        }
+
+        // The mapping starts with the ELF header, and `l_addr` is the first field of the link map.
+        let ehdr = result.dlfo_map_start as usize;
+        if *(ehdr as *const [u8; 4]) != *b"\x7fELF" {
+            return None;
+        }
+        let base = *(result.dlfo_link_map as *const usize);
+        let module = find_module(ehdr_phdrs(ehdr), base, pc)?;
+        search_module(&module, pc)
+    }
+}
+
+#[cfg(all(feature = "fde-phdr-dl", not(feature = "signal-safe")))]
 fn search_dl_phdr(pc: usize) -> Option<FDESearchResult> {
     use core::ffi::c_void;
     use libc::{dl_iterate_phdr, dl_phdr_info};
 
     struct CallbackData {
         pc: usize,
+        first: bool,
         result: Option<FDESearchResult>,
     }
 
     unsafe extern "C" fn phdr_callback(
         info: *mut dl_phdr_info,
-        _size: usize,
+        size: usize,
         data: *mut c_void,
     ) -> c_int {
         unsafe {
             let data = &mut *(data as *mut CallbackData);
-            let phdrs = slice::from_raw_parts((*info).dlpi_phdr, (*info).dlpi_phnum as usize);
-            if let Some(v) = search_phdr(phdrs, (*info).dlpi_addr as _, data.pc) {
-                data.result = Some(v);
+
+            // The cache is only consulted and updated from within the callback, so that the
+            // modules it refers to cannot be unloaded while we're using them.
+            let mut cache = frame_hdr_cache::lock();
+            if data.first {
+                data.first = false;
+                if let Some(cache) = cache.as_deref_mut() {
+                    cache.validate(&*info, size);
+                    if let Some(module) = cache.lookup(data.pc) {
+                        data.result = search_module(module, data.pc);
+                        return 1;
+                    }
+                }
+            }
+
+            let phdrs = slice::from_raw_parts(
+                (*info).dlpi_phdr as *const Elf_Phdr,
+                (*info).dlpi_phnum as usize,
+            );
+            if let Some(module) = find_module(phdrs, (*info).dlpi_addr as _, data.pc) {
+                data.result = search_module(&module, data.pc);
+                if let Some(cache) = cache.as_deref_mut() {
+                    cache.insert(module);
+                }
                 return 1;
             }
             0
         }
     }
 
-    let mut data = CallbackData { pc, result: None };
+    let mut data = CallbackData {
+        pc,
+        first: true,
+        result: None,
+    };
     unsafe { dl_iterate_phdr(Some(phdr_callback), &mut data as *mut CallbackData as _) };
     data.result
 }
 
-fn search_phdr(phdrs: &[Elf_Phdr], base: usize, pc: usize) -> Option<FDESearchResult> {
+/// Cache of recently used modules, similar to libgcc's `frame_hdr_cache`.
+///
+/// `dl_iterate_phdr` reports the number of modules ever loaded and unloaded. If either changes,
+/// all cached entries are discarded, so a module that got unloaded is never dereferenced.
+#[cfg(all(feature = "fde-phdr-dl", not(feature = "signal-safe")))]
+mod frame_hdr_cache {
+    use super::Module;
+    use crate::util::phdr_counters as counters;
+    use core::ops;
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use libc::dl_phdr_info;
+
+    const CACHE_ENTRIES: usize = 8;
+
+    pub struct Cache {
+        adds: u64,
+        subs: u64,
+        // Ordered from the most recently used to the least recently used.
+        entries: [Option<Module>; CACHE_ENTRIES],
+    }
+
+    impl Cache {
+        pub fn validate(&mut self, info: &dl_phdr_info, size: usize) {
+            match counters(info, size) {
+                Some((adds, subs)) if adds == self.adds && subs == self.subs => (),
+                Some((adds, subs)) => {
+                    self.adds = adds;
+                    self.subs = subs;
+                    self.entries = [const { None }; CACHE_ENTRIES];
+                }
+                // Without the counters we can't know whether the entries are still valid.
+                None => {
+                    self.entries = [const { None }; CACHE_ENTRIES];
+                }
+            }
+        }
+
+        pub fn lookup(&mut self, pc: usize) -> Option<&Module> {
+            let idx = self
+                .entries
+                .iter()
+                .position(|x| x.as_ref().is_some_and(|x| x.text.contains(&pc)))?;
+            self.entries[..=idx].rotate_right(1);
+            self.entries[0].as_ref()
+        }
+
+        pub fn insert(&mut self, module: Module) {
+            self.entries.rotate_right(1);
+            self.entries[0] = Some(module);
+        }
+    }
+
+    static LOCKED: AtomicBool = AtomicBool::new(false);
+    static mut CACHE: Cache = Cache {
+        adds: 0,
+        subs: 0,
+        entries: [const { None }; CACHE_ENTRIES],
+    };
+
+    pub struct Guard(());
+
+    impl ops::Deref for Guard {
+        type Target = Cache;
+
+        fn deref(&self) -> &Cache {
+            unsafe { &*core::ptr::addr_of!(CACHE) }
+        }
+    }
+
+    impl ops::DerefMut for Guard {
+        fn deref_mut(&mut self) -> &mut Cache {
+            unsafe { &mut *core::ptr::addr_of_mut!(CACHE) }
+        }
+    }
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            LOCKED.store(false, Ordering::Release);
+        }
+    }
+
+    /// Acquire the cache, or nothing if it's in use.
+    ///
+    /// Not all C libraries serialize `dl_iterate_phdr` callbacks, so the cache needs its own lock.
+    /// Contention (or reentrance) just means we search without the cache rather than wait.
+    pub fn lock() -> Option<Guard> {
+        LOCKED
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| Guard(()))
+    }
+}
+
+/// Program headers of the loaded modules, which can be read without taking the loader lock.
+///
+/// This is searched in signal-safe mode when the C library lacks `_dl_find_object`. It is filled
+/// when the program starts and refreshed by [`update`], so frames of a library loaded with
+/// `dlopen` later on are not found until [`update`] is called. Modules beyond the capacity of the
+/// snapshot are not included.
+#[cfg(all(feature = "fde-phdr-dl", feature = "signal-safe"))]
+pub mod snapshot {
+    use super::{find_module, search_module, Elf_Phdr, FDESearchResult};
+    use crate::util::c_int;
+    use core::ffi::c_void;
+    use core::slice;
+    use core::sync::atomic::{fence, AtomicBool, AtomicUsize, Ordering};
+    use libc::{dl_iterate_phdr, dl_phdr_info};
+
+    /// The number of modules the snapshot can hold.
+    pub const MAX_MODULES: usize = 128;
+
+    struct Slot {
+        // Zero if the slot is free. Written last when a slot is filled, and first when cleared.
+        phdr: AtomicUsize,
+        phnum: AtomicUsize,
+        base: AtomicUsize,
+    }
+
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_SLOT: Slot = Slot {
+        phdr: AtomicUsize::new(0),
+        phnum: AtomicUsize::new(0),
+        base: AtomicUsize::new(0),
+    };
+
+    static SNAPSHOT: [Slot; MAX_MODULES] = [EMPTY_SLOT; MAX_MODULES];
+    static UPDATING: AtomicBool = AtomicBool::new(false);
+
+    // Take the first snapshot before `main`, when no signal handler can interrupt the loader.
+    #[used]
+    #[link_section = ".init_array"]
+    static INIT: extern "C" fn() = update_on_start;
+
+    extern "C" fn update_on_start() {
+        update();
+    }
+
+    /// Takes a new snapshot of the loaded modules.
+    ///
+    /// The snapshot taken when the program starts only covers the modules loaded with it, so this
+    /// needs to be called after shared libraries are loaded or unloaded with `dlopen` or `dlclose`.
+    /// It does nothing if the C library provides `_dl_find_object`.
+    ///
+    /// This takes the loader lock, so it must not be called from a signal handler.
+    pub fn update() {
+        if super::dl_find_object().is_some() {
+            return;
+        }
+        // Not in a signal handler, so it's fine to wait for another thread taking a snapshot.
+        while UPDATING
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+
+        unsafe extern "C" fn phdr_callback(
+            info: *mut dl_phdr_info,
+            _size: usize,
+            data: *mut c_void,
+        ) -> c_int {
+            unsafe {
+                let next = &mut *(data as *mut usize);
+                let Some(slot) = SNAPSHOT.get(*next) else {
+                    return 1;
+                };
+                *next += 1;
+
+                let phdr = (*info).dlpi_phdr as usize;
+                let phnum = (*info).dlpi_phnum as usize;
+                let base = (*info).dlpi_addr as usize;
+                if slot.phdr.load(Ordering::Relaxed) == phdr
+                    && slot.phnum.load(Ordering::Relaxed) == phnum
+                    && slot.base.load(Ordering::Relaxed) == base
+                {
+                    return 0;
+                }
+                slot.phdr.store(0, Ordering::Release);
+                slot.phnum.store(phnum, Ordering::Relaxed);
+                slot.base.store(base, Ordering::Relaxed);
+                slot.phdr.store(phdr, Ordering::Release);
+                0
+            }
+        }
+
+        let mut next = 0usize;
+        unsafe { dl_iterate_phdr(Some(phdr_callback), &mut next as *mut usize as _) };
+        for slot in &SNAPSHOT[next..] {
+            slot.phdr.store(0, Ordering::Release);
+        }
+        UPDATING.store(false, Ordering::Release);
+    }
+
+    pub(super) fn find_fde(pc: usize) -> Option<FDESearchResult> {
+        for slot in SNAPSHOT.iter() {
+            let phdr = slot.phdr.load(Ordering::Acquire);
+            if phdr == 0 {
+                continue;
+            }
+            let phnum = slot.phnum.load(Ordering::Relaxed);
+            let base = slot.base.load(Ordering::Relaxed);
+            fence(Ordering::Acquire);
+            if slot.phdr.load(Ordering::Relaxed) != phdr {
+                continue;
+            }
+
+            let phdrs = unsafe { slice::from_raw_parts(phdr as *const Elf_Phdr, phnum) };
+            if let Some(module) = find_module(phdrs, base, pc) {
+                return search_module(&module, pc);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Clone)]
+struct Module {
+    // Only needed to look the module up in the cache.
+    #[cfg_attr(
+        any(not(feature = "fde-phdr-dl"), feature = "signal-safe"),
+        allow(dead_code)
+    )]
+    text: Range<usize>,
+    bases: BaseAddresses,
+    eh_frame_hdr: ParsedEhFrameHdr<StaticSlice>,
+    eh_frame: &'static [u8],
+    // The end of the last loaded segment.
+    image_end: usize,
+}
+
+fn find_module(phdrs: &[Elf_Phdr], base: usize, pc: usize) -> Option<Module> {
     unsafe {
         let mut text = None;
         let mut eh_frame_hdr = None;
+        let mut image_end = 0;
+        #[cfg(not(target_arch = "powerpc64"))]
         let mut dynamic = None;
 
         for phdr in phdrs {
             let start = base + phdr.p_vaddr as usize;
+            let range = start..start + phdr.p_memsz as usize;
             match phdr.p_type {
                 PT_LOAD => {
-                    let end = start + phdr.p_memsz as usize;
-                    let range = start..end;
+                    image_end = image_end.max(range.end);
                     if range.contains(&pc) {
                         text = Some(range);
                     }
                 }
                 PT_GNU_EH_FRAME => {
-                    eh_frame_hdr = Some(start);
+                    eh_frame_hdr = Some(range);
                 }
+                #[cfg(not(target_arch = "powerpc64"))]
                 PT_DYNAMIC => {
-                    dynamic = Some(start);
+                    dynamic = Some(range);
                 }
                 _ => (),
             }
@@ -107,60 +527,86 @@ fn search_phdr(phdrs: &[Elf_Phdr], base: usize, pc: usize) -> Option<FDESearchRe
         let eh_frame_hdr = eh_frame_hdr?;
 
         let mut bases = BaseAddresses::default()
-            .set_eh_frame_hdr(eh_frame_hdr as _)
+            .set_eh_frame_hdr(eh_frame_hdr.start as _)
             .set_text(text.start as _);
 
-        // Find the GOT section.
-        if let Some(start) = dynamic {
+        // Find the GOT section. On PowerPC64 `DT_PLTGOT` points to the PLT instead, and as libgcc
+        // does, no data-relative base is set there.
+        #[cfg(not(target_arch = "powerpc64"))]
+        if let Some(dynamic) = dynamic {
             const DT_NULL: usize = 0;
             const DT_PLTGOT: usize = 3;
 
-            let mut tags = start as *const [usize; 2];
-            let mut tag = *tags;
-            while tag[0] != DT_NULL {
+            let tags = slice::from_raw_parts(
+                dynamic.start as *const [usize; 2],
+                dynamic.len() / mem::size_of::<[usize; 2]>(),
+            );
+            for tag in tags.iter().take_while(|tag| tag[0] != DT_NULL) {
                 if tag[0] == DT_PLTGOT {
                     bases = bases.set_got(tag[1] as _);
                     break;
                 }
-                tags = tags.add(1);
-                tag = *tags;
             }
         }
 
         // Parse .eh_frame_hdr section.
         let eh_frame_hdr = EhFrameHdr::new(
-            get_unlimited_slice(eh_frame_hdr as usize as _),
+            get_slice(eh_frame_hdr.start, eh_frame_hdr.len()),
             NativeEndian,
         )
         .parse(&bases, mem::size_of::<usize>() as _)
         .ok()?;
 
+        // .eh_frame has no program header of its own, so it's bounded by the segment it's in.
         let eh_frame = deref_pointer(eh_frame_hdr.eh_frame_ptr());
+        let eh_frame_end = phdrs.iter().find_map(|phdr| {
+            let start = base + phdr.p_vaddr as usize;
+            let end = start + phdr.p_memsz as usize;
+            (phdr.p_type == PT_LOAD && (start..end).contains(&eh_frame)).then_some(end)
+        })?;
         bases = bases.set_eh_frame(eh_frame as _);
-        let eh_frame = EhFrame::new(get_unlimited_slice(eh_frame as usize as _), NativeEndian);
-
-        // Use binary search table for address if available.
-        if let Some(table) = eh_frame_hdr.table() {
-            if let Ok(fde) =
-                table.fde_for_address(&eh_frame, &bases, pc as _, EhFrame::cie_from_offset)
-            {
-                return Some(FDESearchResult {
-                    fde,
-                    bases,
-                    eh_frame,
-                });
-            }
-        }
 
-        // Otherwise do the linear search.
-        if let Ok(fde) = eh_frame.fde_for_address(&bases, pc as _, EhFrame::cie_from_offset) {
+        Some(Module {
+            text,
+            bases,
+            eh_frame_hdr,
+            eh_frame: get_slice(eh_frame, eh_frame_end - eh_frame),
+            image_end,
+        })
+    }
+}
+
+fn search_module(module: &Module, pc: usize) -> Option<FDESearchResult> {
+    let bases = module.bases.clone();
+    let eh_frame_hdr = &module.eh_frame_hdr;
+    let eh_frame = EhFrame::new(module.eh_frame, NativeEndian);
+
+    // Use binary search table for address if available.
+    if let Some(table) = eh_frame_hdr.table() {
+        let fde = table.lookup(pc as _, &bases).ok().and_then(|fde_addr| {
+            let offset =
+                unsafe { deref_pointer(fde_addr) }.checked_sub(module.eh_frame.as_ptr() as _)?;
+            fde_from_offset(&eh_frame, &bases, EhFrameOffset(offset)).ok()
+        });
+        if let Some(fde) = fde.filter(|fde| fde.contains(pc as _)) {
             return Some(FDESearchResult {
                 fde,
                 bases,
-                eh_frame,
+                section: eh_frame.into(),
+                image_end: Some(module.image_end),
             });
         }
+    }
 
-        None
+    // Otherwise do the linear search.
+    if let Ok(fde) = fde_for_address(&eh_frame, &bases, pc) {
+        return Some(FDESearchResult {
+            fde,
+            bases,
+            section: eh_frame.into(),
+            image_end: Some(module.image_end),
+        });
     }
+
+    None
 }
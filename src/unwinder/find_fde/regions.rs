@@ -0,0 +1,143 @@
+use super::{hdr_table_fde, table, FDESearchResult};
+use crate::util::*;
+
+use core::mem;
+use core::slice;
+use gimli::{BaseAddresses, EhFrameHdr, NativeEndian};
+
+/// A range of code and the unwind information describing it.
+///
+/// Regions are collected by the linker from the `unwinding_regions` section, so each region is
+/// declared as a static placed in that section:
+///
+/// ```ignore
+/// use core::ptr::addr_of;
+///
+/// extern "C" {
+///     static __kernel_text_start: u8;
+///     static __kernel_text_end: u8;
+///     static __kernel_eh_frame: u8;
+///     static __kernel_eh_frame_end: u8;
+/// }
+///
+/// #[used]
+/// #[link_section = "unwinding_regions"]
+/// static KERNEL: StaticRegion = StaticRegion {
+///     text_start: addr_of!(__kernel_text_start),
+///     text_end: addr_of!(__kernel_text_end),
+///     info: StaticRegionInfo::EhFrame {
+///         start: addr_of!(__kernel_eh_frame),
+///         end: addr_of!(__kernel_eh_frame_end),
+///     },
+/// };
+/// ```
+#[repr(C)]
+pub struct StaticRegion {
+    pub text_start: *const u8,
+    pub text_end: *const u8,
+    pub info: StaticRegionInfo,
+}
+
+#[repr(C)]
+pub enum StaticRegionInfo {
+    /// An `.eh_frame` section. If `end` is null, the section must end with a zero terminator
+    /// before the end of the image, `_end`.
+    EhFrame { start: *const u8, end: *const u8 },
+    /// An `.eh_frame_hdr` section. `end` must be past both the `.eh_frame_hdr` section and the
+    /// `.eh_frame` section it refers to.
+    EhFrameHdr { start: *const u8, end: *const u8 },
+}
+
+unsafe impl Sync for StaticRegion {}
+
+extern "C" {
+    // Provided by the linker for sections whose names are valid C identifiers. They are weak, as
+    // the section doesn't exist if no region is declared.
+    #[linkage = "extern_weak"]
+    static __start_unwinding_regions: *const StaticRegion;
+    #[linkage = "extern_weak"]
+    static __stop_unwinding_regions: *const StaticRegion;
+}
+
+fn regions() -> &'static [StaticRegion] {
+    unsafe {
+        let start = __start_unwinding_regions;
+        let stop = __stop_unwinding_regions;
+        if start.is_null() || stop.is_null() {
+            return &[];
+        }
+        let len = (stop as usize - start as usize) / mem::size_of::<StaticRegion>();
+        slice::from_raw_parts(start, len)
+    }
+}
+
+pub struct RegionsFinder(());
+
+pub const fn get_finder() -> &'static RegionsFinder {
+    &RegionsFinder(())
+}
+
+impl super::FDEFinder for RegionsFinder {
+    fn find_fde(&self, pc: usize) -> Option<FDESearchResult> {
+        regions()
+            .iter()
+            .filter(|region| (region.text_start as usize..region.text_end as usize).contains(&pc))
+            .find_map(|region| unsafe { search_region(region, pc) })
+    }
+}
+
+unsafe fn search_region(region: &StaticRegion, pc: usize) -> Option<FDESearchResult> {
+    let text_start = region.text_start as usize;
+    match region.info {
+        StaticRegionInfo::EhFrame { start, end } => {
+            let eh_frame_addr = start as usize;
+            // The terminator is searched for no further than the end of the image.
+            let end = if end.is_null() {
+                EhFrameEnd::Terminator(image_end().unwrap_or(usize::MAX))
+            } else {
+                EhFrameEnd::At(end as usize)
+            };
+            let bases = BaseAddresses::default()
+                .set_eh_frame(eh_frame_addr as _)
+                .set_text(text_start as _);
+
+            let (eh_frame, fde) = unsafe { table::find_fde(eh_frame_addr, end, &bases, pc)? };
+            Some(FDESearchResult {
+                fde,
+                bases,
+                section: eh_frame.into(),
+                image_end: image_end(),
+            })
+        }
+        StaticRegionInfo::EhFrameHdr { start, end } => {
+            let eh_frame_hdr = start as usize;
+            let end = end as usize;
+            let bases = BaseAddresses::default()
+                .set_text(text_start as _)
+                .set_eh_frame_hdr(eh_frame_hdr as _);
+            let eh_frame_hdr = EhFrameHdr::new(
+                unsafe { get_slice(eh_frame_hdr, end.checked_sub(eh_frame_hdr)?) },
+                NativeEndian,
+            )
+            .parse(&bases, mem::size_of::<usize>() as _)
+            .ok()?;
+            let eh_frame_addr = unsafe { deref_pointer(eh_frame_hdr.eh_frame_ptr()) };
+            let bases = bases.set_eh_frame(eh_frame_addr as _);
+
+            let (eh_frame, fde) = match eh_frame_hdr.table() {
+                // Use binary search table for address if available.
+                Some(table) => unsafe { hdr_table_fde(&table, eh_frame_addr, end, &bases, pc)? },
+                // Otherwise build a search table on first use.
+                None => unsafe {
+                    table::find_fde(eh_frame_addr, EhFrameEnd::Terminator(end), &bases, pc)?
+                },
+            };
+            Some(FDESearchResult {
+                fde,
+                bases,
+                section: eh_frame.into(),
+                image_end: image_end(),
+            })
+        }
+    }
+}
@@ -1,11 +1,12 @@
-use super::FDESearchResult;
-use crate::util::get_unlimited_slice;
-use alloc::boxed::Box;
+use super::table::{self, FdeTableEntry};
+use super::{fde_for_address, FDESearchResult};
+use crate::util::{get_slice, EhFrameEnd};
+use alloc::{boxed::Box, vec::Vec};
 use core::ffi::c_void;
 use core::mem::MaybeUninit;
 use core::ops;
 use core::ptr;
-use gimli::{BaseAddresses, EhFrame, NativeEndian, UnwindSection};
+use gimli::{BaseAddresses, EhFrame, NativeEndian};
 
 enum Table {
     Single(*const c_void),
@@ -17,6 +18,99 @@ struct Object {
     tbase: usize,
     dbase: usize,
     table: Table,
+    // The lengths and search tables of the sections, built on first lookup. This is boxed to keep
+    // the pointer thin, as `Object` needs to fit in the storage that crtbegin.o reserves for
+    // libgcc.
+    index: Option<Box<Index>>,
+}
+
+struct Index {
+    // In the order of `Object::sections`.
+    sections: Box<[SectionIndex]>,
+}
+
+struct SectionIndex {
+    eh_frame_len: usize,
+    // `None` if the table couldn't be allocated, in which case the section is searched linearly.
+    entries: Option<Box<[FdeTableEntry]>>,
+}
+
+impl Object {
+    fn bases(&self, eh_frame_addr: usize) -> BaseAddresses {
+        BaseAddresses::default()
+            .set_text(self.tbase as _)
+            .set_got(self.dbase as _)
+            .set_eh_frame(eh_frame_addr as _)
+    }
+
+    /// The addresses of the registered `.eh_frame` sections.
+    unsafe fn sections(&self) -> impl Iterator<Item = usize> {
+        let (single, mut addrs) = match self.table {
+            Table::Single(addr) => (Some(addr as usize), ptr::null()),
+            Table::Multiple(addrs) => (None, addrs),
+        };
+        single.into_iter().chain(core::iter::from_fn(move || {
+            if addrs.is_null() {
+                return None;
+            }
+            let addr = unsafe { *addrs };
+            if addr.is_null() {
+                return None;
+            }
+            addrs = unsafe { addrs.add(1) };
+            Some(addr as usize)
+        }))
+    }
+
+    /// Measures the sections and builds their search tables. Returns `None` if the allocation
+    /// fails or a section is malformed.
+    unsafe fn build_index(&self) -> Option<Box<Index>> {
+        let mut sections = Vec::new();
+        for addr in unsafe { self.sections() } {
+            // The sections are registered without their length, so the terminator is searched for
+            // once here.
+            let eh_frame_len = unsafe { EhFrameEnd::Terminator(usize::MAX).len(addr)? };
+            let eh_frame = EhFrame::new(unsafe { get_slice(addr, eh_frame_len) }, NativeEndian);
+            let entries = table::build(&eh_frame, &self.bases(addr));
+            sections.try_reserve(1).ok()?;
+            sections.push(SectionIndex {
+                eh_frame_len,
+                entries,
+            });
+        }
+        Some(Box::new(Index {
+            sections: sections.into_boxed_slice(),
+        }))
+    }
+
+    /// Finds the FDE covering `pc` in the sections.
+    unsafe fn find_fde(&self, pc: usize) -> Option<FDESearchResult> {
+        let search = |addr: usize, eh_frame_len: usize, entries: Option<&[FdeTableEntry]>| {
+            let eh_frame = EhFrame::new(unsafe { get_slice(addr, eh_frame_len) }, NativeEndian);
+            let bases = self.bases(addr);
+            let fde = match entries {
+                Some(entries) => table::lookup(entries, &eh_frame, &bases, pc),
+                None => fde_for_address(&eh_frame, &bases, pc).ok(),
+            }?;
+            Some(FDESearchResult {
+                fde,
+                bases,
+                section: eh_frame.into(),
+                image_end: None,
+            })
+        };
+        match &self.index {
+            Some(index) => unsafe { self.sections() }
+                .zip(index.sections.iter())
+                .find_map(|(addr, section)| {
+                    search(addr, section.eh_frame_len, section.entries.as_deref())
+                }),
+            None => unsafe { self.sections() }.find_map(|addr| {
+                let eh_frame_len = unsafe { EhFrameEnd::Terminator(usize::MAX).len(addr)? };
+                search(addr, eh_frame_len, None)
+            }),
+        }
+    }
 }
 
 struct GlobalState {
@@ -29,6 +123,11 @@ pub struct Registry(());
 
 // `unsafe` because there is no protection for reentrance.
 unsafe fn lock_global_state() -> impl ops::DerefMut<Target = GlobalState> {
+    unsafe { acquire_global_state() }
+}
+
+#[cfg(not(feature = "signal-safe"))]
+unsafe fn acquire_global_state() -> impl ops::DerefMut<Target = GlobalState> {
     #[cfg(feature = "libc")]
     {
         static mut MUTEX: libc::pthread_mutex_t = libc::PTHREAD_MUTEX_INITIALIZER;
@@ -73,54 +172,104 @@ unsafe fn lock_global_state() -> impl ops::DerefMut<Target = GlobalState> {
     compile_error!("Either feature \"libc\" or \"spin\" must be enabled to use \"fde-registry\".");
 }
 
-pub fn get_finder() -> &'static Registry {
+// In signal-safe mode the lock records the thread holding it, so that a lookup from a signal
+// handler can tell whether it interrupted that thread. Only then does it not wait for the lock.
+#[cfg(feature = "signal-safe")]
+mod owned_lock {
+    use super::GlobalState;
+    use core::ops;
+    use core::ptr;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    // The thread holding the lock, or zero if it is free.
+    static OWNER: AtomicUsize = AtomicUsize::new(0);
+
+    static mut STATE: GlobalState = GlobalState {
+        object: ptr::null_mut(),
+    };
+
+    // Without `libc` threads can't be told apart, so the lock is always assumed to be held by the
+    // current thread.
+    fn current_thread() -> usize {
+        #[cfg(feature = "libc")]
+        return unsafe { libc::pthread_self() } as usize;
+        #[cfg(not(feature = "libc"))]
+        1
+    }
+
+    pub(super) struct LockGuard(());
+
+    impl Drop for LockGuard {
+        fn drop(&mut self) {
+            OWNER.store(0, Ordering::Release);
+        }
+    }
+
+    impl ops::Deref for LockGuard {
+        type Target = GlobalState;
+
+        #[allow(static_mut_refs)]
+        fn deref(&self) -> &GlobalState {
+            unsafe { &*core::ptr::addr_of!(STATE) }
+        }
+    }
+
+    impl ops::DerefMut for LockGuard {
+        fn deref_mut(&mut self) -> &mut GlobalState {
+            unsafe { &mut *core::ptr::addr_of_mut!(STATE) }
+        }
+    }
+
+    /// Takes the lock, or returns `None` if it is held by the current thread.
+    pub(super) fn lock() -> Option<LockGuard> {
+        let current = current_thread();
+        loop {
+            match OWNER.compare_exchange_weak(0, current, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => return Some(LockGuard(())),
+                Err(owner) if owner == current => return None,
+                Err(_) => {
+                    #[cfg(feature = "libc")]
+                    unsafe {
+                        libc::sched_yield()
+                    };
+                    core::hint::spin_loop();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "signal-safe")]
+unsafe fn acquire_global_state() -> impl ops::DerefMut<Target = GlobalState> {
+    // Registration isn't allowed in signal handlers, so the lock can't be held by this thread.
+    owned_lock::lock().unwrap()
+}
+
+pub const fn get_finder() -> &'static Registry {
     &Registry(())
 }
 
 impl super::FDEFinder for Registry {
     fn find_fde(&self, pc: usize) -> Option<FDESearchResult> {
         unsafe {
+            #[cfg(not(feature = "signal-safe"))]
             let guard = lock_global_state();
+            // If the lock is held by this thread, it's been interrupted by a signal handler while
+            // holding it, so waiting would deadlock.
+            #[cfg(feature = "signal-safe")]
+            let Some(guard) = owned_lock::lock() else {
+                return snapshot::find_fde(pc);
+            };
             let mut cur = guard.object;
 
             while !cur.is_null() {
-                let bases = BaseAddresses::default()
-                    .set_text((*cur).tbase as _)
-                    .set_got((*cur).dbase as _);
-                match (*cur).table {
-                    Table::Single(addr) => {
-                        let eh_frame = EhFrame::new(get_unlimited_slice(addr as _), NativeEndian);
-                        let bases = bases.clone().set_eh_frame(addr as usize as _);
-                        if let Ok(fde) =
-                            eh_frame.fde_for_address(&bases, pc as _, EhFrame::cie_from_offset)
-                        {
-                            return Some(FDESearchResult {
-                                fde,
-                                bases,
-                                eh_frame,
-                            });
-                        }
-                    }
-                    Table::Multiple(mut addrs) => {
-                        let mut addr = *addrs;
-                        while !addr.is_null() {
-                            let eh_frame =
-                                EhFrame::new(get_unlimited_slice(addr as _), NativeEndian);
-                            let bases = bases.clone().set_eh_frame(addr as usize as _);
-                            if let Ok(fde) =
-                                eh_frame.fde_for_address(&bases, pc as _, EhFrame::cie_from_offset)
-                            {
-                                return Some(FDESearchResult {
-                                    fde,
-                                    bases,
-                                    eh_frame,
-                                });
-                            }
-
-                            addrs = addrs.add(1);
-                            addr = *addrs;
-                        }
-                    }
+                // In signal-safe mode, this is done on registration instead.
+                #[cfg(not(feature = "signal-safe"))]
+                if (*cur).index.is_none() {
+                    (*cur).index = (*cur).build_index();
+                }
+                if let Some(result) = (*cur).find_fde(pc) {
+                    return Some(result);
                 }
 
                 cur = (*cur).next;
@@ -148,11 +297,27 @@ unsafe extern "C" fn __register_frame_info_bases(
             tbase: tbase as _,
             dbase: dbase as _,
             table: Table::Single(begin),
+            index: None,
         });
 
+        // Lookups must not allocate in signal-safe mode, so build the search table now.
+        #[cfg(feature = "signal-safe")]
+        {
+            (*ob).index = (*ob).build_index();
+        }
+
         let mut guard = lock_global_state();
         (*ob).next = guard.object;
         guard.object = ob;
+        #[cfg(feature = "signal-safe")]
+        if let Some(index) = &(*ob).index {
+            snapshot::insert(
+                begin as _,
+                index.sections[0].eh_frame_len,
+                tbase as _,
+                dbase as _,
+            );
+        }
     }
 }
 
@@ -184,8 +349,14 @@ unsafe extern "C" fn __register_frame_info_table_bases(
             tbase: tbase as _,
             dbase: dbase as _,
             table: Table::Multiple(begin as _),
+            index: None,
         });
 
+        #[cfg(feature = "signal-safe")]
+        {
+            (*ob).index = (*ob).build_index();
+        }
+
         let mut guard = lock_global_state();
         (*ob).next = guard.object;
         guard.object = ob;
@@ -227,6 +398,9 @@ extern "C" fn __deregister_frame_info_bases(begin: *const c_void) -> *mut Object
             };
             if found {
                 *prev = (*cur).next;
+                (*cur).index = None;
+                #[cfg(feature = "signal-safe")]
+                snapshot::remove(begin as _);
                 return cur;
             }
             prev = &mut (*cur).next;
@@ -250,3 +424,95 @@ unsafe extern "C" fn __deregister_frame(begin: *const c_void) {
     let storage = __deregister_frame_info(begin);
     drop(unsafe { Box::from_raw(storage as *mut MaybeUninit<Object>) })
 }
+
+/// Copies of the registered sections which can be read without taking the lock, for lookups that
+/// interrupted the thread holding it.
+///
+/// Sections registered with `__register_frame_table`, or beyond the capacity of the snapshot, are
+/// not included.
+#[cfg(feature = "signal-safe")]
+mod snapshot {
+    use super::{fde_for_address, FDESearchResult};
+    use crate::util::get_slice;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use gimli::{BaseAddresses, EhFrame, NativeEndian};
+
+    const SLOTS: usize = 64;
+
+    struct Slot {
+        // Zero if the slot is free. Written last when a slot is filled, and first when cleared.
+        begin: AtomicUsize,
+        // Lookups reading the slot. A slot isn't reused, and its section isn't deregistered,
+        // until they are done.
+        readers: AtomicUsize,
+        eh_frame_len: AtomicUsize,
+        tbase: AtomicUsize,
+        dbase: AtomicUsize,
+    }
+
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_SLOT: Slot = Slot {
+        begin: AtomicUsize::new(0),
+        readers: AtomicUsize::new(0),
+        eh_frame_len: AtomicUsize::new(0),
+        tbase: AtomicUsize::new(0),
+        dbase: AtomicUsize::new(0),
+    };
+
+    static SNAPSHOT: [Slot; SLOTS] = [EMPTY_SLOT; SLOTS];
+
+    // Must be called with the registry lock held.
+    pub fn insert(begin: usize, eh_frame_len: usize, tbase: usize, dbase: usize) {
+        if let Some(slot) = SNAPSHOT
+            .iter()
+            .find(|slot| slot.begin.load(Ordering::Relaxed) == 0)
+        {
+            slot.eh_frame_len.store(eh_frame_len, Ordering::Relaxed);
+            slot.tbase.store(tbase, Ordering::Relaxed);
+            slot.dbase.store(dbase, Ordering::Relaxed);
+            slot.begin.store(begin, Ordering::Release);
+        }
+    }
+
+    // Must be called with the registry lock held. Returns once no lookup reads the section.
+    pub fn remove(begin: usize) {
+        if let Some(slot) = SNAPSHOT
+            .iter()
+            .find(|slot| slot.begin.load(Ordering::Relaxed) == begin)
+        {
+            // Paired with `find_fde`, which announces itself as a reader before it reads `begin`.
+            slot.begin.store(0, Ordering::SeqCst);
+            while slot.readers.load(Ordering::SeqCst) != 0 {
+                core::hint::spin_loop();
+            }
+        }
+    }
+
+    pub fn find_fde(pc: usize) -> Option<FDESearchResult> {
+        SNAPSHOT.iter().find_map(|slot| {
+            slot.readers.fetch_add(1, Ordering::SeqCst);
+            let result = match slot.begin.load(Ordering::SeqCst) {
+                0 => None,
+                begin => search(slot, begin, pc),
+            };
+            slot.readers.fetch_sub(1, Ordering::Release);
+            result
+        })
+    }
+
+    fn search(slot: &Slot, begin: usize, pc: usize) -> Option<FDESearchResult> {
+        let eh_frame_len = slot.eh_frame_len.load(Ordering::Relaxed);
+        let bases = BaseAddresses::default()
+            .set_text(slot.tbase.load(Ordering::Relaxed) as _)
+            .set_got(slot.dbase.load(Ordering::Relaxed) as _)
+            .set_eh_frame(begin as _);
+        let eh_frame = EhFrame::new(unsafe { get_slice(begin, eh_frame_len) }, NativeEndian);
+        let fde = fde_for_address(&eh_frame, &bases, pc).ok()?;
+        Some(FDESearchResult {
+            fde,
+            bases,
+            section: eh_frame.into(),
+            image_end: None,
+        })
+    }
+}
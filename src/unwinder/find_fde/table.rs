@@ -0,0 +1,481 @@
+use super::eh_frame::fdes;
+use super::{fde_for_address, fde_from_offset, Fde};
+use crate::util::*;
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, vec::Vec};
+use gimli::{BaseAddresses, EhFrame, EhFrameOffset, NativeEndian};
+
+#[cfg(all(feature = "fde-custom", target_has_atomic = "ptr"))]
+pub use cache::{get, insert, remove};
+#[cfg(target_has_atomic = "ptr")]
+pub use cache::{set_fde_table_buffer, SetFdeTableBufferError};
+
+/// An entry of the binary search table built for an `.eh_frame` section.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FdeTableEntry {
+    initial_location: usize,
+    fde_offset: usize,
+}
+
+impl FdeTableEntry {
+    /// An empty entry, usable for initializing static buffers.
+    pub const EMPTY: Self = Self {
+        initial_location: 0,
+        fde_offset: 0,
+    };
+}
+
+#[cfg(any(target_has_atomic = "ptr", feature = "alloc"))]
+fn count_fdes(eh_frame: &EhFrame<StaticSlice>, bases: &BaseAddresses) -> Option<usize> {
+    let mut count = 0;
+    for fde in fdes(eh_frame, bases) {
+        fde.ok()?;
+        count += 1;
+    }
+    Some(count)
+}
+
+/// Fill `table` with the FDEs of `eh_frame` and sort it. Returns the number of entries used.
+#[cfg(any(target_has_atomic = "ptr", feature = "alloc"))]
+fn fill(
+    table: &mut [FdeTableEntry],
+    eh_frame: &EhFrame<StaticSlice>,
+    bases: &BaseAddresses,
+) -> Option<usize> {
+    let mut len = 0;
+    for fde in fdes(eh_frame, bases) {
+        let fde = fde.ok()?;
+        // Zero-sized FDEs never match any address, so there's no need to index them.
+        if fde.len() == 0 {
+            continue;
+        }
+        *table.get_mut(len)? = FdeTableEntry {
+            initial_location: fde.initial_address() as _,
+            fde_offset: fde.offset(),
+        };
+        len += 1;
+    }
+    table[..len].sort_unstable_by_key(|x| x.initial_location);
+    Some(len)
+}
+
+/// Build a heap-allocated search table for `eh_frame`.
+#[cfg(feature = "alloc")]
+#[cfg_attr(feature = "signal-safe", allow(dead_code))]
+pub fn build(
+    eh_frame: &EhFrame<StaticSlice>,
+    bases: &BaseAddresses,
+) -> Option<Box<[FdeTableEntry]>> {
+    let count = count_fdes(eh_frame, bases)?;
+    let mut table = Vec::new();
+    table.try_reserve_exact(count).ok()?;
+    table.resize(count, FdeTableEntry::EMPTY);
+    let len = fill(&mut table, eh_frame, bases)?;
+    table.truncate(len);
+    Some(table.into_boxed_slice())
+}
+
+/// Look up `pc` in a table previously built for `eh_frame`.
+pub fn lookup(
+    table: &[FdeTableEntry],
+    eh_frame: &EhFrame<StaticSlice>,
+    bases: &BaseAddresses,
+    pc: usize,
+) -> Option<Fde> {
+    let idx = table.partition_point(|x| x.initial_location <= pc);
+    let entry = table.get(idx.checked_sub(1)?)?;
+    let fde = fde_from_offset(eh_frame, bases, EhFrameOffset(entry.fde_offset)).ok()?;
+    if fde.contains(pc as _) {
+        Some(fde)
+    } else {
+        None
+    }
+}
+
+/// Find the FDE for `pc` in an `.eh_frame` section which is part of the program image, and return
+/// it along with the section.
+///
+/// A search table is built and cached on first use, along with the length of the section; if
+/// that's not possible, or the target lacks the atomics needed to share the cache, the section is
+/// searched linearly.
+///
+/// # Safety
+///
+/// The section must be a valid `.eh_frame` section which stays loaded.
+pub unsafe fn find_fde(
+    eh_frame_addr: usize,
+    end: EhFrameEnd,
+    bases: &BaseAddresses,
+    pc: usize,
+) -> Option<(EhFrame<StaticSlice>, Fde)> {
+    #[cfg(target_has_atomic = "ptr")]
+    if let Some(entry) = cache::get_or_build(eh_frame_addr, end, bases) {
+        return entry.find_fde(bases, pc);
+    }
+    let eh_frame = EhFrame::new(
+        unsafe { get_slice(eh_frame_addr, end.len(eh_frame_addr)?) },
+        NativeEndian,
+    );
+    let fde = fde_for_address(&eh_frame, bases, pc).ok()?;
+    Some((eh_frame, fde))
+}
+
+#[cfg(target_has_atomic = "ptr")]
+mod cache {
+    use super::*;
+    use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, AtomicUsize, Ordering};
+
+    static mut BUFFER: *mut FdeTableEntry = core::ptr::null_mut();
+    static BUFFER_LEN: AtomicUsize = AtomicUsize::new(0);
+    static BUFFER_USED: AtomicUsize = AtomicUsize::new(0);
+
+    static BUFFER_STATE: AtomicU32 = AtomicU32::new(UNINITIALIZED);
+
+    const UNINITIALIZED: u32 = 0;
+    const INITIALIZING: u32 = 1;
+    const INITIALIZED: u32 = 2;
+
+    /// The type returned by [`set_fde_table_buffer`] if [`set_fde_table_buffer`] has already been
+    /// called.
+    #[derive(Debug)]
+    pub struct SetFdeTableBufferError(());
+
+    /// Provides the storage used for FDE search tables.
+    ///
+    /// `.eh_frame` sections of the program image that come without a binary search table are indexed
+    /// on first use. When the `alloc` feature is enabled the tables are allocated on the heap if this
+    /// buffer is missing or exhausted; without `alloc`, or with `signal-safe`, this buffer is the only
+    /// storage available. Sections whose table does not fit are searched linearly.
+    ///
+    /// This function should only be called once during the lifetime of the program.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if this function has already been called during the lifetime of the
+    /// program.
+    pub fn set_fde_table_buffer(
+        buffer: &'static mut [FdeTableEntry],
+    ) -> Result<(), SetFdeTableBufferError> {
+        match BUFFER_STATE.compare_exchange(
+            UNINITIALIZED,
+            INITIALIZING,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        ) {
+            Ok(UNINITIALIZED) => {
+                unsafe {
+                    BUFFER = buffer.as_mut_ptr();
+                }
+                BUFFER_LEN.store(buffer.len(), Ordering::SeqCst);
+                BUFFER_STATE.store(INITIALIZED, Ordering::SeqCst);
+                Ok(())
+            }
+            Err(INITIALIZING) => {
+                while BUFFER_STATE.load(Ordering::SeqCst) == INITIALIZING {
+                    core::hint::spin_loop();
+                }
+                Err(SetFdeTableBufferError(()))
+            }
+            Err(INITIALIZED) => Err(SetFdeTableBufferError(())),
+            _ => {
+                unreachable!()
+            }
+        }
+    }
+
+    fn reserve_from_buffer(len: usize) -> Option<&'static mut [FdeTableEntry]> {
+        if BUFFER_STATE.load(Ordering::SeqCst) != INITIALIZED {
+            return None;
+        }
+        let cap = BUFFER_LEN.load(Ordering::SeqCst);
+        let start = BUFFER_USED
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                used.checked_add(len).filter(|&end| end <= cap)
+            })
+            .ok()?;
+        // SAFETY: each region of the buffer is handed out at most once.
+        Some(unsafe { core::slice::from_raw_parts_mut(BUFFER.add(start), len) })
+    }
+
+    fn build_in_buffer(
+        eh_frame: &EhFrame<StaticSlice>,
+        bases: &BaseAddresses,
+    ) -> Option<&'static [FdeTableEntry]> {
+        let count = count_fdes(eh_frame, bases)?;
+        let table = reserve_from_buffer(count)?;
+        let len = fill(table, eh_frame, bases)?;
+        Some(&table[..len])
+    }
+
+    struct CacheSlot {
+        // The address of the `.eh_frame` section, or zero if the slot is free.
+        eh_frame: AtomicUsize,
+        state: AtomicU32,
+        // Incremented each time the slot is claimed, so that a stale `remove` is told apart.
+        generation: AtomicUsize,
+        // Lookups using the slot, which `remove` waits for.
+        readers: AtomicUsize,
+        // Zero if the length of the section is unknown.
+        eh_frame_len: AtomicUsize,
+        table: AtomicPtr<FdeTableEntry>,
+        len: AtomicUsize,
+        // Whether the table was allocated on the heap rather than taken from the buffer.
+        boxed: AtomicBool,
+    }
+
+    const SLOT_BUILDING: u32 = 0;
+    const SLOT_READY: u32 = 1;
+    const SLOT_FAILED: u32 = 2;
+    #[cfg(feature = "fde-custom")]
+    const SLOT_RELEASING: u32 = 3;
+
+    const CACHE_SLOTS: usize = 16;
+
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_SLOT: CacheSlot = CacheSlot {
+        eh_frame: AtomicUsize::new(0),
+        state: AtomicU32::new(SLOT_BUILDING),
+        generation: AtomicUsize::new(0),
+        readers: AtomicUsize::new(0),
+        eh_frame_len: AtomicUsize::new(0),
+        table: AtomicPtr::new(core::ptr::null_mut()),
+        len: AtomicUsize::new(0),
+        boxed: AtomicBool::new(false),
+    };
+
+    // Tables keyed by the address of the `.eh_frame` section. Slots are claimed with a CAS, so
+    // lookups never block: a lookup that races with the thread building the table just does a
+    // linear search. Slots of sections in the program image are never released. Sections supplied
+    // at runtime, which may be freed and their address reused, are only cached while registered
+    // with `insert`.
+    static CACHE: [CacheSlot; CACHE_SLOTS] = [EMPTY_SLOT; CACHE_SLOTS];
+
+    /// A cached section, which can't be released while it is borrowed.
+    pub struct Entry {
+        slot: &'static CacheSlot,
+        eh_frame_addr: usize,
+        eh_frame_len: usize,
+        table: Option<&'static [FdeTableEntry]>,
+    }
+
+    impl Drop for Entry {
+        fn drop(&mut self) {
+            self.slot.readers.fetch_sub(1, Ordering::Release);
+        }
+    }
+
+    impl Entry {
+        /// Find the FDE for `pc` in the section, and return it along with the section.
+        pub fn find_fde(
+            &self,
+            bases: &BaseAddresses,
+            pc: usize,
+        ) -> Option<(EhFrame<StaticSlice>, Fde)> {
+            let eh_frame = EhFrame::new(
+                unsafe { get_slice(self.eh_frame_addr, self.eh_frame_len) },
+                NativeEndian,
+            );
+            let fde = match self.table {
+                Some(table) => lookup(table, &eh_frame, bases, pc),
+                None => fde_for_address(&eh_frame, bases, pc).ok(),
+            }?;
+            Some((eh_frame, fde))
+        }
+    }
+
+    fn acquire(slot: &'static CacheSlot, eh_frame_addr: usize) -> Option<Entry> {
+        // Paired with `remove`, which marks the slot as releasing before it checks for readers.
+        slot.readers.fetch_add(1, Ordering::SeqCst);
+        let mut entry = Entry {
+            slot,
+            eh_frame_addr,
+            eh_frame_len: 0,
+            table: None,
+        };
+        if slot.eh_frame.load(Ordering::SeqCst) != eh_frame_addr {
+            return None;
+        }
+        match slot.state.load(Ordering::SeqCst) {
+            SLOT_READY => {
+                let table = slot.table.load(Ordering::Relaxed);
+                let len = slot.len.load(Ordering::Relaxed);
+                entry.table = Some(unsafe { core::slice::from_raw_parts(table, len) });
+            }
+            // The table couldn't be built, but the length of the section is still known.
+            SLOT_FAILED => (),
+            _ => return None,
+        }
+        entry.eh_frame_len = slot.eh_frame_len.load(Ordering::Relaxed);
+        if entry.eh_frame_len == 0 {
+            return None;
+        }
+        Some(entry)
+    }
+
+    fn claim(eh_frame_addr: usize) -> Option<(usize, &'static CacheSlot)> {
+        CACHE.iter().enumerate().find(|(_, slot)| {
+            slot.eh_frame.load(Ordering::Relaxed) == 0
+                && slot
+                    .eh_frame
+                    .compare_exchange(0, eh_frame_addr, Ordering::AcqRel, Ordering::Relaxed)
+                    .is_ok()
+        })
+    }
+
+    // Build a table in the buffer, or on the heap. Also returns whether it is on the heap.
+    fn build_table(
+        eh_frame: &EhFrame<StaticSlice>,
+        bases: &BaseAddresses,
+    ) -> Option<(&'static [FdeTableEntry], bool)> {
+        if let Some(table) = build_in_buffer(eh_frame, bases) {
+            return Some((table, false));
+        }
+        // The allocator isn't async-signal-safe, so only the buffer is used in signal-safe mode.
+        #[cfg(all(feature = "alloc", not(feature = "signal-safe")))]
+        if let Some(table) = build(eh_frame, bases) {
+            return Some((Box::leak(table), true));
+        }
+        None
+    }
+
+    // Build the table for a claimed slot, and publish it.
+    fn fill_slot(
+        slot: &CacheSlot,
+        eh_frame: &EhFrame<StaticSlice>,
+        eh_frame_len: usize,
+        bases: &BaseAddresses,
+    ) -> bool {
+        slot.eh_frame_len.store(eh_frame_len, Ordering::Relaxed);
+        match build_table(eh_frame, bases) {
+            Some((table, boxed)) => {
+                slot.table
+                    .store(table.as_ptr() as *mut FdeTableEntry, Ordering::Relaxed);
+                slot.len.store(table.len(), Ordering::Relaxed);
+                slot.boxed.store(boxed, Ordering::Relaxed);
+                slot.state.store(SLOT_READY, Ordering::Release);
+                true
+            }
+            None => {
+                slot.state.store(SLOT_FAILED, Ordering::Release);
+                false
+            }
+        }
+    }
+
+    /// Borrow the cached section at `eh_frame_addr`, building its table on first use.
+    ///
+    /// Returns `None` if the section can't be cached, or another thread is building its table.
+    pub fn get_or_build(
+        eh_frame_addr: usize,
+        end: EhFrameEnd,
+        bases: &BaseAddresses,
+    ) -> Option<Entry> {
+        if let Some(slot) = CACHE
+            .iter()
+            .find(|slot| slot.eh_frame.load(Ordering::Acquire) == eh_frame_addr)
+        {
+            return acquire(slot, eh_frame_addr);
+        }
+        let (_, slot) = claim(eh_frame_addr)?;
+        slot.generation.fetch_add(1, Ordering::Relaxed);
+        match unsafe { end.len(eh_frame_addr) } {
+            Some(eh_frame_len) => {
+                let eh_frame = EhFrame::new(
+                    unsafe { get_slice(eh_frame_addr, eh_frame_len) },
+                    NativeEndian,
+                );
+                fill_slot(slot, &eh_frame, eh_frame_len, bases);
+            }
+            None => slot.state.store(SLOT_FAILED, Ordering::Release),
+        }
+        acquire(slot, eh_frame_addr)
+    }
+
+    /// Borrow the cached section at `eh_frame_addr`, if it has been registered with [`insert`].
+    #[cfg(feature = "fde-custom")]
+    pub fn get(eh_frame_addr: usize) -> Option<Entry> {
+        let slot = CACHE
+            .iter()
+            .find(|slot| slot.eh_frame.load(Ordering::Acquire) == eh_frame_addr)?;
+        acquire(slot, eh_frame_addr)
+    }
+
+    /// Build and cache a table for a section which may be unloaded later, until it is removed with
+    /// [`remove`]. Returns the slot used and its generation.
+    ///
+    /// # Safety
+    ///
+    /// `eh_frame` must be a valid `.eh_frame` section which stays loaded until it is removed.
+    #[cfg(feature = "fde-custom")]
+    pub unsafe fn insert(eh_frame: &'static [u8], bases: &BaseAddresses) -> Option<(usize, usize)> {
+        let eh_frame_addr = eh_frame.as_ptr() as usize;
+        if eh_frame.is_empty()
+            || CACHE
+                .iter()
+                .any(|slot| slot.eh_frame.load(Ordering::Acquire) == eh_frame_addr)
+        {
+            return None;
+        }
+        let (index, slot) = claim(eh_frame_addr)?;
+        let generation = slot.generation.fetch_add(1, Ordering::Relaxed) + 1;
+        let eh_frame_len = eh_frame.len();
+        let eh_frame = EhFrame::new(eh_frame, NativeEndian);
+        if fill_slot(slot, &eh_frame, eh_frame_len, bases) {
+            Some((index, generation))
+        } else {
+            release(slot);
+            None
+        }
+    }
+
+    /// Drop the table cached by [`insert`], once no lookup uses it anymore.
+    #[cfg(feature = "fde-custom")]
+    pub fn remove(index: usize, generation: usize) -> bool {
+        let Some(slot) = CACHE.get(index) else {
+            return false;
+        };
+        if slot.generation.load(Ordering::Relaxed) != generation
+            || slot
+                .state
+                .compare_exchange(
+                    SLOT_READY,
+                    SLOT_RELEASING,
+                    Ordering::SeqCst,
+                    Ordering::Relaxed,
+                )
+                .is_err()
+        {
+            return false;
+        }
+        // The slot may have been released and claimed again before it was marked as releasing.
+        if slot.generation.load(Ordering::Relaxed) != generation {
+            slot.state.store(SLOT_READY, Ordering::Release);
+            return false;
+        }
+        while slot.readers.load(Ordering::SeqCst) != 0 {
+            core::hint::spin_loop();
+        }
+        release(slot);
+        true
+    }
+
+    #[cfg(feature = "fde-custom")]
+    fn release(slot: &CacheSlot) {
+        // Tables taken from the buffer can't be returned to it.
+        #[cfg(feature = "alloc")]
+        if slot.boxed.load(Ordering::Relaxed) {
+            let table = core::ptr::slice_from_raw_parts_mut(
+                slot.table.load(Ordering::Relaxed),
+                slot.len.load(Ordering::Relaxed),
+            );
+            drop(unsafe { Box::from_raw(table) });
+        }
+        slot.table.store(core::ptr::null_mut(), Ordering::Relaxed);
+        slot.len.store(0, Ordering::Relaxed);
+        slot.boxed.store(false, Ordering::Relaxed);
+        slot.eh_frame_len.store(0, Ordering::Relaxed);
+        slot.state.store(SLOT_BUILDING, Ordering::Relaxed);
+        slot.eh_frame.store(0, Ordering::Release);
+    }
+}
@@ -1,18 +1,30 @@
-use gimli::{
-    BaseAddresses, CfaRule, Register, RegisterRule, UnwindContext, UnwindExpression, UnwindTableRow,
-};
+use gimli::{BaseAddresses, CfaRule, RegisterRule, UnwindExpression};
 #[cfg(feature = "dwarf-expr")]
 use gimli::{Evaluation, EvaluationResult, Location, Value};
+#[cfg(not(feature = "cfi-minimal"))]
+use gimli::{Register, UnwindContext, UnwindTableRow};
 
 use super::arch::*;
+#[cfg(feature = "cfi-minimal")]
+use super::cfi::{self, Row};
+#[cfg(feature = "fde-compact")]
+use super::compact::{self, CompactFrame};
+#[cfg(any(not(feature = "cfi-minimal"), feature = "dwarf-expr"))]
+use super::find_fde::FrameSection;
 use super::find_fde::{self, FDEFinder, FDESearchResult};
+#[cfg(feature = "frame-stepper")]
+use super::stepper::{self, Step};
+#[cfg(feature = "trap-frame")]
+use super::trap::{self, TrapFrame};
 use crate::abi::PersonalityRoutine;
 use crate::arch::*;
 use crate::util::*;
 
+#[cfg(any(not(feature = "cfi-minimal"), feature = "dwarf-expr"))]
 struct StoreOnStack;
 
 // gimli's MSRV doesn't allow const generics, so we need to pick a supported array size.
+#[cfg(not(feature = "cfi-minimal"))]
 const fn next_value(x: usize) -> usize {
     let supported = [0, 1, 2, 3, 4, 8, 16, 32, 64, 128];
     let mut i = 0;
@@ -25,11 +37,15 @@ const fn next_value(x: usize) -> usize {
     192
 }
 
+#[cfg(not(feature = "cfi-minimal"))]
 impl<O: gimli::ReaderOffset> gimli::UnwindContextStorage<O> for StoreOnStack {
     type Rules = [(Register, RegisterRule<O>); next_value(MAX_REG_RULES)];
     type Stack = [UnwindTableRow<O, Self>; 2];
 }
 
+#[cfg(not(feature = "cfi-minimal"))]
+type Row = UnwindTableRow<usize, StoreOnStack>;
+
 #[cfg(feature = "dwarf-expr")]
 impl<R: gimli::Reader> gimli::EvaluationStorage<R> for StoreOnStack {
     type Stack = [Value; 64];
@@ -37,14 +53,53 @@ impl<R: gimli::Reader> gimli::EvaluationStorage<R> for StoreOnStack {
     type Result = [gimli::Piece<R>; 1];
 }
 
+/// Whether a frame was interrupted, rather than having called the frame below it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Interruption {
+    #[default]
+    None,
+    /// Interrupted by a signal, as described by the unwind information of the signal trampoline.
+    Signal,
+    /// Interrupted by a trap. `ra` is the value of the return address register in the interrupted
+    /// code, if it is a general purpose register and so couldn't also hold the PC.
+    #[cfg(feature = "trap-frame")]
+    Trap { ra: Option<usize> },
+}
+
+impl Interruption {
+    pub fn is_interrupted(self) -> bool {
+        self != Interruption::None
+    }
+
+    /// The value of the return address register in the interrupted code, if it doesn't hold the
+    /// PC.
+    #[cfg_attr(
+        not(any(feature = "trap-frame", feature = "fde-compact")),
+        allow(dead_code)
+    )]
+    pub fn ra(self) -> Option<usize> {
+        #[cfg(feature = "trap-frame")]
+        if let Interruption::Trap { ra } = self {
+            return ra;
+        }
+        None
+    }
+}
+
 #[derive(Debug)]
-pub struct Frame {
-    fde_result: FDESearchResult,
-    row: UnwindTableRow<usize, StoreOnStack>,
+#[allow(clippy::large_enum_variant)]
+pub enum Frame {
+    Dwarf(DwarfFrame),
+    #[cfg(feature = "fde-compact")]
+    Compact(CompactFrame),
+    #[cfg(feature = "frame-stepper")]
+    Stepped(Step),
+    #[cfg(feature = "trap-frame")]
+    Trap(TrapFrame),
 }
 
 impl Frame {
-    pub fn from_context(ctx: &Context, signal: bool) -> Result<Option<Self>, gimli::Error> {
+    pub fn from_context(ctx: &Context, signal: Interruption) -> Result<Option<Self>, gimli::Error> {
         let mut ra = ctx[Arch::RA];
 
         // Reached end of stack
@@ -53,37 +108,258 @@ impl Frame {
         }
 
         // RA points to the *next* instruction, so move it back 1 byte for the call instruction.
-        if !signal {
+        if !signal.is_interrupted() {
             ra -= 1;
         }
 
+        #[cfg(feature = "trap-frame")]
+        if let Some(frame) = trap::find_trap_frame(ctx, ra) {
+            return Ok(Some(Frame::Trap(frame)));
+        }
+
+        #[cfg(feature = "frame-stepper")]
+        if let Some(stepper) = stepper::find_stepper(ra) {
+            return Ok(stepper.step(ctx).map(Frame::Stepped));
+        }
+
+        #[cfg(feature = "fde-compact")]
+        if let Some(frame) = compact::find_compact_frame(ra, signal.ra()) {
+            return Ok(Some(Frame::Compact(frame)));
+        }
+
+        Ok(DwarfFrame::new(ra, signal)?.map(Frame::Dwarf))
+    }
+
+    pub fn adjust_stack_for_args(&self, ctx: &mut Context) {
+        match self {
+            Frame::Dwarf(frame) => frame.adjust_stack_for_args(ctx),
+            #[cfg(feature = "fde-compact")]
+            Frame::Compact(_) => (),
+            #[cfg(feature = "frame-stepper")]
+            Frame::Stepped(_) => (),
+            #[cfg(feature = "trap-frame")]
+            Frame::Trap(_) => (),
+        }
+    }
+
+    pub fn unwind(&self, ctx: &Context) -> Result<Context, gimli::Error> {
+        #[allow(unused_mut)]
+        let mut caller = match self {
+            Frame::Dwarf(frame) => frame.unwind(ctx)?,
+            #[cfg(feature = "fde-compact")]
+            Frame::Compact(frame) => frame.unwind(ctx),
+            #[cfg(feature = "frame-stepper")]
+            Frame::Stepped(step) => step.caller.clone(),
+            #[cfg(feature = "trap-frame")]
+            Frame::Trap(frame) => frame.caller.clone(),
+        };
+
+        // Each frame has one entry on the shadow stack: the return address pushed by its call, or
+        // the restore token the kernel pushes for a signal frame.
+        #[cfg(target_arch = "x86_64")]
+        if ctx.ssp != 0 {
+            caller.ssp = ctx.ssp + 8;
+        }
+
+        Ok(caller)
+    }
+
+    pub fn bases(&self) -> Option<&BaseAddresses> {
+        match self {
+            Frame::Dwarf(frame) => Some(&frame.fde_result.bases),
+            #[cfg(feature = "fde-compact")]
+            Frame::Compact(_) => None,
+            #[cfg(feature = "frame-stepper")]
+            Frame::Stepped(_) => None,
+            #[cfg(feature = "trap-frame")]
+            Frame::Trap(_) => None,
+        }
+    }
+
+    pub fn personality(&self) -> Option<PersonalityRoutine> {
+        match self {
+            Frame::Dwarf(frame) => frame.personality(),
+            #[cfg(feature = "fde-compact")]
+            Frame::Compact(_) => None,
+            #[cfg(feature = "frame-stepper")]
+            Frame::Stepped(step) => step.personality,
+            #[cfg(feature = "trap-frame")]
+            Frame::Trap(_) => None,
+        }
+    }
+
+    pub fn lsda(&self) -> usize {
+        match self {
+            Frame::Dwarf(frame) => frame.lsda(),
+            #[cfg(feature = "fde-compact")]
+            Frame::Compact(_) => 0,
+            #[cfg(feature = "frame-stepper")]
+            Frame::Stepped(step) => step.lsda,
+            #[cfg(feature = "trap-frame")]
+            Frame::Trap(_) => 0,
+        }
+    }
+
+    /// The end of the loaded image the frame's unwind information belongs to, if known.
+    #[cfg(any(feature = "personality", feature = "personality-gcc"))]
+    pub fn image_end(&self) -> Option<usize> {
+        match self {
+            Frame::Dwarf(frame) => frame.fde_result.image_end,
+            #[cfg(feature = "fde-compact")]
+            Frame::Compact(_) => None,
+            #[cfg(feature = "frame-stepper")]
+            Frame::Stepped(_) => None,
+            #[cfg(feature = "trap-frame")]
+            Frame::Trap(_) => None,
+        }
+    }
+
+    pub fn initial_address(&self) -> usize {
+        match self {
+            Frame::Dwarf(frame) => frame.initial_address(),
+            #[cfg(feature = "fde-compact")]
+            Frame::Compact(frame) => frame.function_start,
+            #[cfg(feature = "frame-stepper")]
+            Frame::Stepped(step) => step.function_start,
+            #[cfg(feature = "trap-frame")]
+            Frame::Trap(frame) => frame.entry,
+        }
+    }
+
+    /// How the caller of this frame was left.
+    pub fn caller_interruption(&self) -> Interruption {
+        match self {
+            Frame::Dwarf(frame) if frame.fde_result.fde.is_signal_trampoline() => {
+                Interruption::Signal
+            }
+            Frame::Dwarf(_) => Interruption::None,
+            #[cfg(feature = "fde-compact")]
+            Frame::Compact(_) => Interruption::None,
+            #[cfg(feature = "frame-stepper")]
+            Frame::Stepped(_) => Interruption::None,
+            #[cfg(feature = "trap-frame")]
+            Frame::Trap(frame) => Interruption::Trap { ra: frame.ra },
+        }
+    }
+}
+
+/// Reads the value of `reg` saved at `addr`.
+#[cfg_attr(
+    not(any(
+        target_arch = "powerpc64",
+        target_arch = "s390x",
+        all(
+            target_arch = "riscv64",
+            target_feature = "f",
+            not(target_feature = "d")
+        )
+    )),
+    allow(unused_variables)
+)]
+unsafe fn load_register(reg: gimli::Register, addr: usize) -> usize {
+    // Some registers are saved as a 32-bit word, which isn't the low half of a `usize` read from
+    // there on big-endian targets, and may share that `usize` with another register otherwise.
+    #[cfg(any(
+        target_arch = "powerpc64",
+        target_arch = "s390x",
+        all(
+            target_arch = "riscv64",
+            target_feature = "f",
+            not(target_feature = "d")
+        )
+    ))]
+    if is_word_register(reg) {
+        return unsafe { *(addr as *const u32) } as usize;
+    }
+    unsafe { *(addr as *const usize) }
+}
+
+/// A frame described by DWARF CFI.
+#[derive(Debug)]
+pub struct DwarfFrame {
+    fde_result: FDESearchResult,
+    row: Row,
+    // The value of the return address register, if it is not the PC the frame was looked up by.
+    #[cfg(feature = "trap-frame")]
+    ra: Option<usize>,
+}
+
+impl DwarfFrame {
+    #[cfg_attr(not(feature = "trap-frame"), allow(unused_variables))]
+    fn new(ra: usize, signal: Interruption) -> Result<Option<Self>, gimli::Error> {
         let fde_result = match find_fde::get_finder().find_fde(ra as _) {
             Some(v) => v,
             None => return Ok(None),
         };
+        let ra = ra.wrapping_sub(fde_result.section.bias());
+        #[cfg(feature = "cfi-minimal")]
+        let row = cfi::find_row(&fde_result, ra)?;
+        #[cfg(not(feature = "cfi-minimal"))]
         let mut unwinder = UnwindContext::<_, StoreOnStack>::new_in();
-        let row = fde_result
-            .fde
-            .unwind_info_for_address(
-                &fde_result.eh_frame,
-                &fde_result.bases,
-                &mut unwinder,
-                ra as _,
-            )?
-            .clone();
+        #[cfg(not(feature = "cfi-minimal"))]
+        let row = match &fde_result.section {
+            FrameSection::EhFrame(eh_frame) => {
+                #[cfg(target_arch = "aarch64")]
+                let eh_frame = &{
+                    let mut eh_frame = *eh_frame;
+                    eh_frame.set_vendor(gimli::Vendor::AArch64);
+                    eh_frame
+                };
+                fde_result
+                    .fde
+                    .unwind_info_for_address(eh_frame, &fde_result.bases, &mut unwinder, ra as _)?
+                    .clone()
+            }
+            #[cfg(feature = "fde-debug-frame")]
+            FrameSection::DebugFrame { debug_frame, .. } => {
+                #[cfg(target_arch = "aarch64")]
+                let debug_frame = &{
+                    let mut debug_frame = *debug_frame;
+                    debug_frame.set_vendor(gimli::Vendor::AArch64);
+                    debug_frame
+                };
+                fde_result
+                    .fde
+                    .unwind_info_for_address(
+                        debug_frame,
+                        &fde_result.bases,
+                        &mut unwinder,
+                        ra as _,
+                    )?
+                    .clone()
+            }
+        };
 
-        Ok(Some(Self { fde_result, row }))
+        Ok(Some(Self {
+            fde_result,
+            row,
+            #[cfg(feature = "trap-frame")]
+            ra: signal.ra(),
+        }))
     }
 
+    /// Evaluates a DWARF expression. `cfa` is pushed onto the stack first for register rules.
     #[cfg(feature = "dwarf-expr")]
     fn evaluate_expression(
         &self,
         ctx: &Context,
         expr: UnwindExpression<usize>,
+        cfa: Option<usize>,
     ) -> Result<usize, gimli::Error> {
-        let expr = expr.get(&self.fde_result.eh_frame).unwrap();
-        let mut eval =
-            Evaluation::<_, StoreOnStack>::new_in(expr.0, self.fde_result.fde.cie().encoding());
+        let expr = match &self.fde_result.section {
+            FrameSection::EhFrame(eh_frame) => expr.get(eh_frame),
+            #[cfg(feature = "fde-debug-frame")]
+            FrameSection::DebugFrame { debug_frame, .. } => expr.get(debug_frame),
+        }
+        .unwrap();
+        #[cfg(not(feature = "cfi-minimal"))]
+        let encoding = self.fde_result.fde.cie().encoding();
+        #[cfg(feature = "cfi-minimal")]
+        let encoding = self.fde_result.fde.encoding();
+        let mut eval = Evaluation::<_, StoreOnStack>::new_in(expr.0, encoding);
+        if let Some(cfa) = cfa {
+            eval.set_initial_value(cfa as _);
+        }
         let mut result = eval.evaluate()?;
         loop {
             match result {
@@ -94,6 +370,14 @@ impl Frame {
                 }
                 EvaluationResult::RequiresRegister { register, .. } => {
                     let value = ctx[register];
+                    // Without SVE there is no vector length to offset by.
+                    #[cfg(target_arch = "aarch64")]
+                    let value = if register == gimli::AArch64::VG {
+                        ctx.vg()
+                            .ok_or(gimli::Error::UnsupportedRegister(register.0 as _))?
+                    } else {
+                        value
+                    };
                     result = eval.resume_with_register(Value::Generic(value as _))?;
                 }
                 EvaluationResult::RequiresRelocatedAddress(address) => {
@@ -122,16 +406,32 @@ impl Frame {
         &self,
         _ctx: &Context,
         _expr: UnwindExpression<usize>,
+        _cfa: Option<usize>,
     ) -> Result<usize, gimli::Error> {
         Err(gimli::Error::UnsupportedEvaluation)
     }
 
-    pub fn adjust_stack_for_args(&self, ctx: &mut Context) {
+    fn adjust_stack_for_args(&self, ctx: &mut Context) {
         let size = self.row.saved_args_size();
         ctx[Arch::SP] = ctx[Arch::SP].wrapping_add(size as usize);
     }
 
-    pub fn unwind(&self, ctx: &Context) -> Result<Context, gimli::Error> {
+    fn unwind(&self, ctx: &Context) -> Result<Context, gimli::Error> {
+        #[cfg(feature = "trap-frame")]
+        let trapped;
+        #[cfg(feature = "trap-frame")]
+        let ctx = match self.ra {
+            Some(ra) => {
+                trapped = {
+                    let mut ctx = ctx.clone();
+                    ctx[Arch::RA] = ra;
+                    ctx
+                };
+                &trapped
+            }
+            None => ctx,
+        };
+
         let row = &self.row;
         let mut new_ctx = ctx.clone();
 
@@ -139,26 +439,88 @@ impl Frame {
             CfaRule::RegisterAndOffset { register, offset } => {
                 ctx[register].wrapping_add(offset as usize)
             }
-            CfaRule::Expression(expr) => self.evaluate_expression(ctx, expr)?,
+            CfaRule::Expression(expr) => self.evaluate_expression(ctx, expr, None)?,
         };
 
         new_ctx[Arch::SP] = cfa as _;
         new_ctx[Arch::RA] = 0;
+        // A trapped frame may not have saved its return address yet, so without a rule it keeps
+        // the value it had.
+        #[cfg(feature = "trap-frame")]
+        if let Some(ra) = self.ra {
+            new_ctx[Arch::RA] = ra;
+        }
 
         #[warn(non_exhaustive_omitted_patterns)]
         for (reg, rule) in row.registers() {
+            // Whether the return address is signed is a property of the frame rather than a
+            // register to restore.
+            #[cfg(target_arch = "aarch64")]
+            if *reg == gimli::AArch64::RA_SIGN_STATE {
+                continue;
+            }
+            // Vector registers are too wide for the rules below.
+            #[cfg(all(target_arch = "powerpc64", target_feature = "altivec"))]
+            if let Some(vr) = new_ctx.vr_mut(*reg) {
+                *vr = match *rule {
+                    RegisterRule::Undefined | RegisterRule::SameValue => continue,
+                    RegisterRule::Offset(offset) => unsafe {
+                        (cfa.wrapping_add(offset as usize) as *const u128).read_unaligned()
+                    },
+                    RegisterRule::Expression(expr) => {
+                        let addr = self.evaluate_expression(ctx, expr, Some(cfa))?;
+                        unsafe { (addr as *const u128).read_unaligned() }
+                    }
+                    RegisterRule::ValOffset(_)
+                    | RegisterRule::Register(_)
+                    | RegisterRule::ValExpression(_)
+                    | RegisterRule::Architectural
+                    | RegisterRule::Constant(_) => {
+                        return Err(gimli::Error::UnsupportedRegister(reg.0 as _))
+                    }
+                    _ => unreachable!(),
+                };
+                continue;
+            }
+            // Registers saved by CFI are 64 bits wide on x32, so they are restored in full.
+            #[cfg(all(target_arch = "x86_64", target_pointer_width = "32"))]
+            {
+                let value = match *rule {
+                    RegisterRule::Offset(offset) => unsafe {
+                        Some(*(cfa.wrapping_add(offset as usize) as *const u64))
+                    },
+                    RegisterRule::Register(r) => Some(*ctx.wide(r)),
+                    RegisterRule::Expression(expr) => {
+                        let addr = self.evaluate_expression(ctx, expr, Some(cfa))?;
+                        Some(unsafe { *(addr as *const u64) })
+                    }
+                    RegisterRule::Undefined
+                    | RegisterRule::SameValue
+                    | RegisterRule::ValOffset(_)
+                    | RegisterRule::ValExpression(_)
+                    | RegisterRule::Architectural
+                    | RegisterRule::Constant(_) => None,
+                    _ => unreachable!(),
+                };
+                if let Some(value) = value {
+                    *new_ctx.wide_mut(*reg) = value;
+                    continue;
+                }
+            }
             let value = match *rule {
                 RegisterRule::Undefined | RegisterRule::SameValue => ctx[*reg],
                 RegisterRule::Offset(offset) => unsafe {
-                    *((cfa.wrapping_add(offset as usize)) as *const usize)
+                    load_register(*reg, cfa.wrapping_add(offset as usize))
                 },
                 RegisterRule::ValOffset(offset) => cfa.wrapping_add(offset as usize),
                 RegisterRule::Register(r) => ctx[r],
                 RegisterRule::Expression(expr) => {
-                    let addr = self.evaluate_expression(ctx, expr)?;
-                    unsafe { *(addr as *const usize) }
+                    let addr = self.evaluate_expression(ctx, expr, Some(cfa))?;
+                    unsafe { load_register(*reg, addr) }
+                }
+                RegisterRule::ValExpression(expr) => {
+                    self.evaluate_expression(ctx, expr, Some(cfa))?
                 }
-                RegisterRule::ValExpression(expr) => self.evaluate_expression(ctx, expr)?,
                 RegisterRule::Architectural => unreachable!(),
                 RegisterRule::Constant(value) => value as usize,
                 _ => unreachable!(),
@@ -166,14 +528,31 @@ impl Frame {
             new_ctx[*reg] = value;
         }
 
+        #[cfg(target_arch = "aarch64")]
+        if self.ra_signed() {
+            new_ctx[Arch::RA] = strip_return_address(new_ctx[Arch::RA]);
+        }
+
+        // The TOC pointer is only described by the CFI of frames that save it themselves.
+        #[cfg(target_arch = "powerpc64")]
+        if !row.registers().any(|(reg, _)| *reg == gimli::PowerPc64::R2) {
+            restore_toc(&mut new_ctx);
+        }
+
         Ok(new_ctx)
     }
 
-    pub fn bases(&self) -> &BaseAddresses {
-        &self.fde_result.bases
+    /// Whether the return address saved by this frame is signed with pointer authentication, as
+    /// tracked by `DW_CFA_AARCH64_negate_ra_state`.
+    #[cfg(target_arch = "aarch64")]
+    fn ra_signed(&self) -> bool {
+        self.row.registers().any(|(reg, rule)| {
+            *reg == gimli::AArch64::RA_SIGN_STATE
+                && matches!(*rule, RegisterRule::Constant(state) if state & 1 != 0)
+        })
     }
 
-    pub fn personality(&self) -> Option<PersonalityRoutine> {
+    fn personality(&self) -> Option<PersonalityRoutine> {
         self.fde_result
             .fde
             .personality()
@@ -181,7 +560,7 @@ impl Frame {
             .map(|x| unsafe { core::mem::transmute(x) })
     }
 
-    pub fn lsda(&self) -> usize {
+    fn lsda(&self) -> usize {
         self.fde_result
             .fde
             .lsda()
@@ -189,11 +568,8 @@ impl Frame {
             .unwrap_or(0)
     }
 
-    pub fn initial_address(&self) -> usize {
-        self.fde_result.fde.initial_address() as _
-    }
-
-    pub fn is_signal_trampoline(&self) -> bool {
-        self.fde_result.fde.is_signal_trampoline()
+    fn initial_address(&self) -> usize {
+        (self.fde_result.fde.initial_address() as usize)
+            .wrapping_add(self.fde_result.section.bias())
     }
 }
@@ -1,6 +1,66 @@
 mod arch;
+#[cfg(feature = "cfi-minimal")]
+// Without a finder, no FDE is ever parsed.
+#[cfg_attr(
+    not(any(
+        feature = "fde-phdr",
+        feature = "fde-registry",
+        feature = "fde-static",
+        feature = "fde-static-regions",
+        feature = "fde-gnu-eh-frame-hdr",
+        feature = "fde-custom",
+        feature = "fde-debug-frame"
+    )),
+    allow(dead_code)
+)]
+mod cfi;
+#[cfg(feature = "fde-compact")]
+mod compact;
+#[cfg(all(
+    target_has_atomic = "ptr",
+    any(
+        feature = "fde-custom",
+        feature = "frame-stepper",
+        feature = "stack-switch",
+        feature = "trap-frame"
+    )
+))]
+mod double_buffer;
 mod find_fde;
 mod frame;
+#[cfg(any(
+    all(
+        target_arch = "x86_64",
+        target_pointer_width = "64",
+        target_os = "linux",
+        feature = "libc"
+    ),
+    all(
+        target_arch = "aarch64",
+        any(target_os = "linux", target_os = "android"),
+        feature = "shadow-call-stack"
+    )
+))]
+pub mod shadow_stack;
+#[cfg(feature = "stack-switch")]
+mod stack;
+#[cfg(feature = "frame-stepper")]
+mod stepper;
+#[cfg(feature = "trap-frame")]
+mod trap;
+
+// The registrations these features keep are updated with compare-and-swap.
+#[cfg(all(
+    any(
+        feature = "frame-stepper",
+        feature = "stack-switch",
+        feature = "trap-frame"
+    ),
+    not(target_has_atomic = "ptr")
+))]
+compile_error!(
+    "\"frame-stepper\", \"stack-switch\" and \"trap-frame\" require pointer-sized atomics."
+);
 
 use core::ffi::c_void;
 use core::ptr;
@@ -11,10 +71,57 @@ use crate::arch::*;
 use crate::util::*;
 use arch::*;
 use find_fde::FDEFinder;
-use frame::Frame;
+use frame::{Frame, Interruption};
 
+#[cfg(feature = "fde-compact")]
+pub use compact::{set_compact_unwind_table, SetCompactUnwindTableError};
 #[cfg(feature = "fde-custom")]
 pub use find_fde::custom_eh_frame_finder;
+#[cfg(all(feature = "fde-custom", target_has_atomic = "ptr"))]
+pub use find_fde::fde_finder;
+#[cfg(all(
+    target_has_atomic = "ptr",
+    any(
+        feature = "fde-static",
+        feature = "fde-static-regions",
+        feature = "fde-gnu-eh-frame-hdr"
+    )
+))]
+pub use find_fde::fde_table;
+#[cfg(all(feature = "fde-phdr-dl", feature = "signal-safe"))]
+pub use find_fde::phdr_snapshot;
+#[cfg(feature = "fde-static-regions")]
+pub use find_fde::static_regions;
+
+#[cfg(feature = "frame-stepper")]
+pub mod frame_stepper {
+    pub use super::arch::Context;
+    pub use super::stepper::{
+        register_frame_stepper, unregister_frame_stepper, FrameStepper, FrameStepperId,
+        RegisterFrameStepperError, Step, MAX_FRAME_STEPPERS,
+    };
+    pub use crate::arch::Arch;
+}
+
+#[cfg(feature = "trap-frame")]
+pub mod trap_frame {
+    pub use super::trap::{
+        register_trap_entry, unregister_trap_entry, RegisterTrapEntryError, TrapEntryId,
+        TrapFrameLayout, MAX_TRAP_ENTRIES,
+    };
+    pub use crate::arch::Arch;
+    pub use gimli::Register;
+}
+
+#[cfg(feature = "stack-switch")]
+pub mod stack_switch {
+    pub use super::arch::Context;
+    pub use super::stack::{
+        register_stack_switch, unregister_stack_switch, RegisterStackSwitchError, StackSwitch,
+        StackSwitchId, MAX_STACK_SWITCHES,
+    };
+    pub use crate::arch::Arch;
+}
 
 // Helper function to turn `save_context` which takes function pointer to a closure-taking function.
 fn with_context<T, F: FnOnce(&mut Context) -> T>(f: F) -> T {
@@ -58,6 +165,14 @@ pub struct UnwindContext<'a> {
     signal: bool,
 }
 
+#[cfg(any(feature = "personality", feature = "personality-gcc"))]
+impl UnwindContext<'_> {
+    /// The end of the loaded image the frame's unwind information was found in, if known.
+    pub(crate) fn image_end(&self) -> Option<usize> {
+        self.frame.and_then(|f| f.image_end())
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn _Unwind_GetGR(unwind_ctx: &UnwindContext<'_>, index: c_int) -> usize {
     unwind_ctx.ctx[Register(index as u16)]
@@ -109,7 +224,8 @@ pub extern "C" fn _Unwind_GetRegionStart(unwind_ctx: &UnwindContext<'_>) -> usiz
 pub extern "C" fn _Unwind_GetTextRelBase(unwind_ctx: &UnwindContext<'_>) -> usize {
     unwind_ctx
         .frame
-        .map(|f| f.bases().eh_frame.text.unwrap() as _)
+        .and_then(|f| f.bases())
+        .map(|bases| bases.eh_frame.text.unwrap() as _)
         .unwrap_or(0)
 }
 
@@ -117,7 +233,8 @@ pub extern "C" fn _Unwind_GetTextRelBase(unwind_ctx: &UnwindContext<'_>) -> usiz
 pub extern "C" fn _Unwind_GetDataRelBase(unwind_ctx: &UnwindContext<'_>) -> usize {
     unwind_ctx
         .frame
-        .map(|f| f.bases().eh_frame.data.unwrap() as _)
+        .and_then(|f| f.bases())
+        .map(|bases| bases.eh_frame.data.unwrap() as _)
         .unwrap_or(0)
 }
 
@@ -125,7 +242,7 @@ pub extern "C" fn _Unwind_GetDataRelBase(unwind_ctx: &UnwindContext<'_>) -> usiz
 pub extern "C" fn _Unwind_FindEnclosingFunction(pc: *mut c_void) -> *mut c_void {
     find_fde::get_finder()
         .find_fde(pc as usize - 1)
-        .map(|r| r.fde.initial_address() as usize as _)
+        .map(|r| (r.fde.initial_address() as usize).wrapping_add(r.section.bias()) as _)
         .unwrap_or(ptr::null_mut())
 }
 
@@ -147,6 +264,36 @@ macro_rules! try2 {
     }};
 }
 
+// Find the frame for `ctx`. At the end of a stack that was switched to from another one, carry on
+// with the stack it was entered from.
+#[cfg(feature = "stack-switch")]
+fn next_frame(
+    ctx: &mut Context,
+    signal: &mut Interruption,
+    exception: bool,
+) -> Result<Option<Frame>, gimli::Error> {
+    loop {
+        let frame = Frame::from_context(ctx, *signal)?;
+        if frame.is_none() {
+            if let Some(parent) = stack::parent_context(ctx, exception) {
+                *ctx = parent;
+                *signal = Interruption::None;
+                continue;
+            }
+        }
+        return Ok(frame);
+    }
+}
+
+#[cfg(not(feature = "stack-switch"))]
+fn next_frame(
+    ctx: &mut Context,
+    signal: &mut Interruption,
+    _exception: bool,
+) -> Result<Option<Frame>, gimli::Error> {
+    Frame::from_context(ctx, *signal)
+}
+
 #[inline(never)]
 #[no_mangle]
 pub unsafe extern "C-unwind" fn _Unwind_RaiseException(
@@ -155,9 +302,9 @@ pub unsafe extern "C-unwind" fn _Unwind_RaiseException(
     with_context(|saved_ctx| {
         // Phase 1: Search for handler
         let mut ctx = saved_ctx.clone();
-        let mut signal = false;
+        let mut signal = Interruption::None;
         loop {
-            if let Some(frame) = try1!(Frame::from_context(&ctx, signal)) {
+            if let Some(frame) = try1!(next_frame(&mut ctx, &mut signal, true)) {
                 if let Some(personality) = frame.personality() {
                     let result = unsafe {
                         personality(
@@ -168,7 +315,7 @@ pub unsafe extern "C-unwind" fn _Unwind_RaiseException(
                             &mut UnwindContext {
                                 frame: Some(&frame),
                                 ctx: &mut ctx,
-                                signal,
+                                signal: signal.is_interrupted(),
                             },
                         )
                     };
@@ -183,14 +330,14 @@ pub unsafe extern "C-unwind" fn _Unwind_RaiseException(
                 }
 
                 ctx = try1!(frame.unwind(&ctx));
-                signal = frame.is_signal_trampoline();
+                signal = frame.caller_interruption();
             } else {
                 return UnwindReasonCode::END_OF_STACK;
             }
         }
 
         // Disambiguate normal frame and signal frame.
-        let handler_cfa = ctx[Arch::SP] - signal as usize;
+        let handler_cfa = ctx[Arch::SP] - signal.is_interrupted() as usize;
         unsafe {
             (*exception).private_1 = None;
             (*exception).private_2 = handler_cfa;
@@ -209,10 +356,10 @@ fn raise_exception_phase2(
     ctx: &mut Context,
     handler_cfa: usize,
 ) -> UnwindReasonCode {
-    let mut signal = false;
+    let mut signal = Interruption::None;
     loop {
-        if let Some(frame) = try2!(Frame::from_context(ctx, signal)) {
-            let frame_cfa = ctx[Arch::SP] - signal as usize;
+        if let Some(frame) = try2!(next_frame(ctx, &mut signal, true)) {
+            let frame_cfa = ctx[Arch::SP] - signal.is_interrupted() as usize;
             if let Some(personality) = frame.personality() {
                 let code = unsafe {
                     personality(
@@ -228,7 +375,7 @@ fn raise_exception_phase2(
                         &mut UnwindContext {
                             frame: Some(&frame),
                             ctx,
-                            signal,
+                            signal: signal.is_interrupted(),
                         },
                     )
                 };
@@ -244,7 +391,7 @@ fn raise_exception_phase2(
             }
 
             *ctx = try2!(frame.unwind(ctx));
-            signal = frame.is_signal_trampoline();
+            signal = frame.caller_interruption();
         } else {
             return UnwindReasonCode::FATAL_PHASE2_ERROR;
         }
@@ -278,15 +425,17 @@ fn force_unwind_phase2(
     stop: UnwindStopFn,
     stop_arg: *mut c_void,
 ) -> UnwindReasonCode {
-    let mut signal = false;
+    let mut signal = Interruption::None;
     loop {
-        let frame = try2!(Frame::from_context(ctx, signal));
+        let frame = try2!(next_frame(ctx, &mut signal, true));
 
+        // As with libgcc, the stop function is called once more with `END_OF_STACK` when there are
+        // no frames left.
         let code = unsafe {
             stop(
                 1,
                 UnwindAction::FORCE_UNWIND
-                    | UnwindAction::END_OF_STACK
+                    | UnwindAction::CLEANUP_PHASE
                     | if frame.is_none() {
                         UnwindAction::END_OF_STACK
                     } else {
@@ -297,7 +446,7 @@ fn force_unwind_phase2(
                 &mut UnwindContext {
                     frame: frame.as_ref(),
                     ctx,
-                    signal,
+                    signal: signal.is_interrupted(),
                 },
                 stop_arg,
             )
@@ -318,7 +467,7 @@ fn force_unwind_phase2(
                         &mut UnwindContext {
                             frame: Some(&frame),
                             ctx,
-                            signal,
+                            signal: signal.is_interrupted(),
                         },
                     )
                 };
@@ -334,7 +483,7 @@ fn force_unwind_phase2(
             }
 
             *ctx = try2!(frame.unwind(ctx));
-            signal = frame.is_signal_trampoline();
+            signal = frame.caller_interruption();
         } else {
             return UnwindReasonCode::END_OF_STACK;
         }
@@ -395,17 +544,17 @@ pub extern "C-unwind" fn _Unwind_Backtrace(
 ) -> UnwindReasonCode {
     with_context(|ctx| {
         let mut ctx = ctx.clone();
-        let mut signal = false;
+        let mut signal = Interruption::None;
         let mut skipping = cfg!(feature = "hide-trace");
 
         loop {
-            let frame = try1!(Frame::from_context(&ctx, signal));
+            let frame = try1!(next_frame(&mut ctx, &mut signal, false));
             if !skipping {
                 let code = trace(
                     &UnwindContext {
                         frame: frame.as_ref(),
                         ctx: &mut ctx,
-                        signal,
+                        signal: signal.is_interrupted(),
                     },
                     trace_argument,
                 );
@@ -421,7 +570,7 @@ pub extern "C-unwind" fn _Unwind_Backtrace(
                     }
                 }
                 ctx = try1!(frame.unwind(&ctx));
-                signal = frame.is_signal_trampoline();
+                signal = frame.caller_interruption();
             } else {
                 return UnwindReasonCode::END_OF_STACK;
             }
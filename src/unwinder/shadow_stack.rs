@@ -0,0 +1,123 @@
+//! Return addresses kept on a shadow stack: the CET shadow stack on x86_64, or the shadow call
+//! stack pointed to by x18 on AArch64 code built with `-Zsanitizer=shadow-call-stack`.
+
+use core::arch::asm;
+use core::mem::{self, MaybeUninit};
+
+#[cfg(target_arch = "aarch64")]
+use super::arch::strip_return_address;
+
+const CHUNK: usize = 32;
+
+/// Returns the current shadow stack pointer, or `None` if shadow stacks are not enabled for the
+/// current thread.
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+pub fn shadow_stack_pointer() -> Option<usize> {
+    let mut ssp: usize = 0;
+    // RDSSPQ is a no-op if shadow stacks are not enabled.
+    unsafe { asm!("rdsspq {}", inout(reg) ssp, options(nomem, nostack, preserves_flags)) };
+    if ssp == 0 {
+        None
+    } else {
+        Some(ssp)
+    }
+}
+
+/// Returns the current shadow call stack pointer, or `None` if x18 is not set.
+#[cfg(target_arch = "aarch64")]
+#[inline(always)]
+pub fn shadow_stack_pointer() -> Option<usize> {
+    let ssp: usize;
+    unsafe { asm!("mov {}, x18", out(reg) ssp, options(nomem, nostack, preserves_flags)) };
+    if ssp == 0 {
+        None
+    } else {
+        Some(ssp)
+    }
+}
+
+/// Walks the return addresses on the shadow stack, starting with the one into this function's
+/// caller, and calls `f` with each until it returns `false`.
+///
+/// Unlike `_Unwind_Backtrace`, this needs no unwind tables and can't be misled by a corrupted
+/// stack, but it gives no context other than the return address. On x86_64, entries pushed by the
+/// kernel for signal frames are skipped. On AArch64, frames not built with the shadow call stack
+/// don't appear.
+///
+/// Returns `false` if shadow stacks are not enabled for the current thread.
+#[inline(never)]
+pub fn shadow_stack_backtrace<F: FnMut(usize) -> bool>(mut f: F) -> bool {
+    let ssp = match shadow_stack_pointer() {
+        Some(ssp) => ssp,
+        None => return false,
+    };
+    walk(ssp, &mut f);
+    true
+}
+
+/// Reads the memory at `addr` into `buf`, returning the number of entries read. Shadow stacks
+/// have no end marker, and are followed by a guard gap, so they are read through
+/// `process_vm_readv`, which stops at the end of the mapping instead of faulting.
+fn read(addr: usize, buf: &mut [MaybeUninit<usize>]) -> usize {
+    let local = libc::iovec {
+        iov_base: buf.as_mut_ptr() as _,
+        iov_len: mem::size_of_val(buf),
+    };
+    let remote = libc::iovec {
+        iov_base: addr as _,
+        iov_len: mem::size_of_val(buf),
+    };
+    let read = unsafe { libc::process_vm_readv(libc::getpid(), &local, 1, &remote, 1, 0) };
+    if read <= 0 {
+        0
+    } else {
+        read as usize / mem::size_of::<usize>()
+    }
+}
+
+// The CET shadow stack grows downwards, and the pointer points to the last entry pushed.
+#[cfg(target_arch = "x86_64")]
+fn walk(mut ssp: usize, f: &mut dyn FnMut(usize) -> bool) {
+    // Entries that aren't return addresses have this bit set.
+    const DATA: usize = 1 << 63;
+
+    let mut buf = [MaybeUninit::<usize>::uninit(); CHUNK];
+    loop {
+        let count = read(ssp, &mut buf);
+        for entry in &buf[..count] {
+            let entry = unsafe { entry.assume_init() };
+            if entry & DATA == 0 && !f(entry) {
+                return;
+            }
+        }
+        if count < CHUNK {
+            return;
+        }
+        ssp += mem::size_of_val(&buf);
+    }
+}
+
+// The shadow call stack grows upwards, and the pointer points past the last entry pushed. Chunks
+// are aligned so they never straddle a page, as a read that starts in the guard gap fails as a
+// whole. The memory of a fresh shadow call stack is zeroed, so a null entry ends it as well.
+#[cfg(target_arch = "aarch64")]
+fn walk(mut ssp: usize, f: &mut dyn FnMut(usize) -> bool) {
+    const CHUNK_SIZE: usize = CHUNK * mem::size_of::<usize>();
+
+    let mut buf = [MaybeUninit::<usize>::uninit(); CHUNK];
+    while ssp % mem::size_of::<usize>() == 0 && ssp > CHUNK_SIZE {
+        let start = (ssp - mem::size_of::<usize>()) & !(CHUNK_SIZE - 1);
+        let len = (ssp - start) / mem::size_of::<usize>();
+        if read(start, &mut buf[..len]) < len {
+            return;
+        }
+        for entry in buf[..len].iter().rev() {
+            let entry = unsafe { entry.assume_init() };
+            if entry == 0 || !f(strip_return_address(entry)) {
+                return;
+            }
+        }
+        ssp = start;
+    }
+}
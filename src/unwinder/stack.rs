@@ -0,0 +1,96 @@
+use super::arch::Context;
+use super::double_buffer::Registry;
+use crate::arch::Arch;
+
+use core::ops::Range;
+
+/// Maximum number of stack ranges that can be registered at the same time.
+pub const MAX_STACK_SWITCHES: usize = 16;
+
+/// Provides the context a stack was entered from, so unwinding can continue past its root frame.
+///
+/// # Safety
+///
+/// The contexts returned must describe frames that are live on the stack being unwound, as they
+/// are installed when an exception propagates into them.
+pub unsafe trait StackSwitch {
+    /// Returns the context of the code that switched to the stack `ctx` is on, or `None` if the
+    /// stack was not entered from another one.
+    ///
+    /// `ctx` is the context unwinding stopped at: the stack pointer is within the registered range
+    /// (or at its end), but the return address may not be meaningful.
+    fn parent_context(&self, ctx: &Context) -> Option<Context>;
+}
+
+/// Identifies a stack range registered with [`register_stack_switch`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StackSwitchId(u32);
+
+#[derive(Clone, Copy)]
+struct Entry {
+    start: usize,
+    end: usize,
+    exceptions: bool,
+    switch: &'static (dyn StackSwitch + Sync),
+}
+
+static SWITCHES: Registry<Entry, MAX_STACK_SWITCHES> = Registry::new();
+
+/// Get the context to continue unwinding from after reaching the end of the stack at `ctx`.
+pub fn parent_context(ctx: &Context, exception: bool) -> Option<Context> {
+    let sp = ctx[Arch::SP];
+    let parent = SWITCHES
+        .values()
+        .filter(|entry| entry.exceptions || !exception)
+        .find(|entry| (entry.start..=entry.end).contains(&sp))?
+        .switch
+        .parent_context(ctx)?;
+
+    // Don't go round in circles.
+    if parent[Arch::SP] == sp {
+        return None;
+    }
+    Some(parent)
+}
+
+/// The type returned by [`register_stack_switch`] if [`MAX_STACK_SWITCHES`] ranges are already
+/// registered.
+#[derive(Debug)]
+pub struct RegisterStackSwitchError(());
+
+/// Registers a range of stack memory whose root frames continue on another stack.
+///
+/// When unwinding runs out of frames with the stack pointer in `stack`, `switch` is asked for the
+/// context the stack was entered from, and unwinding carries on from there. `stack` may cover
+/// many stacks, such as the region all coroutine stacks are allocated from.
+///
+/// Backtraces always continue into the parent stack. Exceptions only do so if `exceptions` is
+/// `true`; catching an exception there abandons the frames that remain on the registered stack.
+///
+/// # Errors
+///
+/// An error is returned if [`MAX_STACK_SWITCHES`] ranges are already registered.
+pub fn register_stack_switch(
+    stack: Range<usize>,
+    switch: &'static (dyn StackSwitch + Sync),
+    exceptions: bool,
+) -> Result<StackSwitchId, RegisterStackSwitchError> {
+    let entry = Entry {
+        start: stack.start,
+        end: stack.end,
+        exceptions,
+        switch,
+    };
+    SWITCHES
+        .register(entry)
+        .map(StackSwitchId)
+        .ok_or(RegisterStackSwitchError(()))
+}
+
+/// Unregisters a stack range.
+///
+/// Returns `false` if the range is not registered. An unwind that is already in progress may
+/// still use it.
+pub fn unregister_stack_switch(id: StackSwitchId) -> bool {
+    SWITCHES.unregister(id.0)
+}
@@ -0,0 +1,92 @@
+use super::arch::Context;
+use super::double_buffer::Registry;
+use crate::abi::PersonalityRoutine;
+
+use core::ops::Range;
+
+/// Maximum number of frame steppers that can be registered at the same time.
+pub const MAX_FRAME_STEPPERS: usize = 16;
+
+/// Recovers the caller of frames that have no DWARF unwind information, such as JIT-compiled code
+/// or interpreter frames.
+///
+/// # Safety
+///
+/// The contexts returned must describe frames that are live on the stack being unwound, as they
+/// are installed when unwinding lands in them.
+pub unsafe trait FrameStepper {
+    /// Steps out of the frame described by `ctx`, whose PC lies in the range the stepper was
+    /// registered for. Returns `None` if the frame cannot be unwound.
+    fn step(&self, ctx: &Context) -> Option<Step>;
+}
+
+/// The result of stepping out of a frame with a [`FrameStepper`].
+#[derive(Clone, Debug)]
+pub struct Step {
+    /// The registers of the caller, with [`Arch::RA`](crate::frame_stepper::Arch::RA) being the
+    /// address it resumes execution at. [`Arch::SP`](crate::frame_stepper::Arch::SP) is taken as
+    /// the canonical frame address of the frame being stepped out of.
+    pub caller: Context,
+    /// The start of the function, as returned by `_Unwind_GetRegionStart`.
+    pub function_start: usize,
+    /// The personality routine to call for this frame, if any.
+    pub personality: Option<PersonalityRoutine>,
+    /// The language-specific data passed to the personality routine.
+    pub lsda: usize,
+}
+
+/// Identifies a registered [`FrameStepper`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameStepperId(u32);
+
+#[derive(Clone, Copy)]
+struct Entry {
+    start: usize,
+    end: usize,
+    stepper: &'static (dyn FrameStepper + Sync),
+}
+
+static STEPPERS: Registry<Entry, MAX_FRAME_STEPPERS> = Registry::new();
+
+pub fn find_stepper(pc: usize) -> Option<&'static (dyn FrameStepper + Sync)> {
+    STEPPERS
+        .values()
+        .find(|entry| (entry.start..entry.end).contains(&pc))
+        .map(|entry| entry.stepper)
+}
+
+/// The type returned by [`register_frame_stepper`] if [`MAX_FRAME_STEPPERS`] steppers are already
+/// registered.
+#[derive(Debug)]
+pub struct RegisterFrameStepperError(());
+
+/// Registers a stepper for the frames whose PC lies in `pcs`.
+///
+/// Steppers take precedence over the unwind information found for these PCs. If the ranges of
+/// several steppers overlap, it is unspecified which one is used.
+///
+/// # Errors
+///
+/// An error is returned if [`MAX_FRAME_STEPPERS`] steppers are already registered.
+pub fn register_frame_stepper(
+    pcs: Range<usize>,
+    stepper: &'static (dyn FrameStepper + Sync),
+) -> Result<FrameStepperId, RegisterFrameStepperError> {
+    let entry = Entry {
+        start: pcs.start,
+        end: pcs.end,
+        stepper,
+    };
+    STEPPERS
+        .register(entry)
+        .map(FrameStepperId)
+        .ok_or(RegisterFrameStepperError(()))
+}
+
+/// Unregisters a stepper.
+///
+/// Returns `false` if the stepper is not registered. An unwind that is already in progress may
+/// still use the stepper.
+pub fn unregister_frame_stepper(id: FrameStepperId) -> bool {
+    STEPPERS.unregister(id.0)
+}
@@ -0,0 +1,113 @@
+use super::arch::Context;
+use super::double_buffer::Registry;
+use crate::arch::Arch;
+
+use core::ops::Range;
+use gimli::Register;
+
+/// Maximum number of trap entry points that can be registered at the same time.
+pub const MAX_TRAP_ENTRIES: usize = 8;
+
+/// Where a trap entry stub saves the state of the interrupted code.
+///
+/// All offsets are in bytes.
+#[derive(Clone, Copy, Debug)]
+pub struct TrapFrameLayout {
+    /// Offset of the trap frame from the stack pointer of the entry stub, at the point where it
+    /// calls the handler.
+    pub offset: usize,
+    /// Size of the trap frame. If the stack pointer is not among `registers`, the interrupted code
+    /// is assumed to have its stack pointer right past the end of the trap frame.
+    pub size: usize,
+    /// Offset of the saved PC of the interrupted code.
+    pub pc: usize,
+    /// Offsets of the saved registers. Registers not listed keep the value they have in the entry
+    /// stub.
+    pub registers: &'static [(Register, usize)],
+}
+
+/// Identifies a trap entry point registered with [`register_trap_entry`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrapEntryId(u32);
+
+#[derive(Clone, Copy)]
+struct Entry {
+    start: usize,
+    end: usize,
+    layout: &'static TrapFrameLayout,
+}
+
+static ENTRIES: Registry<Entry, MAX_TRAP_ENTRIES> = Registry::new();
+
+/// The frame of a trap entry stub.
+#[derive(Debug)]
+pub struct TrapFrame {
+    pub entry: usize,
+    /// The interrupted code, with the return address register holding its PC.
+    pub caller: Context,
+    /// What the return address register held in the interrupted code, if it is a general purpose
+    /// register that was saved in the trap frame.
+    pub ra: Option<usize>,
+}
+
+pub fn find_trap_frame(ctx: &Context, pc: usize) -> Option<TrapFrame> {
+    let entry = ENTRIES
+        .values()
+        .find(|entry| (entry.start..entry.end).contains(&pc))?;
+    let layout = entry.layout;
+    let base = ctx[Arch::SP].wrapping_add(layout.offset);
+    let load = |offset: usize| unsafe { *(base.wrapping_add(offset) as *const usize) };
+
+    let mut caller = ctx.clone();
+    caller[Arch::SP] = base.wrapping_add(layout.size);
+    let mut ra = None;
+    for &(reg, offset) in layout.registers {
+        caller[reg] = load(offset);
+        if reg == Arch::RA {
+            ra = Some(caller[reg]);
+        }
+    }
+    caller[Arch::RA] = load(layout.pc);
+
+    Some(TrapFrame {
+        entry: entry.start,
+        caller,
+        ra,
+    })
+}
+
+/// The type returned by [`register_trap_entry`] if [`MAX_TRAP_ENTRIES`] entry points are already
+/// registered.
+#[derive(Debug)]
+pub struct RegisterTrapEntryError(());
+
+/// Registers a trap entry stub, which saves the interrupted state as described by `layout`.
+///
+/// `entry` is the code range of the stub, which must contain the call into the handler. When
+/// unwinding reaches the stub, it continues in the interrupted code. That frame is unwound as if
+/// interrupted by a signal, so its PC is not adjusted to point into the preceding instruction.
+///
+/// # Errors
+///
+/// An error is returned if [`MAX_TRAP_ENTRIES`] entry points are already registered.
+pub fn register_trap_entry(
+    entry: Range<usize>,
+    layout: &'static TrapFrameLayout,
+) -> Result<TrapEntryId, RegisterTrapEntryError> {
+    let entry = Entry {
+        start: entry.start,
+        end: entry.end,
+        layout,
+    };
+    ENTRIES
+        .register(entry)
+        .map(TrapEntryId)
+        .ok_or(RegisterTrapEntryError(()))
+}
+
+/// Unregisters a trap entry stub.
+///
+/// Returns `false` if the stub is not registered.
+pub fn unregister_trap_entry(id: TrapEntryId) -> bool {
+    ENTRIES.unregister(id.0)
+}
@@ -1,15 +1,191 @@
+#[cfg(any(
+    all(feature = "unwinder", not(target_arch = "arm")),
+    feature = "personality",
+    feature = "personality-gcc"
+))]
 use gimli::{EndianSlice, NativeEndian, Pointer};
 
+#[cfg(any(
+    all(feature = "unwinder", not(target_arch = "arm")),
+    feature = "personality",
+    feature = "personality-gcc"
+))]
 pub type StaticSlice = EndianSlice<'static, NativeEndian>;
 
-pub unsafe fn get_unlimited_slice<'a>(start: *const u8) -> &'a [u8] {
-    // Create the largest possible slice for this address.
-    let start = start as usize;
-    let end = start.saturating_add(isize::MAX as _);
-    let len = end - start;
+#[cfg(any(
+    all(
+        feature = "unwinder",
+        not(target_arch = "arm"),
+        any(
+            feature = "fde-phdr",
+            feature = "fde-registry",
+            feature = "fde-static",
+            feature = "fde-static-regions",
+            feature = "fde-gnu-eh-frame-hdr",
+            feature = "fde-custom"
+        )
+    ),
+    feature = "personality",
+    feature = "personality-gcc"
+))]
+pub unsafe fn get_slice<'a>(start: usize, len: usize) -> &'a [u8] {
     unsafe { core::slice::from_raw_parts(start as *const _, len) }
 }
 
+/// Where an `.eh_frame` section ends.
+#[cfg(any(
+    all(feature = "fde-registry", not(target_arch = "arm")),
+    all(feature = "fde-static", not(target_arch = "arm")),
+    feature = "fde-static-regions",
+    feature = "fde-gnu-eh-frame-hdr",
+    feature = "fde-custom"
+))]
+#[cfg_attr(
+    not(any(
+        feature = "fde-static",
+        feature = "fde-static-regions",
+        feature = "fde-gnu-eh-frame-hdr",
+        feature = "fde-custom"
+    )),
+    allow(dead_code)
+)]
+#[derive(Clone, Copy)]
+pub enum EhFrameEnd {
+    /// At this address.
+    At(usize),
+    /// At a zero terminator, which is no further than this address.
+    Terminator(usize),
+}
+
+#[cfg(any(
+    all(feature = "fde-registry", not(target_arch = "arm")),
+    all(feature = "fde-static", not(target_arch = "arm")),
+    feature = "fde-static-regions",
+    feature = "fde-gnu-eh-frame-hdr",
+    feature = "fde-custom"
+))]
+impl EhFrameEnd {
+    /// The largest address the section could end at.
+    #[cfg(any(
+        feature = "fde-static-regions",
+        feature = "fde-gnu-eh-frame-hdr",
+        feature = "fde-custom"
+    ))]
+    pub fn bound(self) -> usize {
+        match self {
+            EhFrameEnd::At(end) | EhFrameEnd::Terminator(end) => end,
+        }
+    }
+
+    /// Get the length of the `.eh_frame` section at `start`.
+    ///
+    /// Returns `None` if an entry runs past the end.
+    pub unsafe fn len(self, start: usize) -> Option<usize> {
+        match self {
+            EhFrameEnd::At(end) => end.checked_sub(start),
+            EhFrameEnd::Terminator(end) => {
+                let mut ptr = start;
+                while ptr != end {
+                    let (next, terminator) = unsafe { eh_frame_entry(ptr, end)? };
+                    ptr = next;
+                    if terminator {
+                        break;
+                    }
+                }
+                Some(ptr - start)
+            }
+        }
+    }
+}
+
+/// Get the end of the `.eh_frame` entry at `ptr`, and whether it is a zero terminator.
+///
+/// Returns `None` if the entry runs past `end`.
+#[cfg(any(
+    all(feature = "fde-registry", not(target_arch = "arm")),
+    all(feature = "fde-static", not(target_arch = "arm")),
+    feature = "fde-static-regions",
+    feature = "fde-gnu-eh-frame-hdr",
+    feature = "fde-custom"
+))]
+pub unsafe fn eh_frame_entry(ptr: usize, end: usize) -> Option<(usize, bool)> {
+    let available = end.checked_sub(ptr)?;
+    if available < 4 {
+        return None;
+    }
+    let len = unsafe { (ptr as *const u32).read_unaligned() };
+    let next = if len == u32::MAX {
+        if available < 12 {
+            return None;
+        }
+        let len = unsafe { ((ptr + 4) as *const u64).read_unaligned() };
+        (ptr + 12).checked_add(usize::try_from(len).ok()?)?
+    } else {
+        (ptr + 4).checked_add(len as usize)?
+    };
+    (next <= end).then_some((next, len == 0))
+}
+
+/// Get the length of an `.eh_frame_hdr` section from the encodings and the FDE count in its header.
+#[cfg(any(feature = "fde-gnu-eh-frame-hdr", feature = "fde-custom"))]
+pub unsafe fn eh_frame_hdr_len(start: usize) -> Option<usize> {
+    fn size(encoding: u8) -> Option<usize> {
+        match encoding & 0x0F {
+            0x00 => Some(core::mem::size_of::<usize>()),
+            0x02 | 0x0A => Some(2),
+            0x03 | 0x0B => Some(4),
+            0x04 | 0x0C => Some(8),
+            _ => None,
+        }
+    }
+
+    let header = unsafe { get_slice(start, 4) };
+    if header[0] != 1 {
+        return None;
+    }
+    let mut len = 4 + size(header[1])?;
+    // DW_EH_PE_omit
+    if header[2] == 0xFF || header[3] == 0xFF {
+        return Some(len);
+    }
+    let fde_count = start + len;
+    let fde_count = unsafe {
+        match size(header[2])? {
+            2 => (fde_count as *const u16).read_unaligned() as usize,
+            4 => (fde_count as *const u32).read_unaligned() as usize,
+            _ => (fde_count as *const u64).read_unaligned() as usize,
+        }
+    };
+    len += size(header[2])?;
+    fde_count
+        .checked_mul(2 * size(header[3])?)?
+        .checked_add(len)
+}
+
+/// Get the end of the program image from `_end`, which most linkers define.
+#[cfg(all(
+    not(target_arch = "arm"),
+    any(
+        feature = "fde-static",
+        feature = "fde-static-regions",
+        feature = "fde-gnu-eh-frame-hdr"
+    )
+))]
+pub fn image_end() -> Option<usize> {
+    extern "C" {
+        #[linkage = "extern_weak"]
+        static _end: *const u8;
+    }
+
+    let end = unsafe { _end };
+    (!end.is_null()).then_some(end as usize)
+}
+
+#[cfg(any(
+    all(feature = "unwinder", not(target_arch = "arm")),
+    feature = "personality",
+    feature = "personality-gcc"
+))]
 pub unsafe fn deref_pointer(ptr: Pointer) -> usize {
     match ptr {
         Pointer::Direct(x) => x as _,
@@ -17,6 +193,41 @@ pub unsafe fn deref_pointer(ptr: Pointer) -> usize {
     }
 }
 
+/// The number of modules ever loaded and unloaded, as reported by `dl_iterate_phdr`.
+#[cfg(all(
+    any(
+        all(
+            feature = "fde-phdr-dl",
+            not(feature = "signal-safe"),
+            not(target_arch = "arm")
+        ),
+        feature = "fde-debug-frame"
+    ),
+    not(any(target_env = "uclibc", target_os = "nto"))
+))]
+pub fn phdr_counters(info: &libc::dl_phdr_info, size: usize) -> Option<(u64, u64)> {
+    // Older C libraries pass a smaller structure without the counters.
+    if size < core::mem::offset_of!(libc::dl_phdr_info, dlpi_subs) + core::mem::size_of::<u64>() {
+        return None;
+    }
+    Some((info.dlpi_adds as _, info.dlpi_subs as _))
+}
+
+#[cfg(all(
+    any(
+        all(
+            feature = "fde-phdr-dl",
+            not(feature = "signal-safe"),
+            not(target_arch = "arm")
+        ),
+        feature = "fde-debug-frame"
+    ),
+    any(target_env = "uclibc", target_os = "nto")
+))]
+pub fn phdr_counters(_info: &libc::dl_phdr_info, _size: usize) -> Option<(u64, u64)> {
+    None
+}
+
 #[cfg(feature = "libc")]
 pub use libc::c_int;
 
@@ -0,0 +1,26 @@
+#!/usr/bin/env bash
+set -o pipefail
+trap "rm -f run.log table.bin" EXIT
+features=${UNWINDING_FEATURES:+--features "unwinding/$UNWINDING_FEATURES"}
+exe=$(${CARGO:-cargo} build --release $BUILD_STD $features --message-format=json-render-diagnostics |
+    grep -ao '"executable":"[^"]*"' | cut -d'"' -f4)
+if [ -z "$exe" ]; then
+    echo build failed
+    exit 1
+fi
+${CARGO:-cargo} run --release $BUILD_STD -p unwinding-compact -- "$exe" table.bin 2>&1 | tee run.log
+status=$?
+if grep -q 'unsupported architecture' run.log; then
+    echo "skipped: no compact table for this architecture"
+    exit 0
+fi
+if [ $status -ne 0 ]; then
+    echo conversion failed
+    exit 1
+fi
+${CARGO:-cargo} run --release $BUILD_STD $features -- table.bin 2>&1 | tee run.log
+if [ $? -ne 0 ]; then
+    echo process did not exit successfully
+    exit 1
+fi
+grep -Pz 'without table: 3 frames, 3 looked up\nwith table: 3 frames, 0 looked up\n' run.log
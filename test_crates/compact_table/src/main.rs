@@ -0,0 +1,119 @@
+#[cfg(not(target_arch = "arm"))]
+mod table {
+    use std::ffi::c_void;
+    use std::hint::black_box;
+    use std::sync::Mutex;
+    use unwinding::abi::*;
+    use unwinding::compact_unwind::{set_compact_unwind_table, CompactTable};
+    use unwinding::custom_eh_frame_finder::{set_custom_eh_frame_finder, EhFrameFinder, FrameInfo};
+
+    // PCs DWARF CFI has been looked up for.
+    static LOOKED_UP: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+    // The custom finder is consulted before the others, so it sees every DWARF lookup, and leaves
+    // them to the others.
+    struct Recorder;
+
+    unsafe impl EhFrameFinder for Recorder {
+        fn find(&self, pc: usize) -> Option<FrameInfo> {
+            LOOKED_UP.lock().unwrap().push(pc);
+            None
+        }
+    }
+
+    static RECORDER: Recorder = Recorder;
+
+    // The start of the function and the IP of each frame.
+    static TRACE: Mutex<Vec<(usize, usize)>> = Mutex::new(Vec::new());
+
+    extern "C" fn callback(unwind_ctx: &UnwindContext<'_>, _arg: *mut c_void) -> UnwindReasonCode {
+        TRACE.lock().unwrap().push((
+            _Unwind_GetRegionStart(unwind_ctx),
+            _Unwind_GetIP(unwind_ctx),
+        ));
+        UnwindReasonCode::NO_REASON
+    }
+
+    // These only save the return address and frame pointer, so the compact table describes them.
+    #[inline(never)]
+    fn level3() {
+        _Unwind_Backtrace(callback, core::ptr::null_mut());
+        black_box(());
+    }
+
+    #[inline(never)]
+    fn level2() {
+        level3();
+        black_box(());
+    }
+
+    #[inline(never)]
+    fn level1() {
+        level2();
+        black_box(());
+    }
+
+    // Returns the number of frames of `level1` to `level3` in a backtrace, and how many of them
+    // were looked up in DWARF CFI.
+    fn backtrace() -> (usize, usize) {
+        TRACE.lock().unwrap().clear();
+        LOOKED_UP.lock().unwrap().clear();
+        level1();
+        let trace = TRACE.lock().unwrap();
+        let looked_up = LOOKED_UP.lock().unwrap();
+        let levels = [level1 as usize, level2 as usize, level3 as usize];
+        let frames: Vec<_> = trace
+            .iter()
+            .filter(|(start, _)| levels.contains(start))
+            .collect();
+        let dwarf = frames
+            .iter()
+            .filter(|&&&(start, ip)| looked_up.iter().any(|pc| (start..=ip).contains(pc)))
+            .count();
+        (frames.len(), dwarf)
+    }
+
+    // The difference between the runtime and link-time addresses of the executable.
+    fn load_bias() -> usize {
+        unsafe extern "C" fn callback(
+            info: *mut libc::dl_phdr_info,
+            _size: libc::size_t,
+            data: *mut c_void,
+        ) -> libc::c_int {
+            unsafe { *(data as *mut usize) = (*info).dlpi_addr as usize };
+            // The executable comes first.
+            1
+        }
+        let mut bias = 0usize;
+        unsafe { libc::dl_iterate_phdr(Some(callback), &mut bias as *mut usize as *mut c_void) };
+        bias
+    }
+
+    pub fn main() {
+        let path = std::env::args()
+            .nth(1)
+            .expect("usage: compact_table <table>");
+        let data = std::fs::read(path).unwrap();
+        // The table needs to be aligned to 8 bytes.
+        let words: &'static mut [u64] = vec![0; data.len().div_ceil(8)].leak();
+        let bytes: &'static mut [u8] =
+            unsafe { core::slice::from_raw_parts_mut(words.as_mut_ptr() as *mut u8, data.len()) };
+        bytes.copy_from_slice(&data);
+
+        set_custom_eh_frame_finder(&RECORDER).unwrap();
+
+        let (frames, dwarf) = backtrace();
+        println!("without table: {frames} frames, {dwarf} looked up");
+
+        let table = CompactTable::parse(bytes).expect("invalid table");
+        set_compact_unwind_table(table, load_bias()).unwrap();
+        let (frames, dwarf) = backtrace();
+        println!("with table: {frames} frames, {dwarf} looked up");
+    }
+}
+
+#[cfg(not(target_arch = "arm"))]
+use table::main;
+
+#[cfg(target_arch = "arm")]
+fn main() {}
@@ -0,0 +1,87 @@
+extern crate unwinding;
+
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use unwinding::abi::*;
+use unwinding::panicking::{self, Exception, StopAction};
+
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+struct Guard;
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        DROPPED.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[repr(C)]
+struct Cancelled {
+    exception: MaybeUninit<UnwindException>,
+}
+
+unsafe impl Exception for Cancelled {
+    const CLASS: [u8; 8] = *b"TESTCANC";
+
+    fn wrap(this: Self) -> *mut UnwindException {
+        Box::into_raw(Box::new(this)) as *mut UnwindException
+    }
+
+    unsafe fn unwrap(ex: *mut UnwindException) -> Self {
+        *unsafe { Box::from_raw(ex as *mut Cancelled) }
+    }
+}
+
+// Stop once `body` is reached, so that the exception is caught in `target`.
+fn stop(ctx: &UnwindContext<'_>) -> StopAction {
+    if _Unwind_GetRegionStart(ctx) == body as usize {
+        StopAction::Stop
+    } else {
+        StopAction::Continue
+    }
+}
+
+#[inline(never)]
+fn cancel() {
+    let _guard = Guard;
+    panicking::force_unwind(
+        Cancelled {
+            exception: MaybeUninit::uninit(),
+        },
+        stop,
+    );
+}
+
+#[inline(never)]
+fn guarded() {
+    let _guard = Guard;
+    cancel();
+}
+
+// The forced unwind isn't stopped yet here, so it's resumed. This runs with the personality of
+// `std`, which lands in `catch_unwind` like the one of this crate.
+#[inline(never)]
+fn protected() {
+    let _ = panicking::catch_unwind::<Cancelled, _, _>(guarded);
+    println!("forced unwind caught");
+}
+
+#[inline(never)]
+fn body() {
+    let _guard = Guard;
+    protected();
+}
+
+#[inline(never)]
+fn target() {
+    let result = panicking::catch_unwind::<Cancelled, _, _>(body);
+    println!(
+        "reached target: {} dropped: {}",
+        matches!(result, Err(Some(_))),
+        DROPPED.load(Ordering::SeqCst)
+    );
+}
+
+fn main() {
+    target();
+}
@@ -0,0 +1,9 @@
+fn main() {
+    // `fde-static` finds the program and `.eh_frame` with symbols that a baremetal linker script
+    // would provide.
+    println!("cargo:rustc-link-arg-bins=-Wl,--defsym=__etext=etext");
+    println!("cargo:rustc-link-arg-bins=-Wl,--defsym=__eh_frame=ADDR(.eh_frame)");
+    println!(
+        "cargo:rustc-link-arg-bins=-Wl,--defsym=__eh_frame_end=ADDR(.eh_frame)+SIZEOF(.eh_frame)"
+    );
+}
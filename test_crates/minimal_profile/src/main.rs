@@ -0,0 +1,67 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use core::alloc::{GlobalAlloc, Layout};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+struct System;
+
+unsafe impl GlobalAlloc for System {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        unsafe { libc::memalign(layout.align(), layout.size()) as *mut u8 }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
+        unsafe { libc::free(ptr as *mut libc::c_void) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: System = System;
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo<'_>) -> ! {
+    unsafe { libc::abort() }
+}
+
+fn print(s: &str) {
+    unsafe { libc::write(1, s.as_ptr() as *const libc::c_void, s.len()) };
+}
+
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+struct Guard;
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        DROPPED.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[inline(never)]
+fn throw() {
+    let _guard = Guard;
+    unwinding::panic::begin_panic(Box::new(42u32));
+}
+
+#[inline(never)]
+fn nested() {
+    let _guard = Guard;
+    throw();
+}
+
+#[no_mangle]
+extern "C" fn main(_argc: libc::c_int, _argv: *const *const libc::c_char) -> libc::c_int {
+    let payload = match unwinding::panic::catch_unwind(nested) {
+        Ok(()) => return 1,
+        Err(payload) => payload,
+    };
+    if payload.downcast_ref::<u32>() != Some(&42) || DROPPED.load(Ordering::SeqCst) != 2 {
+        return 1;
+    }
+    print("caught\n");
+    0
+}
@@ -0,0 +1,14 @@
+#!/usr/bin/env bash
+set -o pipefail
+# Return addresses are only signed when the test is built with branch protection.
+case "$RUSTFLAGS" in
+    *branch-protection*) ;;
+    *) echo "skipped: branch protection is not enabled"; exit 0 ;;
+esac
+trap "rm -f run.log" EXIT
+${CARGO:-cargo} run --release $BUILD_STD ${UNWINDING_FEATURES:+--features "unwinding/$UNWINDING_FEATURES"} 2>&1 | tee run.log
+if [ $? -ne 0 ]; then
+    echo process did not exit successfully
+    exit 1
+fi
+grep -Pz 'frames: 6 stripped: true caught: Some\(42\)\n' run.log
@@ -0,0 +1,56 @@
+extern crate unwinding;
+
+use std::ffi::c_void;
+use std::hint::black_box;
+use unwinding::abi::*;
+
+#[derive(Default)]
+struct Trace {
+    // Frames of `nested`, whose return addresses are signed.
+    frames: usize,
+    // Whether every IP has its pointer authentication code stripped.
+    stripped: bool,
+}
+
+extern "C" fn callback(ctx: &UnwindContext<'_>, arg: *mut c_void) -> UnwindReasonCode {
+    let trace = unsafe { &mut *(arg as *mut Trace) };
+    // Only the low 48 bits hold the address; a code would be in the bits above.
+    trace.stripped &= _Unwind_GetIP(ctx) >> 48 == 0;
+    if _Unwind_GetRegionStart(ctx) == nested as usize {
+        trace.frames += 1;
+    }
+    UnwindReasonCode::NO_REASON
+}
+
+#[inline(never)]
+fn nested(depth: usize, f: &mut dyn FnMut()) {
+    if depth == 0 {
+        f();
+    } else {
+        nested(black_box(depth - 1), f);
+    }
+    // Keeps the call from becoming a tail call, so each frame stays on the stack.
+    black_box(());
+}
+
+fn main() {
+    let mut trace = Trace {
+        stripped: true,
+        ..Default::default()
+    };
+    nested(5, &mut || {
+        _Unwind_Backtrace(callback, &mut trace as *mut _ as _);
+    });
+
+    let result = unwinding::panic::catch_unwind(|| {
+        nested(5, &mut || {
+            unwinding::panic::begin_panic(Box::new(42));
+        })
+    });
+    println!(
+        "frames: {} stripped: {} caught: {:?}",
+        trace.frames,
+        trace.stripped,
+        result.err().and_then(|e| e.downcast::<i32>().ok())
+    );
+}
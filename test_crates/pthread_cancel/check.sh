@@ -0,0 +1,18 @@
+#!/usr/bin/env bash
+set -o pipefail
+lib_dir=$(mktemp -d)
+trap "rm -rf run.log $lib_dir" EXIT
+${CARGO:-cargo} build --release $BUILD_STD -p unwinding_dyn ${UNWINDING_FEATURES:+--features "unwinding/$UNWINDING_FEATURES"} || exit 1
+target_dir=$(${CARGO:-cargo} metadata --format-version 1 --no-deps | grep -Po '"target_directory":"\K[^"]+')
+target_dir="$target_dir/${CARGO_BUILD_TARGET:+$CARGO_BUILD_TARGET/}release"
+# glibc loads libgcc_s.so.1 itself to unwind cancelled threads, so the unwinder has to take its
+# place, symbol versions included.
+${CC:-cc} -shared -o "$lib_dir/libgcc_s.so.1" -Wl,-soname,libgcc_s.so.1 \
+    -Wl,--version-script=../../cdylib/libgcc_s.map \
+    -Wl,--whole-archive "$target_dir/libunwinding_dyn.a" -Wl,--no-whole-archive || exit 1
+${CARGO:-cargo} run --release $BUILD_STD -- "$lib_dir/libgcc_s.so.1" 2>&1 | tee run.log
+if [ $? -ne 0 ]; then
+    echo process did not exit successfully
+    exit 1
+fi
+grep -Pz 'unwinder preloaded: true\ncancelled: true dropped: 2\nexited: true dropped: 2\n' run.log
@@ -0,0 +1,115 @@
+use std::ffi::c_void;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+extern "C-unwind" {
+    fn pthread_testcancel();
+    fn pthread_exit(value: *mut c_void) -> !;
+}
+
+const PTHREAD_CANCELED: *mut c_void = -1isize as *mut c_void;
+
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+struct Guard;
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        DROPPED.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[inline(never)]
+fn wait_for_cancel() {
+    let _guard = Guard;
+    loop {
+        unsafe { pthread_testcancel() };
+        std::thread::yield_now();
+    }
+}
+
+#[inline(never)]
+fn exit_thread() {
+    let _guard = Guard;
+    unsafe { pthread_exit(42 as *mut c_void) };
+}
+
+extern "C-unwind" fn cancelled(_: *mut c_void) -> *mut c_void {
+    let _guard = Guard;
+    wait_for_cancel();
+    ptr::null_mut()
+}
+
+extern "C-unwind" fn exited(_: *mut c_void) -> *mut c_void {
+    let _guard = Guard;
+    exit_thread();
+    ptr::null_mut()
+}
+
+fn run(f: extern "C-unwind" fn(*mut c_void) -> *mut c_void, cancel: bool) -> *mut c_void {
+    DROPPED.store(0, Ordering::SeqCst);
+    unsafe {
+        let mut thread = 0;
+        // SAFETY: the unwind ABI does not change how the function is called.
+        let f = core::mem::transmute::<
+            extern "C-unwind" fn(*mut c_void) -> *mut c_void,
+            extern "C" fn(*mut c_void) -> *mut c_void,
+        >(f);
+        assert_eq!(
+            libc::pthread_create(&mut thread, ptr::null(), f, ptr::null_mut()),
+            0
+        );
+        if cancel {
+            assert_eq!(libc::pthread_cancel(thread), 0);
+        }
+        let mut ret = ptr::null_mut();
+        assert_eq!(libc::pthread_join(thread, &mut ret), 0);
+        ret
+    }
+}
+
+// glibc looks up the unwinder for thread cancellation in the `libgcc_s.so.1` it loads, rather than
+// in the global scope.
+fn unwinder_preloaded() -> bool {
+    let Some(preload) = std::env::var_os("LD_PRELOAD") else {
+        return false;
+    };
+    unsafe {
+        let handle = libc::dlopen(c"libgcc_s.so.1".as_ptr(), libc::RTLD_NOW | libc::RTLD_NOLOAD);
+        if handle.is_null() {
+            return false;
+        }
+        let sym = libc::dlsym(handle, c"_Unwind_ForcedUnwind".as_ptr());
+        let mut info: libc::Dl_info = core::mem::zeroed();
+        libc::dladdr(sym, &mut info) != 0
+            && std::ffi::CStr::from_ptr(info.dli_fname).to_bytes() == preload.as_encoded_bytes()
+    }
+}
+
+fn main() {
+    // Run again with the unwinder preloaded; setting `LD_PRELOAD` for cargo would preload it into
+    // cargo as well.
+    if let Some(unwinder) = std::env::args_os().nth(1) {
+        let status = std::process::Command::new(std::env::current_exe().unwrap())
+            .env("LD_PRELOAD", unwinder)
+            .status()
+            .unwrap();
+        std::process::exit(status.code().unwrap_or(1));
+    }
+
+    println!("unwinder preloaded: {}", unwinder_preloaded());
+
+    let ret = run(cancelled, true);
+    println!(
+        "cancelled: {} dropped: {}",
+        ret == PTHREAD_CANCELED,
+        DROPPED.load(Ordering::SeqCst)
+    );
+
+    let ret = run(exited, false);
+    println!(
+        "exited: {} dropped: {}",
+        ret as usize == 42,
+        DROPPED.load(Ordering::SeqCst)
+    );
+}
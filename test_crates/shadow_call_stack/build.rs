@@ -0,0 +1,13 @@
+fn main() {
+    // Rust doesn't support the shadow call stack sanitizer on Linux, so the frame that uses it is
+    // built from C with Clang.
+    if std::env::var("CARGO_CFG_TARGET_ARCH").unwrap() == "aarch64" {
+        cc::Build::new()
+            .compiler("clang")
+            .file("src/scs.c")
+            .flag("-fsanitize=shadow-call-stack")
+            .flag("-ffixed-x18")
+            .flag("-funwind-tables")
+            .compile("scs");
+    }
+}
@@ -0,0 +1,14 @@
+#!/usr/bin/env bash
+set -o pipefail
+# The shadow call stack pointer only survives in x18 if nothing else uses the register.
+case "$RUSTFLAGS" in
+    *fixed-x18*) ;;
+    *) echo "skipped: x18 is not reserved"; exit 0 ;;
+esac
+trap "rm -f run.log" EXIT
+${CARGO:-cargo} run --release $BUILD_STD ${UNWINDING_FEATURES:+--features "unwinding/$UNWINDING_FEATURES"} 2>&1 | tee run.log
+if [ $? -ne 0 ]; then
+    echo process did not exit successfully
+    exit 1
+fi
+grep -Pz 'caught: true x18 restored: true entries: 1\n' run.log
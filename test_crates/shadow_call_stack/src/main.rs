@@ -0,0 +1,102 @@
+extern crate unwinding;
+
+#[cfg(target_arch = "aarch64")]
+mod scs {
+    use core::ptr::addr_of_mut;
+    use std::mem::MaybeUninit;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use unwinding::abi::*;
+    use unwinding::panicking::{self, Exception};
+    use unwinding::shadow_stack::shadow_stack_backtrace;
+
+    // `scs_enter` switches x18 to a shadow call stack of its own. `scs_push`, built with the
+    // shadow call stack sanitizer, pushes its return address onto it, with the CFI the compiler
+    // emits for that: DW_CFA_val_expression: x18, DW_OP_breg18 -8.
+    core::arch::global_asm!(
+        ".globl scs_enter",
+        "scs_enter:",
+        ".cfi_startproc",
+        "stp x29, x30, [sp, -32]!",
+        ".cfi_def_cfa_offset 32",
+        ".cfi_offset x30, -24",
+        ".cfi_offset x29, -32",
+        "str x18, [sp, 16]",
+        ".cfi_offset x18, -16",
+        "mov x18, x0",
+        "blr x1",
+        "ldr x18, [sp, 16]",
+        "ldp x29, x30, [sp], 32",
+        ".cfi_def_cfa_offset 0",
+        ".cfi_restore x18",
+        ".cfi_restore x30",
+        ".cfi_restore x29",
+        "ret",
+        ".cfi_endproc",
+    );
+
+    extern "C-unwind" {
+        fn scs_enter(scs: *mut usize, f: extern "C-unwind" fn());
+        fn scs_push(f: extern "C-unwind" fn());
+    }
+
+    // The first entry stays null, which ends the shadow call stack for the backtrace.
+    static mut SHADOW_CALL_STACK: [usize; 16] = [0; 16];
+
+    static mut EXCEPTION: MaybeUninit<UnwindException> = MaybeUninit::zeroed();
+
+    static CAUGHT: AtomicBool = AtomicBool::new(false);
+    static RESTORED: AtomicBool = AtomicBool::new(false);
+    static ENTRIES: AtomicUsize = AtomicUsize::new(0);
+
+    struct Thrown;
+
+    // Nothing is allocated, as the allocator is not built with x18 reserved.
+    unsafe impl Exception for Thrown {
+        const CLASS: [u8; 8] = *b"TESTSCS\0";
+
+        fn wrap(_this: Self) -> *mut UnwindException {
+            unsafe { (*addr_of_mut!(EXCEPTION)).as_mut_ptr() }
+        }
+
+        unsafe fn unwrap(_ex: *mut UnwindException) -> Self {
+            Thrown
+        }
+    }
+
+    fn base() -> *mut usize {
+        unsafe { (addr_of_mut!(SHADOW_CALL_STACK) as *mut usize).add(1) }
+    }
+
+    extern "C-unwind" fn throw() {
+        let mut entries = 0;
+        shadow_stack_backtrace(|_| {
+            entries += 1;
+            true
+        });
+        ENTRIES.store(entries, Ordering::Relaxed);
+        panicking::begin_panic(Thrown);
+    }
+
+    extern "C-unwind" fn body() {
+        let caught = panicking::catch_unwind::<Thrown, _, _>(|| unsafe { scs_push(throw) });
+        let x18: usize;
+        unsafe { core::arch::asm!("mov {}, x18", out(reg) x18) };
+        CAUGHT.store(caught.is_err(), Ordering::Relaxed);
+        RESTORED.store(x18 == base() as usize, Ordering::Relaxed);
+    }
+
+    pub fn run() {
+        unsafe { scs_enter(base(), body) };
+        println!(
+            "caught: {} x18 restored: {} entries: {}",
+            CAUGHT.load(Ordering::Relaxed),
+            RESTORED.load(Ordering::Relaxed),
+            ENTRIES.load(Ordering::Relaxed)
+        );
+    }
+}
+
+fn main() {
+    #[cfg(target_arch = "aarch64")]
+    scs::run();
+}
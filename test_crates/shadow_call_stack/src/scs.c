@@ -0,0 +1,7 @@
+// Built with -fsanitize=shadow-call-stack, so the compiler pushes the return address onto the
+// shadow call stack and emits the CFI for x18 itself.
+void scs_push(void (*f)(void)) {
+    f();
+    // Keep the call from becoming a tail call, which wouldn't push anything.
+    __asm__ volatile("" ::: "memory");
+}
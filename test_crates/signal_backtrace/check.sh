@@ -0,0 +1,13 @@
+#!/usr/bin/env bash
+set -o pipefail
+# Unwind tables can't be looked up from a signal handler on ARM.
+case "$CARGO_BUILD_TARGET" in
+    arm*|thumb*) echo "skipped: signal-safe is not supported with ARM EHABI"; exit 0 ;;
+esac
+trap "rm -f run.log" EXIT
+${CARGO:-cargo} run --release $BUILD_STD ${UNWINDING_FEATURES:+--features "unwinding/$UNWINDING_FEATURES"} 2>&1 | tee run.log
+if [ $? -ne 0 ]; then
+    echo process did not exit successfully
+    exit 1
+fi
+grep -P '^backtraces: [1-9]\d* interrupted: [1-9]\d*$' run.log
@@ -0,0 +1,137 @@
+extern crate unwinding;
+
+use std::ffi::c_void;
+use std::os::unix::thread::JoinHandleExt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use unwinding::abi::*;
+
+extern "C" {
+    fn __register_frame(begin: *const u8);
+    fn __deregister_frame(begin: *const u8);
+}
+
+static BACKTRACES: AtomicUsize = AtomicUsize::new(0);
+// Backtraces that went from the handler through the signal frame into `interrupted`.
+static INTERRUPTED: AtomicUsize = AtomicUsize::new(0);
+static DONE: AtomicBool = AtomicBool::new(false);
+
+#[derive(Default)]
+struct Trace {
+    frames: usize,
+    in_handler: bool,
+    in_interrupted: bool,
+}
+
+extern "C" fn handler(_: libc::c_int) {
+    extern "C" fn callback(ctx: &UnwindContext<'_>, arg: *mut c_void) -> UnwindReasonCode {
+        let trace = unsafe { &mut *(arg as *mut Trace) };
+        trace.frames += 1;
+        let start = _Unwind_GetRegionStart(ctx);
+        if start == handler as usize {
+            trace.in_handler = true;
+        } else if start == interrupted as usize && trace.in_handler {
+            trace.in_interrupted = true;
+        }
+        UnwindReasonCode::NO_REASON
+    }
+    let mut trace = Trace::default();
+    _Unwind_Backtrace(callback, &mut trace as *mut _ as _);
+    if trace.frames > 0 {
+        BACKTRACES.fetch_add(1, Ordering::Relaxed);
+    }
+    if trace.in_interrupted {
+        INTERRUPTED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// Spins until the test is done, so that it's interrupted by the signals sent to its thread.
+#[inline(never)]
+extern "C" fn interrupted() {
+    while !DONE.load(Ordering::Relaxed) {
+        std::hint::spin_loop();
+    }
+}
+
+// An `.eh_frame` section with a single FDE covering `pc_begin..pc_begin + pc_range`.
+fn eh_frame(pc_begin: usize, pc_range: usize) -> Vec<u32> {
+    let mut bytes = Vec::new();
+    // CIE: version 1, augmentation "zR", code alignment 1, data alignment -8, return address
+    // register 16, absolute pointer encoding.
+    bytes.extend_from_slice(&16u32.to_ne_bytes());
+    bytes.extend_from_slice(&0u32.to_ne_bytes());
+    bytes.extend_from_slice(&[1, b'z', b'R', 0, 1, 0x78, 16, 1, 0, 0, 0, 0]);
+    // FDE, pointing back to the CIE.
+    let len = 4 + 2 * std::mem::size_of::<usize>() + 4;
+    bytes.extend_from_slice(&(len as u32).to_ne_bytes());
+    bytes.extend_from_slice(&24u32.to_ne_bytes());
+    bytes.extend_from_slice(&pc_begin.to_ne_bytes());
+    bytes.extend_from_slice(&pc_range.to_ne_bytes());
+    bytes.extend_from_slice(&[0, 0, 0, 0]);
+    // Terminator.
+    bytes.extend_from_slice(&0u32.to_ne_bytes());
+    bytes
+        .chunks(4)
+        .map(|x| u32::from_ne_bytes(x.try_into().unwrap()))
+        .collect()
+}
+
+fn main() {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handler as usize;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigaction(libc::SIGUSR1, &action, std::ptr::null_mut());
+        // Fail rather than hang if the unwinder deadlocks.
+        libc::alarm(60);
+    }
+
+    let register = thread::spawn(|| {
+        let code = [0u8; 64];
+        let frame = eh_frame(code.as_ptr() as usize, code.len());
+        while !DONE.load(Ordering::Relaxed) {
+            unsafe {
+                __register_frame(frame.as_ptr() as _);
+                __deregister_frame(frame.as_ptr() as _);
+            }
+        }
+    });
+
+    let dlopen = thread::spawn(|| {
+        while !DONE.load(Ordering::Relaxed) {
+            unsafe {
+                let handle = libc::dlopen(c"libutil.so.1".as_ptr(), libc::RTLD_NOW);
+                if !handle.is_null() {
+                    libc::dlclose(handle);
+                }
+            }
+        }
+    });
+
+    let spin = thread::spawn(|| interrupted());
+
+    let threads = [
+        register.as_pthread_t(),
+        dlopen.as_pthread_t(),
+        spin.as_pthread_t(),
+        unsafe { libc::pthread_self() },
+    ];
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(2) {
+        for &thread in threads.iter() {
+            unsafe { libc::pthread_kill(thread, libc::SIGUSR1) };
+        }
+        thread::sleep(Duration::from_micros(100));
+    }
+
+    DONE.store(true, Ordering::Relaxed);
+    register.join().unwrap();
+    dlopen.join().unwrap();
+    spin.join().unwrap();
+    println!(
+        "backtraces: {} interrupted: {}",
+        BACKTRACES.load(Ordering::Relaxed),
+        INTERRUPTED.load(Ordering::Relaxed)
+    );
+}
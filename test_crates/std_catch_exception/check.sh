@@ -1,7 +1,7 @@
 #!/usr/bin/env bash
 set -o pipefail
 trap "rm -f run.log" EXIT
-${CARGO:-cargo} run --release $BUILD_STD 2>&1 | tee run.log
+${CARGO:-cargo} run --release $BUILD_STD ${UNWINDING_FEATURES:+--features "unwinding/$UNWINDING_FEATURES"} 2>&1 | tee run.log
 if [ $? -ne 134 ]; then
     echo process is not aborted
     exit 1
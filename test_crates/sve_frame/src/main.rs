@@ -0,0 +1,61 @@
+extern crate unwinding;
+
+// A frame that spills an SVE register, described the way LLVM does: the size of the frame, and
+// so the CFA and the save slot of d8, depend on the vector length held by VG.
+#[cfg(target_arch = "aarch64")]
+core::arch::global_asm!(
+    ".arch_extension sve",
+    ".globl sve_frame",
+    "sve_frame:",
+    ".cfi_startproc",
+    "stp x29, x30, [sp, -16]!",
+    ".cfi_def_cfa_offset 16",
+    ".cfi_offset x30, -8",
+    ".cfi_offset x29, -16",
+    "addvl sp, sp, #-2",
+    // DW_CFA_def_cfa_expression: DW_OP_breg31 (sp) 16, DW_OP_bregx 46 (VG) 0, DW_OP_consts 16,
+    // DW_OP_mul, DW_OP_plus
+    ".cfi_escape 0x0f, 0x09, 0x8f, 0x10, 0x92, 0x2e, 0x00, 0x11, 0x10, 0x1e, 0x22",
+    "str z8, [sp, #1, mul vl]",
+    // DW_CFA_expression: d8, DW_OP_bregx 46 (VG) 0, DW_OP_consts -8, DW_OP_mul, DW_OP_plus,
+    // DW_OP_consts -16, DW_OP_plus
+    ".cfi_escape 0x10, 0x48, 0x0a, 0x92, 0x2e, 0x00, 0x11, 0x78, 0x1e, 0x22, 0x11, 0x70, 0x22",
+    "blr x0",
+    "ldr z8, [sp, #1, mul vl]",
+    "addvl sp, sp, #2",
+    ".cfi_def_cfa sp, 16",
+    "ldp x29, x30, [sp], 16",
+    ".cfi_def_cfa_offset 0",
+    ".cfi_restore x30",
+    ".cfi_restore x29",
+    "ret",
+    ".cfi_endproc",
+);
+
+#[cfg(target_arch = "aarch64")]
+extern "C-unwind" {
+    fn sve_frame(f: extern "C-unwind" fn());
+}
+
+#[cfg(target_arch = "aarch64")]
+extern "C-unwind" fn throw() {
+    unwinding::panic::begin_panic(Box::new(42));
+}
+
+#[cfg(target_arch = "aarch64")]
+fn main() {
+    if unsafe { libc::getauxval(libc::AT_HWCAP) } & libc::HWCAP_SVE == 0 {
+        println!("skipped: SVE is not available");
+        return;
+    }
+    let result = unwinding::panic::catch_unwind(|| unsafe { sve_frame(throw) });
+    println!(
+        "caught: {:?}",
+        result.err().and_then(|e| e.downcast::<i32>().ok())
+    );
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+fn main() {
+    println!("skipped: SVE is not available");
+}
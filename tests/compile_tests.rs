@@ -8,13 +8,27 @@ fn main() {
         "throw_and_catch",
         "catch_std_exception",
         "std_catch_exception",
+        "signal_backtrace",
+        "pthread_cancel",
+        "force_unwind",
+        "sve_frame",
+        "shadow_call_stack",
+        "pac_ret",
+        "minimal_profile",
+        "compact_table",
     ];
 
+    // Extra features of `unwinding` to run each test crate with.
+    let features = ["", "cfi-minimal"];
+
     for test in tests {
-        let status = Command::new("./check.sh")
-            .current_dir(format!("{dir}/test_crates/{test}"))
-            .status()
-            .unwrap();
-        assert!(status.success());
+        for features in features {
+            let status = Command::new("./check.sh")
+                .current_dir(format!("{dir}/test_crates/{test}"))
+                .env("UNWINDING_FEATURES", features)
+                .status()
+                .unwrap();
+            assert!(status.success(), "{test} failed with features {features:?}");
+        }
     }
 }